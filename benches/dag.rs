@@ -0,0 +1,108 @@
+//! Throughput of the contract under `WasmiRuntime` as players and DAG depth
+//! grow. Run with `cargo bench --bench dag`.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+mod report;
+
+use std::time::Instant;
+
+use p2pio::gossip::{local_dag, GossipNode, LocalNode, NullTransport};
+
+use rustdag_lib::{
+    dag::contract::ContractValue::U64,
+    security::keys::eddsa::{get_address, get_public_key, new_key_pair, EdDSAKeyPair},
+};
+
+use common::contract_source;
+use report::report;
+
+const PLAYER_COUNTS: [usize; 4] = [1, 10, 50, 100];
+/// Turns each player makes per player count
+const TURNS: usize = 5;
+/// Transactions replicated per measurement of commit cost
+const COMMIT_CHUNK: usize = 100;
+
+fn address(key: &EdDSAKeyPair) -> u64 {
+    get_address(&get_public_key(key))
+}
+
+/// Spawn point for the `i`th player, far enough from the others that they
+/// don't run into each other
+fn spawn_point(i: usize) -> (u64, u64) {
+    let line = |cells: usize| u64::from(u32::MAX) + (cells as u64) * 40;
+    (line(10 + (i % 20) * 14), line(10 + (i / 20) * 14))
+}
+
+fn main() {
+    let _ = simple_logger::init_with_level(log::Level::Warn);
+
+    for &players in PLAYER_COUNTS.iter() {
+        let contract_key = new_key_pair();
+        let contract_id = address(&contract_key);
+        let keys = (0..players).map(|_| new_key_pair()).collect::<Vec<_>>();
+        let mut node = GossipNode::new(local_dag(address(&keys[0])), NullTransport);
+        node.deploy(&contract_key, contract_source()).unwrap();
+
+        let start = Instant::now();
+        for (i, key) in keys.iter().enumerate() {
+            let (x, y) = spawn_point(i);
+            node.execute(key, contract_id, "spawn_player", &[U64(x), U64(y)])
+                .unwrap();
+        }
+        report(
+            &format!("spawn_player ({} players)", players),
+            players,
+            start.elapsed(),
+        );
+
+        // Alternating up and right, so nobody turns back into their trail
+        let start = Instant::now();
+        for turn in 0..TURNS {
+            let heading = if turn % 2 == 0 { 0 } else { 3 };
+            for key in keys.iter() {
+                node.execute(key, contract_id, "apply_input", &[U64(heading)])
+                    .unwrap();
+            }
+        }
+        report(
+            &format!("apply_input ({} players)", players),
+            players * TURNS,
+            start.elapsed(),
+        );
+
+        let start = Instant::now();
+        for key in keys.iter() {
+            for getter in ["get_player_x", "get_player_y", "get_player_heading"].iter() {
+                node.query(key, contract_id, getter, &[U64(address(key))])
+                    .unwrap();
+            }
+        }
+        report(
+            &format!("player getters ({} players)", players),
+            players * 3,
+            start.elapsed(),
+        );
+
+        // Replaying the history into a fresh replica, a chunk at a time, shows
+        // how executing and committing grows with the depth of the DAG
+        let history = node.history();
+        let mut replica = GossipNode::new(local_dag(address(&keys[0])), NullTransport);
+        for (i, chunk) in history.chunks(COMMIT_CHUNK).enumerate() {
+            let start = Instant::now();
+            for trans in chunk {
+                replica.replicate(trans.clone());
+            }
+            let depth = i * COMMIT_CHUNK;
+            report(
+                &format!(
+                    "execute + commit (depth {}..{})",
+                    depth,
+                    depth + chunk.len()
+                ),
+                chunk.len(),
+                start.elapsed(),
+            );
+        }
+    }
+}
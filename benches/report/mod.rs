@@ -0,0 +1,16 @@
+//! Output shared by the benchmarks, so their results line up when run
+//! together
+
+use std::time::Duration;
+
+/// Print throughput and cost per operation for `ops` operations that took
+/// `elapsed` in all
+pub fn report(name: &str, ops: usize, elapsed: Duration) {
+    let secs = elapsed.as_secs_f64();
+    println!(
+        "{:<44} {:>10.1} ops/sec {:>10.1} us/op",
+        name,
+        ops as f64 / secs,
+        secs * 1e6 / ops as f64
+    );
+}
@@ -0,0 +1,74 @@
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+/// The client's config.json, kept as loose JSON so fields this tool doesn't
+/// know about are written back untouched
+pub struct Config {
+    json: Value,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let json = serde_json::from_str(&text)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+        Ok(Config { json })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut text = serde_json::to_string_pretty(&self.json).expect("JSON values serialize");
+        text.push('\n');
+        fs::write(path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn contract(&self) -> Option<u64> {
+        self.json["contractID"].as_str()?.parse().ok()
+    }
+
+    /// The client expects the id as a string, since it doesn't fit in a
+    /// JavaScript number
+    pub fn set_contract(&mut self, contract: u64) {
+        self.json["contractID"] = Value::String(contract.to_string());
+    }
+
+    pub fn nodes(&self) -> Vec<String> {
+        match self.json["nodes"].as_array() {
+            Some(nodes) => nodes
+                .iter()
+                .filter_map(|node| node.as_str().map(String::from))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_contract_keeps_other_fields() {
+        let path = std::env::temp_dir().join("p2pio-cli-config.json");
+        fs::write(
+            &path,
+            r#"{"dev": true, "contractID": "1", "nodes": ["http://localhost:8090"], "consts": {"SPEED": 5}}"#,
+        )
+        .unwrap();
+
+        let mut config = Config::load(&path).unwrap();
+        assert_eq!(config.contract(), Some(1));
+        config.set_contract(7382977913628775120);
+        config.save(&path).unwrap();
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.contract(), Some(7382977913628775120));
+        assert_eq!(config.nodes(), vec!["http://localhost:8090".to_string()]);
+        assert_eq!(config.json["consts"]["SPEED"], 5);
+        let keys = config.json.as_object().unwrap().keys().collect::<Vec<_>>();
+        assert_eq!(keys, vec!["dev", "contractID", "nodes", "consts"]);
+        fs::remove_file(&path).unwrap();
+    }
+}
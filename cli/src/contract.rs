@@ -0,0 +1,55 @@
+use std::path::Path;
+use std::process::Command;
+
+use p2pio::tools::cargo;
+
+/// Deploy `wasm` to the node at `url` as `key_path`, through rustdag's own
+/// CLI, and return the contract's id
+pub fn deploy(root: &Path, url: &str, key_path: &Path, wasm: &Path) -> Result<u64, String> {
+    let output = Command::new(cargo())
+        .arg("run")
+        .arg("--quiet")
+        .arg("--manifest-path")
+        .arg(root.join("rustdag/cli/Cargo.toml"))
+        .arg("--")
+        .arg(format!("-s={}", url))
+        .arg("deploy")
+        .arg(key_path)
+        .arg(wasm)
+        .output()
+        .map_err(|e| format!("Failed to run the rustdag cli: {}", e))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        return Err(format!(
+            "Deploy failed: {}{}",
+            stdout,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    contract_id(&stdout).ok_or_else(|| format!("No contract id in deploy output: {}", stdout))
+}
+
+/// Pick the id out of the rustdag CLI's "Contract ID" line
+fn contract_id(output: &str) -> Option<u64> {
+    output
+        .lines()
+        .find(|line| line.contains("Contract ID"))?
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|word| !word.is_empty())
+        .last()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contract_id_from_output() {
+        let output = "Deploying contract.\nContract ID: 7382977913628775120\n";
+        assert_eq!(contract_id(output), Some(7382977913628775120));
+        assert_eq!(contract_id("Contract ID: \"42\""), Some(42));
+        assert_eq!(contract_id("Deployed."), None);
+    }
+}
@@ -0,0 +1,12 @@
+use std::fs;
+use std::path::Path;
+
+use rustdag_lib::security::keys::eddsa::EdDSAKeyPair;
+
+/// Read a key written by `rustdag key generate`
+pub fn load_key(path: &Path) -> Result<EdDSAKeyPair, String> {
+    let bytes =
+        fs::read(path).map_err(|e| format!("Failed to read key {}: {}", path.display(), e))?;
+    EdDSAKeyPair::from_pkcs8(&bytes)
+        .map_err(|e| format!("Failed to load key {}: {:?}", path.display(), e))
+}
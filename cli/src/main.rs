@@ -0,0 +1,457 @@
+//! Builds, deploys and administers a p2pio contract on a rustdag node
+
+mod config;
+mod contract;
+mod key;
+
+use std::path::{Path, PathBuf};
+
+use clap::{value_t, App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use p2pio::tools::{build_contract, Backend, Remote};
+use p2pio_contract::{error::ContractError, heading::PlayerHeading, GRID_ORIGIN};
+
+use rustdag_lib::{
+    dag::contract::ContractValue,
+    security::keys::eddsa::{new_key_pair, EdDSAKeyPair},
+};
+
+use config::Config;
+use key::load_key;
+
+fn game_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("game")
+        .long("game")
+        .short("g")
+        .takes_value(true)
+        .default_value("0")
+        .help("Game to act on")
+}
+
+fn player_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("player")
+        .required(true)
+        .help("Address of the player")
+}
+
+fn main() {
+    let matches = App::new("p2pio")
+        .about("Builds, deploys and administers a p2pio contract")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .takes_value(true)
+                .default_value("config.json")
+                .global(true)
+                .help("Client config to read the node and contract from"),
+        )
+        .arg(
+            Arg::with_name("node")
+                .long("node")
+                .short("n")
+                .takes_value(true)
+                .global(true)
+                .help("URL of a rustdag node. Defaults to the first node in the config."),
+        )
+        .arg(
+            Arg::with_name("contract")
+                .long("contract")
+                .short("c")
+                .takes_value(true)
+                .global(true)
+                .help("Address of the contract. Defaults to contractID in the config."),
+        )
+        .arg(
+            Arg::with_name("key")
+                .long("key")
+                .short("k")
+                .takes_value(true)
+                .global(true)
+                .help("Key to sign calls with. Defaults to a new key."),
+        )
+        .arg(
+            Arg::with_name("root")
+                .long("root")
+                .takes_value(true)
+                .default_value(".")
+                .global(true)
+                .help("Root of the p2pio repository"),
+        )
+        .subcommand(SubCommand::with_name("build").about("Build the contract's wasm module"))
+        .subcommand(
+            SubCommand::with_name("deploy")
+                .about("Deploy the contract and write its id to the config")
+                .arg(
+                    Arg::with_name("wasm")
+                        .long("wasm")
+                        .takes_value(true)
+                        .help("Module to deploy. Builds the contract if not given."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("create-game")
+                .about("Open a new game and print its id")
+                .arg(
+                    Arg::with_name("round-length")
+                        .long("round-length")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Ticks each round lasts, or 0 for rounds that never end"),
+                )
+                .arg(
+                    Arg::with_name("max-players")
+                        .long("max-players")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Players alive at once, or 0 for the default"),
+                )
+                .arg(
+                    Arg::with_name("teams")
+                        .long("teams")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Teams to split players into, or 0 for none"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("configure")
+                .about("Change a game's settings")
+                .arg(game_arg())
+                .arg(
+                    Arg::with_name("round-length")
+                        .long("round-length")
+                        .takes_value(true)
+                        .help("Ticks each round lasts, or 0 for rounds that never end"),
+                )
+                .arg(
+                    Arg::with_name("max-players")
+                        .long("max-players")
+                        .takes_value(true)
+                        .help("Players alive at once, or 0 for the default"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("reset-round")
+                .about("End a game's round now and start the next one on a clear board")
+                .arg(game_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("pause")
+                .about("Stop players spawning and steering in a game")
+                .arg(game_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("resume")
+                .about("Let players back into a paused game")
+                .arg(game_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("kick")
+                .about("Kill a player, who can spawn again straight away")
+                .arg(player_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("ban")
+                .about("Kill a player and keep them out of every game")
+                .arg(player_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("unban")
+                .about("Let a banned player play again")
+                .arg(player_arg()),
+        )
+        .subcommand(SubCommand::with_name("owner").about("Print the contract owner's address"))
+        .subcommand(
+            SubCommand::with_name("transfer-ownership")
+                .about("Hand the contract over to another address")
+                .arg(
+                    Arg::with_name("owner")
+                        .required(true)
+                        .help("Address of the new owner"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("call")
+                .about("Call any entry point and print what it returns")
+                .arg(Arg::with_name("function").required(true))
+                .arg(
+                    Arg::with_name("args")
+                        .multiple(true)
+                        .allow_hyphen_values(true)
+                        .help("Integer arguments"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("players")
+                .about("List a game's leaderboard and how many players are alive")
+                .arg(game_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("positions")
+                .about("Dump where a game's live players are and where they're heading")
+                .arg(game_arg()),
+        )
+        .get_matches();
+
+    let _ = simple_logger::init_with_level(log::Level::Warn);
+    let (command, args) = matches.subcommand();
+    let args = args.expect("A subcommand is required");
+    if let Err(e) = run(command, args) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(command: &str, args: &ArgMatches) -> Result<(), String> {
+    let root = PathBuf::from(args.value_of("root").unwrap());
+    let config_path = root.join(args.value_of("config").unwrap());
+    match command {
+        "build" => {
+            println!("{}", build_contract(&root)?.display());
+            Ok(())
+        }
+        "deploy" => deploy(&root, &config_path, args),
+        _ => {
+            let mut node = connect(&config_path, args)?;
+            let key = match args.value_of("key") {
+                Some(path) => load_key(Path::new(path))?,
+                None => new_key_pair(),
+            };
+            match command {
+                "create-game" => create_game(&mut node, &key, args),
+                "configure" => configure(&mut node, &key, args),
+                "reset-round" => {
+                    admin_call(&mut node, &key, "reset_round", &[value(args, "game")?])
+                }
+                "pause" => admin_call(&mut node, &key, "pause_game", &[value(args, "game")?]),
+                "resume" => admin_call(&mut node, &key, "resume_game", &[value(args, "game")?]),
+                "kick" => admin_call(&mut node, &key, "kick_player", &[value(args, "player")?]),
+                "ban" => admin_call(&mut node, &key, "ban_player", &[value(args, "player")?]),
+                "unban" => admin_call(&mut node, &key, "unban_player", &[value(args, "player")?]),
+                "transfer-ownership" => admin_call(
+                    &mut node,
+                    &key,
+                    "transfer_ownership",
+                    &[value(args, "owner")?],
+                ),
+                "owner" => {
+                    node.sync()?;
+                    println!("{}", node.query("get_owner", &[])? as u64);
+                    Ok(())
+                }
+                "call" => call(&mut node, &key, args),
+                "players" => players(&mut node, args),
+                "positions" => positions(&mut node, args),
+                _ => unreachable!("Unknown subcommand {}", command),
+            }
+        }
+    }
+}
+
+/// Node and contract from the arguments, falling back to the config
+fn connect(config_path: &Path, args: &ArgMatches) -> Result<Remote, String> {
+    let config = Config::load(config_path).ok();
+    let url = match args.value_of("node") {
+        Some(url) => url.to_string(),
+        None => config
+            .as_ref()
+            .and_then(|config| config.nodes().into_iter().next())
+            .ok_or("No --node given, and no nodes in the config")?,
+    };
+    let contract = if args.is_present("contract") {
+        value_t!(args, "contract", u64).map_err(|e| e.to_string())?
+    } else {
+        config
+            .as_ref()
+            .and_then(Config::contract)
+            .ok_or("No --contract given, and no contractID in the config")?
+    };
+    Ok(Remote::new(&url, contract))
+}
+
+/// Integer argument, which may be a signed value or an address past the
+/// range of `i64`
+fn value(args: &ArgMatches, name: &str) -> Result<ContractValue, String> {
+    let value = value_t!(args, name, i64)
+        .map(|value| value as u64)
+        .or_else(|_| value_t!(args, name, u64))
+        .map_err(|e| e.to_string())?;
+    Ok(ContractValue::U64(value))
+}
+
+/// Make an owner only call, failing with the contract's error code
+fn admin_call(
+    node: &mut Remote,
+    key: &EdDSAKeyPair,
+    func_name: &str,
+    args: &[ContractValue],
+) -> Result<(), String> {
+    match node.call(key, func_name, args)? {
+        Some(ContractValue::U64(0)) => Ok(()),
+        Some(ContractValue::U64(code)) if code as i64 == ContractError::NotOwner.code() => {
+            Err(format!(
+                "{} is owner only. Pass the owner's key with --key.",
+                func_name
+            ))
+        }
+        Some(ContractValue::U64(code)) => {
+            Err(format!("{} failed with error code {}", func_name, code))
+        }
+        _ => Err(format!("{} didn't return a code", func_name)),
+    }
+}
+
+fn deploy(root: &Path, config_path: &Path, args: &ArgMatches) -> Result<(), String> {
+    let key = args
+        .value_of("key")
+        .ok_or("Deploying needs the contract's --key")?;
+    let mut config = Config::load(config_path)?;
+    let url = match args.value_of("node") {
+        Some(url) => url.to_string(),
+        None => config
+            .nodes()
+            .into_iter()
+            .next()
+            .ok_or("No --node given, and no nodes in the config")?,
+    };
+    let wasm = match args.value_of("wasm") {
+        Some(wasm) => PathBuf::from(wasm),
+        None => build_contract(root)?,
+    };
+    let contract = contract::deploy(root, &url, Path::new(key), &wasm)?;
+    config.set_contract(contract);
+    config.save(config_path)?;
+    println!("Contract ID: {}", contract);
+    println!("Written to {}", config_path.display());
+    Ok(())
+}
+
+fn create_game(node: &mut Remote, key: &EdDSAKeyPair, args: &ArgMatches) -> Result<(), String> {
+    let game_args = [
+        value(args, "round-length")?,
+        value(args, "max-players")?,
+        value(args, "teams")?,
+    ];
+    // Refusals are negated error codes
+    match node.call(key, "create_game", &game_args)? {
+        Some(ContractValue::U64(game)) if game as i64 >= 0 => {
+            println!("Game {}", game);
+            Ok(())
+        }
+        Some(ContractValue::U64(code)) if -(code as i64) == ContractError::NotOwner.code() => {
+            Err("create_game is owner only. Pass the owner's key with --key.".to_string())
+        }
+        _ => Err("The contract didn't open the game".to_string()),
+    }
+}
+
+fn configure(node: &mut Remote, key: &EdDSAKeyPair, args: &ArgMatches) -> Result<(), String> {
+    let game = value(args, "game")?;
+    node.sync()?;
+    // Settings left out keep their current values
+    let round_length = match args.value_of("round-length") {
+        Some(_) => value(args, "round-length")?,
+        None => ContractValue::U64(node.query("get_round_length", &[game])? as u64),
+    };
+    let max_players = match args.value_of("max-players") {
+        Some(_) => value(args, "max-players")?,
+        None => ContractValue::U64(node.query("get_max_players", &[game])? as u64),
+    };
+    admin_call(node, key, "configure", &[game, round_length, max_players])?;
+    println!(
+        "Round length {}, up to {} players",
+        node.query("get_round_length", &[game])?,
+        node.query("get_max_players", &[game])?
+    );
+    Ok(())
+}
+
+fn call(node: &mut Remote, key: &EdDSAKeyPair, args: &ArgMatches) -> Result<(), String> {
+    let func_name = args.value_of("function").unwrap();
+    let call_args = args
+        .values_of("args")
+        .into_iter()
+        .flatten()
+        .map(|arg| {
+            arg.parse::<i64>()
+                .map(|value| ContractValue::U64(value as u64))
+                .map_err(|e| format!("Bad argument {}: {}", arg, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    match node.call(key, func_name, &call_args)? {
+        Some(ContractValue::U64(value)) => println!("{}", value as i64),
+        Some(value) => println!("{:?}", value),
+        None => println!("Nothing returned"),
+    }
+    Ok(())
+}
+
+/// Addresses of a game's leaderboard, best first
+fn ranked(node: &mut Remote, game: ContractValue) -> Result<Vec<i64>, String> {
+    node.sync()?;
+    let size = node.query("get_leaderboard_size", &[game])?;
+    (0..size)
+        .map(|rank| node.query("get_leaderboard", &[game, ContractValue::U64(rank as u64)]))
+        .collect()
+}
+
+/// Addresses of every live player in a game, in no particular order
+fn live(node: &mut Remote, game: ContractValue) -> Result<Vec<i64>, String> {
+    node.sync()?;
+    let count = node.query("get_player_count", &[game])?;
+    (0..count)
+        .map(|index| node.query("get_live_player", &[game, ContractValue::U64(index as u64)]))
+        .collect()
+}
+
+fn name(node: &mut Remote, id: ContractValue) -> Result<String, String> {
+    let len = node.query("get_player_name_len", &[id])? as usize;
+    let mut bytes = Vec::new();
+    for word in 0..3 {
+        let word = node.query("get_player_name_word", &[id, ContractValue::U64(word)])?;
+        bytes.extend_from_slice(&word.to_le_bytes());
+    }
+    bytes.truncate(len);
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn players(node: &mut Remote, args: &ArgMatches) -> Result<(), String> {
+    let game = value(args, "game")?;
+    println!(
+        "{:>4} {:>20} {:<24} {:>6} {:>5} {:>4}",
+        "rank", "address", "name", "area", "kills", "team"
+    );
+    for (rank, id) in ranked(node, game)?.into_iter().enumerate() {
+        let id_arg = ContractValue::U64(id as u64);
+        println!(
+            "{:>4} {:>20} {:<24} {:>6} {:>5} {:>4}",
+            rank + 1,
+            id as u64,
+            name(node, id_arg)?,
+            node.query("get_player_area", &[id_arg])?,
+            node.query("get_player_kills", &[id_arg])?,
+            node.query("get_player_team", &[id_arg])?
+        );
+    }
+    println!("{} players alive", node.query("get_player_count", &[game])?);
+    Ok(())
+}
+
+fn positions(node: &mut Remote, args: &ArgMatches) -> Result<(), String> {
+    let game = value(args, "game")?;
+    let ids = live(node, game)?;
+    println!("tick {}", node.query("get_current_game_tick", &[game])?);
+    for id in ids {
+        let id_arg = ContractValue::U64(id as u64);
+        // Offset like the client's coordinates, from the top left corner
+        println!(
+            "{:>20} x {:>6} y {:>6} {:?}",
+            id as u64,
+            node.query("get_player_x", &[id_arg])? - GRID_ORIGIN,
+            node.query("get_player_y", &[id_arg])? - GRID_ORIGIN,
+            PlayerHeading::from(node.query("get_player_heading", &[id_arg])?)
+        );
+    }
+    Ok(())
+}
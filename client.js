@@ -15,18 +15,24 @@ rust
     } else {
       contractID = urlParams.get("id");
     }
-    return new m.Context("http://localhost:8090", contractID);
+    var nodes = urlParams.has("nodes") ? urlParams.get("nodes").split(",") : config.nodes;
+    return new m.Context(nodes, contractID);
   })
   .then(main)
   .catch(console.error);
 
 
 async function main(rctx) {
+    var urlParams = new URLSearchParams(window.location.search);
     window.$ = window.jQuery = require("jquery");
     var err = $("#error");
     err.text("Loading... Please wait");
 	console.log("Syncing tips");
 	await rctx.tips_sync();
+	if (!(await rctx.subscribe())) console.log("No subscription endpoint, polling for events");
+
+    if (urlParams.has("game")) await rctx.join_game(urlParams.get("game"));
+    if (urlParams.has("team")) rctx.join_team(Number(urlParams.get("team")));
 
     var client = require("./src/game-client");
     client.giveContext(rctx);
@@ -54,6 +60,8 @@ async function main(rctx) {
                   newHeading = 2; break; //DOWN (S)
               case "a": case "ArrowLeft":
                   newHeading = 3; break; //LEFT (A)
+              case " ":
+                  client.activateBoost(); return; //BOOST (Space)
               default: return; //Exit handler for other keys
           }
           client.changeHeading(newHeading);
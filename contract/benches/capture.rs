@@ -0,0 +1,61 @@
+//! Cost of capturing territory, the flood fill the JS client used to do,
+//! now that it runs in the contract. Runs natively against the in-process
+//! host, so it measures the game rules rather than the wasm runtime. Run
+//! with `cargo bench --features mock-host`.
+
+#[path = "../../benches/report/mod.rs"]
+mod report;
+
+use std::time::{Duration, Instant};
+
+use p2pio_contract::heading::PlayerHeading;
+use p2pio_contract::{P2PIOContract, TestHost, CELL_WIDTH, GRID_ORIGIN};
+
+use report::report;
+
+/// Sides of the square loops captured, in cells. Multiples of 3 keep each
+/// leg a whole number of seconds, so timestamps land exactly on the tick.
+const LOOP_SIDES: [i64; 4] = [3, 12, 30, 60];
+const REPEATS: usize = 20;
+const START: i64 = 1_600_000_000_000;
+
+/// Walk a `side` cell square out of the spawn block and back into it.
+/// Returns how long the turn that closed the loop took.
+fn capture_loop(side: i64) -> Duration {
+    let host = TestHost::new();
+    host.at(START).sender(1);
+    let mut contract = P2PIOContract::default();
+    let game = contract.create_game(0, 0, 0);
+    contract.join_game(game);
+    let corner = GRID_ORIGIN + 100 * CELL_WIDTH;
+    contract.spawn_player(corner, corner);
+
+    let leg_ms = side * CELL_WIDTH * 1000 / 60;
+    for heading in [PlayerHeading::Right, PlayerHeading::Up, PlayerHeading::Left].iter() {
+        contract.apply_input(heading.as_i64());
+        host.wait(leg_ms);
+    }
+    contract.apply_input(PlayerHeading::Down.as_i64());
+    host.wait(leg_ms);
+
+    let start = Instant::now();
+    contract.apply_input(PlayerHeading::Right.as_i64());
+    let elapsed = start.elapsed();
+    assert!(
+        contract.get_player_area(1) >= side * side,
+        "Loop of {} cells wasn't captured",
+        side
+    );
+    elapsed
+}
+
+fn main() {
+    for &side in LOOP_SIDES.iter() {
+        let elapsed = (0..REPEATS).map(|_| capture_loop(side)).sum();
+        report(
+            &format!("capture {0}x{0} loop ({1} cells)", side, side * side),
+            REPEATS,
+            elapsed,
+        );
+    }
+}
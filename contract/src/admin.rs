@@ -0,0 +1,41 @@
+use crate::host::{GlobalI64, Mapping};
+use crate::index::{BANNED_MAPPING_INDEX, OWNER_INDEX};
+
+/// Who runs the contract. The owner starts out as the deployer, and is the
+/// only one who can reconfigure games or keep players out of them. Shared by
+/// every game in the contract.
+pub struct Admin {
+    owner: GlobalI64,
+    banned_mapping: Mapping,
+}
+
+impl Default for Admin {
+    fn default() -> Self {
+        Admin {
+            owner: GlobalI64(OWNER_INDEX),
+            banned_mapping: Mapping(BANNED_MAPPING_INDEX),
+        }
+    }
+}
+
+impl Admin {
+    pub fn get_owner(&self) -> i64 {
+        self.owner.get()
+    }
+
+    pub fn is_owner(&self, id: i64) -> bool {
+        self.get_owner() == id
+    }
+
+    pub fn set_owner(&mut self, id: i64) {
+        self.owner.set(id);
+    }
+
+    pub fn is_banned(&self, id: i64) -> bool {
+        self.banned_mapping.get(id) != 0
+    }
+
+    pub fn set_banned(&mut self, id: i64, banned: bool) {
+        self.banned_mapping.set(id, banned as i64);
+    }
+}
@@ -0,0 +1,52 @@
+/// Why an entry point didn't do what it was asked. Entry points return the
+/// code, or 0 when the call went through.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ContractError {
+    /// Timestamped before the round started
+    StaleTimestamp = 1,
+    /// Timestamped too far ahead of the latest call in the game
+    FutureTimestamp,
+    /// The game isn't taking this call in its current phase
+    NotAccepting,
+    /// The sender isn't alive in their game
+    NotAlive,
+    InvalidHeading,
+    /// The sender's last boost is still cooling down
+    BoostCoolingDown,
+    /// Only the contract's owner can make this call
+    NotOwner,
+    /// The sender has been banned by the owner
+    Banned,
+    /// The owner has paused the game
+    Paused,
+    /// The sender is already alive, and can only spawn again once dead
+    AlreadyAlive,
+}
+
+impl ContractError {
+    pub fn code(&self) -> i64 {
+        (*self as u8) as i64
+    }
+}
+
+/// Code an entry point returns for `result`
+pub fn result_code(result: Result<(), ContractError>) -> i64 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => e.code(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_distinct_from_success() {
+        assert_eq!(result_code(Ok(())), 0);
+        assert_eq!(result_code(Err(ContractError::StaleTimestamp)), 1);
+        assert_eq!(ContractError::BoostCoolingDown.code(), 6);
+        assert_eq!(ContractError::Paused.code(), 9);
+        assert_eq!(ContractError::AlreadyAlive.code(), 10);
+    }
+}
@@ -0,0 +1,536 @@
+use crate::index::{
+    game_index, FROZEN_TICK_INDEX, GAME_COUNT_INDEX, LATEST_TIME_INDEX, MAX_GAMES, MAX_PLAYERS_INDEX, PAUSED_AT_INDEX,
+    PAUSED_INDEX, PAUSED_TOTAL_INDEX, PHASE_INDEX, PLAYER_GAME_MAPPING_INDEX, ROUND_INDEX,
+    ROUND_LENGTH_INDEX, START_TIME_INDEX, TEAM_COUNT_INDEX, WINNER_INDEX, WINNING_TEAM_INDEX,
+};
+use crate::error::ContractError;
+use crate::host::{api_get_timestamp, GlobalI64, Mapping};
+use crate::movement::{ms_delta_to_tick, MAX_FUTURE_MS};
+use crate::score::{Leaderboard, Roster};
+use crate::team::{Teams, MAX_TEAMS};
+
+/// Live players allowed in a game unless it was created with another limit,
+/// matching `MAX_PLAYERS` in config.json
+pub const DEFAULT_MAX_PLAYERS: i64 = 70;
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Phase {
+    /// Players can spawn but the clock is stopped
+    Lobby = 0,
+    Running,
+    /// The winner has been declared and the board is frozen
+    Finished,
+}
+
+impl From<i64> for Phase {
+    fn from(value: i64) -> Self {
+        match value {
+            0 => Phase::Lobby,
+            1 => Phase::Running,
+            2 => Phase::Finished,
+            _ => std::process::abort(),
+        }
+    }
+}
+
+impl From<Phase> for i64 {
+    fn from(value: Phase) -> Self {
+        value.as_i64()
+    }
+}
+
+impl Phase {
+    pub fn as_i64(&self) -> i64 {
+        (*self as u8) as i64
+    }
+}
+
+/// Round and phase of one game.
+///
+/// The game tick counts from the start of the current round and only moves
+/// while the round is running. Outside of that it stays at the tick the last
+/// round ended on, so nothing moves in the lobby or after the winner is
+/// declared. Time spent paused doesn't count either, so players stop where
+/// they are for the pause and carry on from there.
+pub struct Game {
+    id: i64,
+    start_time: GlobalI64,
+    round: GlobalI64,
+    phase: GlobalI64,
+    round_length: GlobalI64,
+    frozen_tick: GlobalI64,
+    winner: GlobalI64,
+    max_players: GlobalI64,
+    team_count: GlobalI64,
+    winning_team: GlobalI64,
+    paused: GlobalI64,
+    /// Time the current pause started
+    paused_at: GlobalI64,
+    /// Milliseconds the current round spent paused, up to the current pause
+    paused_total: GlobalI64,
+    /// Latest timestamp of a call the game accepted, which calls may only
+    /// run so far ahead of
+    latest_time: GlobalI64,
+}
+
+impl Game {
+    pub fn load(id: i64) -> Self {
+        Game {
+            id,
+            start_time: GlobalI64(game_index(id, START_TIME_INDEX)),
+            round: GlobalI64(game_index(id, ROUND_INDEX)),
+            phase: GlobalI64(game_index(id, PHASE_INDEX)),
+            round_length: GlobalI64(game_index(id, ROUND_LENGTH_INDEX)),
+            frozen_tick: GlobalI64(game_index(id, FROZEN_TICK_INDEX)),
+            winner: GlobalI64(game_index(id, WINNER_INDEX)),
+            max_players: GlobalI64(game_index(id, MAX_PLAYERS_INDEX)),
+            team_count: GlobalI64(game_index(id, TEAM_COUNT_INDEX)),
+            winning_team: GlobalI64(game_index(id, WINNING_TEAM_INDEX)),
+            paused: GlobalI64(game_index(id, PAUSED_INDEX)),
+            paused_at: GlobalI64(game_index(id, PAUSED_AT_INDEX)),
+            paused_total: GlobalI64(game_index(id, PAUSED_TOTAL_INDEX)),
+            latest_time: GlobalI64(game_index(id, LATEST_TIME_INDEX)),
+        }
+    }
+
+    /// Start the first round straight away. A `max_players` of 0 uses the
+    /// default limit, and a `team_count` of 0 makes it every player for
+    /// themselves.
+    pub fn init(&mut self, round_length: i64, max_players: i64, team_count: i64) {
+        self.round.set(1);
+        self.set_round_length(round_length);
+        self.set_max_players(max_players);
+        self.team_count.set(team_count.max(0).min(MAX_TEAMS));
+        self.start();
+    }
+
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    pub fn get_start_time(&self) -> i64 {
+        self.start_time.get()
+    }
+
+    pub fn get_round(&self) -> i64 {
+        self.round.get()
+    }
+
+    pub fn get_phase(&self) -> Phase {
+        Phase::from(self.phase.get())
+    }
+
+    /// Length of a round in ticks, or 0 if rounds never run out
+    pub fn get_round_length(&self) -> i64 {
+        self.round_length.get()
+    }
+
+    pub fn set_round_length(&mut self, ticks: i64) {
+        self.round_length.set(ticks.max(0));
+    }
+
+    pub fn get_max_players(&self) -> i64 {
+        self.max_players.get()
+    }
+
+    /// Limit the live players, or go back to the default limit if
+    /// `max_players` is 0. Players already alive past the limit stay alive.
+    pub fn set_max_players(&mut self, max_players: i64) {
+        self.max_players.set(if max_players > 0 {
+            max_players
+        } else {
+            DEFAULT_MAX_PLAYERS
+        });
+    }
+
+    /// Milliseconds the round spent paused, not counting a pause still going
+    pub fn get_paused_ms(&self) -> i64 {
+        self.paused_total.get()
+    }
+
+    /// Whether the owner has stopped players spawning and steering
+    pub fn is_paused(&self) -> bool {
+        self.paused.get() != 0
+    }
+
+    /// Whether a call timestamped `ms` falls in the current pause. One made
+    /// before the pause started was made while the game ran, whichever of
+    /// the two is committed first.
+    pub fn is_paused_at(&self, ms: i64) -> bool {
+        self.is_paused() && ms >= self.paused_at.get()
+    }
+
+    /// Stop the clock. Returns false if the game was already paused.
+    pub fn pause(&mut self) -> bool {
+        if self.is_paused() {
+            return false;
+        }
+        self.paused.set(1);
+        self.paused_at.set(api_get_timestamp());
+        self.observe();
+        true
+    }
+
+    /// Start the clock again from where it stopped. Returns false if the
+    /// game wasn't paused.
+    pub fn resume(&mut self) -> bool {
+        if !self.is_paused() {
+            return false;
+        }
+        // An owner whose clock is behind the pause can't wind the game back
+        let paused = (api_get_timestamp() - self.paused_at.get()).max(0);
+        self.paused_total.set(self.paused_total.get() + paused);
+        self.paused.set(0);
+        self.observe();
+        true
+    }
+
+    /// Milliseconds the round has been running for, not counting pauses.
+    /// The clock stops at the pause, but a call from before it still reads
+    /// its own time.
+    fn elapsed_ms(&self) -> i64 {
+        let now = if self.is_paused() {
+            self.paused_at.get().min(api_get_timestamp())
+        } else {
+            api_get_timestamp()
+        };
+        now.saturating_sub(self.get_start_time())
+            .saturating_sub(self.paused_total.get())
+    }
+
+    /// Whether the game has as many live players as it allows
+    pub fn is_full(&self) -> bool {
+        Roster::load(self.id).len() >= self.get_max_players()
+    }
+
+    /// Number of teams players are split into, or 0 if there are none
+    pub fn get_team_count(&self) -> i64 {
+        self.team_count.get()
+    }
+
+    /// Winner of the last finished round, or 0 if nobody was alive
+    pub fn get_winner(&self) -> i64 {
+        self.winner.get()
+    }
+
+    /// Team that won the last finished round, or 0 if the game has no teams
+    /// or nobody was alive
+    pub fn get_winning_team(&self) -> i64 {
+        self.winning_team.get()
+    }
+
+    pub fn get_current_tick(&self) -> i64 {
+        if self.get_phase() != Phase::Running {
+            return self.frozen_tick.get();
+        }
+        // Clocks behind the start of the round read as its first tick
+        let tick = ms_delta_to_tick(self.elapsed_ms()).max(0);
+        match self.get_round_length() {
+            0 => tick,
+            length => tick.min(length),
+        }
+    }
+
+    /// Tick of the transaction `id` sent, rejecting timestamps from before
+    /// the round started or too far ahead of the game. A player alone in the
+    /// game has nobody to get ahead of, so only their round is checked.
+    ///
+    /// Calls may be ahead of the latest accepted one by as long as the round
+    /// had run by then, so a game where everyone went quiet for a while
+    /// catches up instead of refusing every call from then on. Rejected calls
+    /// change nothing, so sending one again doesn't get it through.
+    pub fn checked_tick(&self, id: i64) -> Result<i64, ContractError> {
+        let elapsed_ms = if self.get_phase() == Phase::Running { self.elapsed_ms() } else { 0 };
+        let latest = self.latest_time.get();
+        let ahead_ms = if self.has_rivals(id) { api_get_timestamp().saturating_sub(latest) } else { 0 };
+        check_time(elapsed_ms, ahead_ms, latest.saturating_sub(self.get_start_time()))?;
+        Ok(self.get_current_tick())
+    }
+
+    /// Note the timestamp of a call the game accepted
+    pub fn observe(&mut self) {
+        self.latest_time.set(self.latest_time.get().max(api_get_timestamp()));
+    }
+
+    /// Whether anyone other than `id` is alive in the game
+    fn has_rivals(&self, id: i64) -> bool {
+        let roster = Roster::load(self.id);
+        roster.len() > roster.contains(id) as i64
+    }
+
+    /// Whether the running round has reached its length and is waiting for
+    /// `end_round`
+    pub fn is_expired(&self) -> bool {
+        let length = self.get_round_length();
+        self.get_phase() == Phase::Running
+            && length > 0
+            && self.get_current_tick() >= length
+    }
+
+    /// Whether players can spawn now
+    pub fn accepts_spawns(&self) -> bool {
+        match self.get_phase() {
+            Phase::Lobby => true,
+            Phase::Running => !self.is_expired(),
+            Phase::Finished => false,
+        }
+    }
+
+    /// Whether players can steer now
+    pub fn accepts_input(&self) -> bool {
+        self.get_phase() == Phase::Running && !self.is_expired()
+    }
+
+    /// Finish an expired round, declaring the top of the leaderboard the
+    /// winner. Returns false if the round is not over yet.
+    pub fn end_round(&mut self) -> bool {
+        if !self.is_expired() {
+            return false;
+        }
+        self.finish();
+        true
+    }
+
+    /// Finish the running round now, however long it has left, and open the
+    /// lobby for the next one. Returns false if no round was running.
+    pub fn cut_round(&mut self) -> bool {
+        if self.get_phase() != Phase::Running {
+            return false;
+        }
+        self.finish();
+        self.open_lobby()
+    }
+
+    /// Declare the top of the leaderboard the winner and freeze the board
+    fn finish(&mut self) {
+        let leaderboard = Leaderboard::load(self.id);
+        let winner = if leaderboard.len() > 0 {
+            leaderboard.get(0)
+        } else {
+            0
+        };
+        self.winner.set(winner);
+        let winning_team = match Teams::load(self).ranking().first() {
+            Some(top) if top.players > 0 => top.team,
+            _ => 0,
+        };
+        self.winning_team.set(winning_team);
+        self.frozen_tick.set(self.get_current_tick());
+        self.phase.set(Phase::Finished.as_i64());
+    }
+
+    /// Move on from a finished round to the lobby of the next one. Players
+    /// keep their addresses, but everyone has to spawn again and the board is
+    /// empty.
+    pub fn open_lobby(&mut self) -> bool {
+        if self.get_phase() != Phase::Finished {
+            return false;
+        }
+        self.round.set(self.get_round() + 1);
+        self.frozen_tick.set(0);
+        self.phase.set(Phase::Lobby.as_i64());
+        Roster::load(self.id).clear();
+        Leaderboard::load(self.id).clear();
+        true
+    }
+
+    /// Start the clock on the round waiting in the lobby
+    pub fn start_round(&mut self) -> bool {
+        if self.get_phase() != Phase::Lobby {
+            return false;
+        }
+        self.start();
+        true
+    }
+
+    fn start(&mut self) {
+        let now = api_get_timestamp();
+        self.start_time.set(now);
+        self.frozen_tick.set(0);
+        self.phase.set(Phase::Running.as_i64());
+        // Pauses from before the round don't count against it, and one still
+        // going starts counting now
+        self.paused_total.set(0);
+        self.paused_at.set(now);
+        self.observe();
+    }
+}
+
+/// Every game hosted by the contract, and which one each player is in
+pub struct Games {
+    count: GlobalI64,
+    player_game_mapping: Mapping,
+}
+
+impl Default for Games {
+    fn default() -> Self {
+        Games {
+            count: GlobalI64(GAME_COUNT_INDEX),
+            player_game_mapping: Mapping(PLAYER_GAME_MAPPING_INDEX),
+        }
+    }
+}
+
+impl Games {
+    pub fn len(&self) -> i64 {
+        self.count.get()
+    }
+
+    /// Open a new game with its first round running. Returns `None` once the
+    /// contract is out of room for more games.
+    pub fn create(
+        &mut self,
+        round_length: i64,
+        max_players: i64,
+        team_count: i64,
+    ) -> Option<Game> {
+        let id = self.len();
+        if id >= MAX_GAMES {
+            return None;
+        }
+        self.count.set(id + 1);
+        let mut game = Game::load(id);
+        game.init(round_length, max_players, team_count);
+        Some(game)
+    }
+
+    pub fn get(&self, id: i64) -> Option<Game> {
+        if 0 <= id && id < self.len() {
+            Some(Game::load(id))
+        } else {
+            None
+        }
+    }
+
+    /// Game a player is in. Players that never joined one are in game 0.
+    pub fn game_of(&self, player: i64) -> Game {
+        Game::load(self.player_game_mapping.get(player))
+    }
+
+    pub fn join(&mut self, player: i64, game: &Game) {
+        self.player_game_mapping.set(player, game.id());
+    }
+}
+
+/// Check a transaction against the start of its round and the latest call
+/// its game accepted, from how many milliseconds it is after each and how
+/// long the round had run at that latest call
+fn check_time(elapsed_ms: i64, ahead_ms: i64, latest_elapsed_ms: i64) -> Result<(), ContractError> {
+    if elapsed_ms < 0 {
+        Err(ContractError::StaleTimestamp)
+    } else if ahead_ms > MAX_FUTURE_MS.max(latest_elapsed_ms) {
+        Err(ContractError::FutureTimestamp)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skewed_clocks() {
+        assert_eq!(check_time(-1, 0, 0), Err(ContractError::StaleTimestamp));
+        assert_eq!(check_time(0, 0, 0), Ok(()));
+        assert_eq!(check_time(i64::MAX, -1000, 0), Ok(()));
+        assert_eq!(check_time(0, MAX_FUTURE_MS, 0), Ok(()));
+        assert_eq!(check_time(0, MAX_FUTURE_MS + 1, 0), Err(ContractError::FutureTimestamp));
+        assert_eq!(check_time(0, i64::MAX, 0), Err(ContractError::FutureTimestamp));
+        // A game that had run for longer lets quiet spells catch up
+        assert_eq!(check_time(0, 2 * MAX_FUTURE_MS, 2 * MAX_FUTURE_MS), Ok(()));
+        assert_eq!(check_time(0, 2 * MAX_FUTURE_MS + 1, 2 * MAX_FUTURE_MS), Err(ContractError::FutureTimestamp));
+    }
+
+    #[test]
+    fn test_phase_round_trip() {
+        for phase in [Phase::Lobby, Phase::Running, Phase::Finished].iter() {
+            assert_eq!(Phase::from(phase.as_i64()), *phase);
+        }
+    }
+
+    #[test]
+    fn test_unlimited_round_never_ends() {
+        let mut game = Game::load(7);
+        game.init(0, 0, 0);
+        assert_eq!(game.get_round(), 1);
+        assert_eq!(game.get_phase(), Phase::Running);
+        assert!(game.accepts_input());
+
+        assert!(!game.end_round());
+        assert!(!game.open_lobby());
+        assert!(!game.start_round());
+        assert_eq!(game.get_phase(), Phase::Running);
+    }
+
+    #[test]
+    fn test_pause_freezes_clock() {
+        use crate::host::TestHost;
+
+        let host = TestHost::new();
+        host.at(10_000);
+        let mut game = Game::load(3);
+        game.init(600, 0, 0);
+        host.wait(1000);
+        assert_eq!(game.get_current_tick(), 60);
+
+        assert!(game.pause());
+        assert!(!game.pause());
+        host.wait(5000);
+        assert_eq!(game.get_current_tick(), 60);
+        assert!(game.resume());
+        assert!(!game.resume());
+        assert_eq!(game.get_current_tick(), 60);
+        host.wait(500);
+        assert_eq!(game.get_current_tick(), 90);
+
+        // Pauses add up over the round, and the round runs its full length
+        game.pause();
+        host.wait(20_000);
+        game.resume();
+        host.wait(8000);
+        assert_eq!(game.get_current_tick(), 570);
+        assert!(!game.is_expired());
+        host.wait(500);
+        assert!(game.end_round());
+
+        // A new round starts its clock from scratch, even mid-pause
+        game.pause();
+        host.wait(3000);
+        assert!(game.open_lobby());
+        assert!(game.start_round());
+        host.wait(1000);
+        assert_eq!(game.get_current_tick(), 0);
+        game.resume();
+        host.wait(1000);
+        assert_eq!(game.get_current_tick(), 60);
+    }
+
+    #[test]
+    fn test_games_have_separate_boards() {
+        use crate::grid::{Board, Cell, CELL_WIDTH, GRID_ORIGIN};
+        use crate::player::Player;
+
+        let mut games = Games::default();
+        let first = games.create(0, 0, 0).unwrap();
+        let second = games.create(600, 8, 0).unwrap();
+        assert_ne!(first.id(), second.id());
+        assert_eq!(second.get_round_length(), 600);
+        assert_eq!(first.get_max_players(), DEFAULT_MAX_PLAYERS);
+        assert_eq!(second.get_max_players(), 8);
+        assert!(games.get(games.len()).is_none());
+
+        let id = 701;
+        games.join(id, &second);
+        assert_eq!(games.game_of(id).id(), second.id());
+
+        let (x, y) = (GRID_ORIGIN + 5 * CELL_WIDTH, GRID_ORIGIN + 5 * CELL_WIDTH);
+        let mut player = Player::spawn(second.id(), id, x, y, 0);
+        Board::load(second.id()).spawn(&mut player, x, y);
+
+        let cell = Cell { col: 5, row: 5 };
+        assert_eq!(Board::load(second.id()).owner(cell), Some(id));
+        assert_eq!(Board::load(first.id()).owner(cell), None);
+        assert!(!Player::load(first.id(), id).is_alive());
+    }
+}
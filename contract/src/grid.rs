@@ -0,0 +1,440 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use crate::host::Mapping;
+use crate::index::{
+    game_index, CELL_LIFE_MAPPING_INDEX, CELL_OWNER_MAPPING_INDEX, TRAIL_LIFE_MAPPING_INDEX,
+    TRAIL_OWNER_MAPPING_INDEX,
+};
+use crate::game::Game;
+use crate::player::Player;
+use crate::score::{Leaderboard, Roster};
+use crate::team::Teams;
+
+pub const GRID_COUNT: i64 = 300;
+pub const CELL_WIDTH: i64 = 40;
+/// Clients shift coordinates by `u32::MAX` so they fit in unsigned contract
+/// values, which puts the top left corner of the grid here
+pub const GRID_ORIGIN: i64 = u32::MAX as i64;
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash)]
+pub struct Cell {
+    pub col: i64,
+    pub row: i64,
+}
+
+impl Cell {
+    /// Cell containing the point, if it is on the grid
+    pub fn at(x: i64, y: i64) -> Option<Cell> {
+        let cell = Cell {
+            col: (x - GRID_ORIGIN).div_euclid(CELL_WIDTH),
+            row: (y - GRID_ORIGIN).div_euclid(CELL_WIDTH),
+        };
+        if cell.in_bounds() {
+            Some(cell)
+        } else {
+            None
+        }
+    }
+
+    pub fn in_bounds(&self) -> bool {
+        0 <= self.col && self.col < GRID_COUNT && 0 <= self.row && self.row < GRID_COUNT
+    }
+
+    pub fn index(&self) -> i64 {
+        self.row * GRID_COUNT + self.col
+    }
+
+    pub fn from_index(index: i64) -> Cell {
+        Cell {
+            col: index % GRID_COUNT,
+            row: index / GRID_COUNT,
+        }
+    }
+
+    fn neighbours(&self) -> [Cell; 4] {
+        [
+            Cell { col: self.col + 1, row: self.row },
+            Cell { col: self.col - 1, row: self.row },
+            Cell { col: self.col, row: self.row + 1 },
+            Cell { col: self.col, row: self.row - 1 },
+        ]
+    }
+}
+
+/// Cells visited when moving in a straight line from `from` to `to`, not
+/// counting the starting cell. Off grid cells are skipped.
+pub fn cells_between(from: (i64, i64), to: (i64, i64)) -> Vec<Cell> {
+    let col = |x: i64| (x - GRID_ORIGIN).div_euclid(CELL_WIDTH);
+    let row = |y: i64| (y - GRID_ORIGIN).div_euclid(CELL_WIDTH);
+    let (c0, r0, c1, r1) = (col(from.0), row(from.1), col(to.0), row(to.1));
+
+    let step = |a: i64, b: i64| if b >= a { 1 } else { -1 };
+    let mut cells = Vec::new();
+    if c0 != c1 {
+        let mut c = c0;
+        while c != c1 {
+            c += step(c0, c1);
+            cells.push(Cell { col: c, row: r0 });
+        }
+    } else {
+        let mut r = r0;
+        while r != r1 {
+            r += step(r0, r1);
+            cells.push(Cell { col: c0, row: r });
+        }
+    }
+    cells.retain(Cell::in_bounds);
+    cells
+}
+
+/// Ownership of territory and trails on a game's grid.
+///
+/// Cells remember the life of the player that claimed them, so a dead or
+/// respawned player's cells become free again without being cleared.
+pub struct Board {
+    game: i64,
+    cell_owner_mapping: Mapping,
+    cell_life_mapping: Mapping,
+    trail_owner_mapping: Mapping,
+    trail_life_mapping: Mapping,
+    /// Players whose score changed and need re-ranking
+    touched: BTreeSet<i64>,
+}
+
+impl Board {
+    pub fn load(game: i64) -> Self {
+        Board {
+            game,
+            cell_owner_mapping: Mapping(game_index(game, CELL_OWNER_MAPPING_INDEX)),
+            cell_life_mapping: Mapping(game_index(game, CELL_LIFE_MAPPING_INDEX)),
+            trail_owner_mapping: Mapping(game_index(game, TRAIL_OWNER_MAPPING_INDEX)),
+            trail_life_mapping: Mapping(game_index(game, TRAIL_LIFE_MAPPING_INDEX)),
+            touched: BTreeSet::new(),
+        }
+    }
+
+    /// Current owner of a cell's territory
+    pub fn owner(&self, cell: Cell) -> Option<i64> {
+        let owner = self.cell_owner_mapping.get(cell.index());
+        let life = self.cell_life_mapping.get(cell.index());
+        if Player::load(self.game, owner).holds(life) {
+            Some(owner)
+        } else {
+            None
+        }
+    }
+
+    /// Player whose trail currently runs through a cell
+    pub fn trail(&self, cell: Cell) -> Option<i64> {
+        let owner = self.trail_owner_mapping.get(cell.index());
+        let life = self.trail_life_mapping.get(cell.index());
+        if Player::load(self.game, owner).holds(life) {
+            Some(owner)
+        } else {
+            None
+        }
+    }
+
+    /// Give the 3x3 block around a new player's spawn point to them
+    pub fn spawn(&mut self, player: &mut Player, x: i64, y: i64) {
+        if let Some(center) = Cell::at(x, y) {
+            for dr in -1..=1 {
+                for dc in -1..=1 {
+                    let cell = Cell { col: center.col + dc, row: center.row + dr };
+                    if cell.in_bounds() {
+                        self.claim(player, cell);
+                    }
+                }
+            }
+        }
+        self.touched.insert(player.id());
+    }
+
+    /// Move a player along their path up to `now_tick`, laying trail,
+    /// killing players whose trails are crossed and capturing territory when
+    /// the trail is closed. Capturing or killing settles the path, since
+    /// neither can be laid again.
+    pub fn advance(&mut self, player: &mut Player, now_tick: i64) {
+        let path = player.advance_path(now_tick);
+        let cells = path
+            .windows(2)
+            .flat_map(|leg| {
+                let (from, to) = (leg[0], leg[1]);
+                cells_between((from.0, from.1), (to.0, to.1))
+                    .into_iter()
+                    .map(move |cell| (cell, from.2))
+            })
+            .collect::<Vec<_>>();
+
+        let mut settled = false;
+        for (cell, tick) in cells {
+            // Teammates' territory closes a trail just like the player's own
+            if self.owner(cell).map_or(false, |owner| player.is_ally(owner)) {
+                if player.trail_len() > 0 {
+                    self.capture(player);
+                    settled = true;
+                }
+                continue;
+            }
+
+            match self.trail(cell) {
+                Some(other) if other == player.id() => {
+                    // Crossed our own trail
+                    self.kill(player, now_tick);
+                    return;
+                }
+                // Teammates pass over each other's trails
+                Some(other) if player.is_ally(other) => (),
+                Some(other) => {
+                    let mut victim = Player::load(self.game, other);
+                    self.kill(&mut victim, now_tick);
+                    player.add_kill();
+                    self.touched.insert(player.id());
+                    settled = true;
+                }
+                None => (),
+            }
+
+            self.trail_owner_mapping.set(cell.index(), player.id());
+            self.trail_life_mapping.set(cell.index(), player.get_life());
+            player.push_trail(cell, tick);
+        }
+        if settled {
+            player.settle(now_tick);
+        }
+    }
+
+    /// Lay a player's trail again from `tick`, for a turn that landed behind
+    /// where the board had already moved them, so the trail ends up the same
+    /// whichever of their turns arrived first
+    pub fn relay(&mut self, player: &mut Player, tick: i64) {
+        let advanced = player.get_advanced_tick();
+        if tick >= advanced || !player.is_alive() {
+            return;
+        }
+        for cell in player.rewind_trail(tick) {
+            // A teammate may have laid their own trail over it since
+            if self.trail_owner_mapping.get(cell.index()) == player.id() {
+                self.trail_life_mapping.set(cell.index(), 0);
+            }
+        }
+        self.advance(player, advanced);
+    }
+
+    pub fn kill(&mut self, player: &mut Player, now_tick: i64) {
+        player.kill(now_tick);
+        self.touched.insert(player.id());
+    }
+
+    /// Re-rank every player whose score changed
+    pub fn commit_scores(&mut self) {
+        let mut roster = Roster::load(self.game);
+        let mut leaderboard = Leaderboard::load(self.game);
+        for id in std::mem::take(&mut self.touched) {
+            let player = Player::load(self.game, id);
+            roster.update(&player);
+            leaderboard.update(&player, &roster);
+        }
+    }
+
+    fn claim(&mut self, player: &mut Player, cell: Cell) {
+        match self.owner(cell) {
+            Some(owner) if owner == player.id() => return,
+            Some(owner) => {
+                Player::load(self.game, owner).add_area(-1);
+                self.touched.insert(owner);
+            }
+            None => (),
+        }
+        self.cell_owner_mapping.set(cell.index(), player.id());
+        self.cell_life_mapping.set(cell.index(), player.get_life());
+        player.add_area(1);
+        player.extend_bounds(cell);
+    }
+
+    /// Turn the trail into territory, along with everything it encloses
+    fn capture(&mut self, player: &mut Player) {
+        for i in 0..player.trail_len() {
+            let cell = player.trail_cell(i);
+            self.claim(player, cell);
+        }
+        player.clear_trail();
+        self.touched.insert(player.id());
+
+        // Flood the bounding box of the player and their teammates, grown by
+        // a cell on each side, from its edge. Whatever the flood can't reach
+        // is enclosed.
+        let (mut min, mut max) = player.get_bounds();
+        let teams = Teams::load(&Game::load(self.game));
+        for ally in teams.members(player.get_team()) {
+            let (ally_min, ally_max) = ally.get_bounds();
+            min = Cell { col: min.col.min(ally_min.col), row: min.row.min(ally_min.row) };
+            max = Cell { col: max.col.max(ally_max.col), row: max.row.max(ally_max.row) };
+        }
+        let (min, max) = (
+            Cell { col: min.col - 1, row: min.row - 1 },
+            Cell { col: max.col + 1, row: max.row + 1 },
+        );
+        let width = (max.col - min.col + 1) as usize;
+        let height = (max.row - min.row + 1) as usize;
+        let slot = |c: Cell| (c.row - min.row) as usize * width + (c.col - min.col) as usize;
+        let inside = |c: Cell| min.col <= c.col && c.col <= max.col && min.row <= c.row && c.row <= max.row;
+
+        let mut owned = HashMap::new();
+        let mut is_own = |board: &Board, c: Cell| {
+            c.in_bounds()
+                && *owned
+                    .entry(c)
+                    .or_insert_with(|| board.owner(c).map_or(false, |owner| player.is_ally(owner)))
+        };
+
+        let mut reached = vec![false; width * height];
+        let mut queue = VecDeque::new();
+        for col in min.col..=max.col {
+            queue.push_back(Cell { col, row: min.row });
+            queue.push_back(Cell { col, row: max.row });
+        }
+        for row in min.row..=max.row {
+            queue.push_back(Cell { col: min.col, row });
+            queue.push_back(Cell { col: max.col, row });
+        }
+        while let Some(cell) = queue.pop_front() {
+            if reached[slot(cell)] || is_own(self, cell) {
+                continue;
+            }
+            reached[slot(cell)] = true;
+            for next in cell.neighbours().iter() {
+                if inside(*next) && !reached[slot(*next)] {
+                    queue.push_back(*next);
+                }
+            }
+        }
+
+        let mut enclosed = Vec::new();
+        for row in min.row..=max.row {
+            for col in min.col..=max.col {
+                let cell = Cell { col, row };
+                if !reached[slot(cell)] && cell.in_bounds() && !is_own(self, cell) {
+                    enclosed.push(cell);
+                }
+            }
+        }
+        for cell in enclosed {
+            self.claim(player, cell);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heading::PlayerHeading;
+
+    fn point(col: i64, row: i64) -> (i64, i64) {
+        (GRID_ORIGIN + col * CELL_WIDTH, GRID_ORIGIN + row * CELL_WIDTH)
+    }
+
+    #[test]
+    fn test_cell_at() {
+        let (x, y) = point(3, 7);
+        assert_eq!(Cell::at(x, y), Some(Cell { col: 3, row: 7 }));
+        assert_eq!(Cell::at(x + CELL_WIDTH - 1, y), Some(Cell { col: 3, row: 7 }));
+        assert_eq!(Cell::at(0, 15), None);
+        assert_eq!(Cell::from_index(Cell { col: 3, row: 7 }.index()), Cell { col: 3, row: 7 });
+    }
+
+    #[test]
+    fn test_cells_between() {
+        assert_eq!(
+            cells_between(point(3, 7), point(5, 7)),
+            vec![Cell { col: 4, row: 7 }, Cell { col: 5, row: 7 }]
+        );
+        assert_eq!(
+            cells_between(point(3, 7), point(3, 5)),
+            vec![Cell { col: 3, row: 6 }, Cell { col: 3, row: 5 }]
+        );
+        assert_eq!(cells_between(point(0, 0), point(-2, 0)), Vec::<Cell>::new());
+    }
+
+    #[test]
+    fn test_capture_encloses_loop() {
+        let id = 9001;
+        let (x, y) = point(10, 10);
+        let mut board = Board::load(0);
+        let mut player = Player::spawn(0, id, x, y, 0);
+        board.spawn(&mut player, x, y);
+        assert_eq!(player.get_area(), 9);
+
+        // Leave territory to the right, loop around and come back in
+        let mut tick = 0;
+        for (heading, cells) in [
+            (PlayerHeading::Right, 3),
+            (PlayerHeading::Up, 3),
+            (PlayerHeading::Left, 3),
+            (PlayerHeading::Down, 2),
+        ].iter() {
+            let now_tick = tick + cells * CELL_WIDTH;
+            player.apply_input(*heading, tick);
+            board.advance(&mut player, now_tick);
+            tick = now_tick;
+        }
+
+        assert!(player.is_alive());
+        assert_eq!(player.trail_len(), 0);
+        // Spawn block, the 9 trail cells and the 3 cells they enclose
+        assert_eq!(player.get_area(), 21);
+        assert_eq!(board.owner(Cell { col: 12, row: 12 }), Some(id));
+        assert_eq!(board.owner(Cell { col: 14, row: 12 }), None);
+    }
+
+    #[test]
+    fn test_crossing_trail_kills() {
+        let (hunter_id, prey_id) = (9002, 9003);
+        let mut board = Board::load(0);
+
+        let (x, y) = point(40, 40);
+        let mut prey = Player::spawn(0, prey_id, x, y, 0);
+        board.spawn(&mut prey, x, y);
+        prey.apply_input(PlayerHeading::Right, 0);
+        board.advance(&mut prey, 4 * CELL_WIDTH);
+        assert_eq!(board.trail(Cell { col: 43, row: 40 }), Some(prey_id));
+
+        let (x, y) = point(43, 37);
+        let mut hunter = Player::spawn(0, hunter_id, x, y, 0);
+        board.spawn(&mut hunter, x, y);
+        hunter.apply_input(PlayerHeading::Up, 0);
+        board.advance(&mut hunter, 4 * CELL_WIDTH);
+
+        let prey = Player::load(0, prey_id);
+        assert!(!prey.is_alive());
+        assert_eq!(board.trail(Cell { col: 43, row: 40 }), Some(hunter_id));
+        assert_eq!(board.owner(Cell { col: 40, row: 40 }), None);
+        assert_eq!(hunter.get_kills(), 1);
+    }
+
+    #[test]
+    fn test_kill_behind_prey_clock() {
+        let (hunter_id, prey_id) = (9004, 9005);
+        let mut board = Board::load(0);
+
+        let (x, y) = point(60, 60);
+        let mut prey = Player::spawn(0, prey_id, x, y, 0);
+        board.spawn(&mut prey, x, y);
+        prey.apply_input(PlayerHeading::Right, 0);
+        board.advance(&mut prey, 4 * CELL_WIDTH);
+
+        // The hunter's clock is behind the prey's last move when they cross
+        let (x, y) = point(63, 57);
+        let mut hunter = Player::spawn(0, hunter_id, x, y, 0);
+        board.spawn(&mut hunter, x, y);
+        hunter.apply_input(PlayerHeading::Up, 0);
+        board.advance(&mut hunter, 4 * CELL_WIDTH - 10);
+
+        let prey = Player::load(0, prey_id);
+        assert!(!prey.is_alive());
+        // Frozen where they were on the hunter's tick
+        let (x, y) = point(60, 60);
+        assert_eq!(prey.get_position(0), (x + 4 * CELL_WIDTH - 10, y));
+        assert_eq!(hunter.get_kills(), 1);
+    }
+}
@@ -0,0 +1,111 @@
+//! What the chain provides to a call: the sender, the timestamp, the hash of
+//! its transaction and storage.
+//! Tests and benchmarks run natively with no chain behind them, so they get
+//! an in-process stand-in with the same interface, scripted through
+//! `TestHost`. The `mock-host` feature turns it on outside of tests.
+
+#[cfg(not(any(test, feature = "mock-host")))]
+pub use rustdag_wasm_api::globals::{api_get_hash, api_get_sender_address, api_get_timestamp, GlobalI64};
+#[cfg(not(any(test, feature = "mock-host")))]
+pub use rustdag_wasm_api::mapping::Mapping;
+
+#[cfg(any(test, feature = "mock-host"))]
+pub use self::mock::{api_get_hash, api_get_sender_address, api_get_timestamp, GlobalI64, Mapping, TestHost};
+
+#[cfg(any(test, feature = "mock-host"))]
+mod mock {
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+
+    thread_local! {
+        static SENDER: Cell<i64> = Cell::new(0);
+        static TIMESTAMP: Cell<i64> = Cell::new(0);
+        static HASH: Cell<i64> = Cell::new(0);
+        static GLOBALS: RefCell<HashMap<i32, i64>> = RefCell::new(HashMap::new());
+        static MAPPINGS: RefCell<HashMap<(i32, i64), i64>> = RefCell::new(HashMap::new());
+    }
+
+    pub fn api_get_sender_address() -> i64 {
+        SENDER.with(|sender| sender.get())
+    }
+
+    pub fn api_get_timestamp() -> i64 {
+        TIMESTAMP.with(|timestamp| timestamp.get())
+    }
+
+    pub fn api_get_hash() -> i64 {
+        HASH.with(|hash| hash.get())
+    }
+
+    /// The chain as seen by calls on the current thread. Each test runs on
+    /// its own thread, and creating a host also wipes storage, so tests
+    /// never see each other's state.
+    pub struct TestHost(());
+
+    impl TestHost {
+        /// Empty storage, with calls sent from address 0 at time 0 in a
+        /// transaction hashed 0
+        pub fn new() -> Self {
+            GLOBALS.with(|globals| globals.borrow_mut().clear());
+            MAPPINGS.with(|mappings| mappings.borrow_mut().clear());
+            let host = TestHost(());
+            host.sender(0).at(0).hash(0);
+            host
+        }
+
+        /// Send the following calls from `id`
+        pub fn sender(&self, id: i64) -> &Self {
+            SENDER.with(|sender| sender.set(id));
+            self
+        }
+
+        /// Timestamp the following calls `ms`
+        pub fn at(&self, ms: i64) -> &Self {
+            TIMESTAMP.with(|timestamp| timestamp.set(ms));
+            self
+        }
+
+        /// Hash the transaction of the following calls `hash`
+        pub fn hash(&self, hash: i64) -> &Self {
+            HASH.with(|cell| cell.set(hash));
+            self
+        }
+
+        /// Move the clock on by `ms`
+        pub fn wait(&self, ms: i64) -> &Self {
+            self.at(self.time() + ms)
+        }
+
+        pub fn time(&self) -> i64 {
+            api_get_timestamp()
+        }
+    }
+
+    /// Global at a storage index. Unset globals read as 0, as on chain.
+    pub struct GlobalI64(pub i32);
+
+    impl GlobalI64 {
+        pub fn get(&self) -> i64 {
+            GLOBALS.with(|globals| globals.borrow().get(&self.0).copied().unwrap_or(0))
+        }
+
+        pub fn set(&self, value: i64) {
+            GLOBALS.with(|globals| globals.borrow_mut().insert(self.0, value));
+        }
+    }
+
+    /// Mapping at a storage index. Unset keys read as 0, as on chain.
+    pub struct Mapping(pub i32);
+
+    impl Mapping {
+        pub fn get(&self, key: i64) -> i64 {
+            MAPPINGS.with(|mappings| {
+                mappings.borrow().get(&(self.0, key)).copied().unwrap_or(0)
+            })
+        }
+
+        pub fn set(&self, key: i64, value: i64) {
+            MAPPINGS.with(|mappings| mappings.borrow_mut().insert((self.0, key), value));
+        }
+    }
+}
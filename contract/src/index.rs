@@ -1,5 +1,90 @@
+// Shared by every game
+pub const GAME_COUNT_INDEX: i32 = 0;
+pub const PLAYER_GAME_MAPPING_INDEX: i32 = 1;
+pub const PROFILE_NAME_LEN_MAPPING_INDEX: i32 = 2;
+pub const PROFILE_NAME_MAPPING_INDEX: i32 = 3;
+pub const PROFILE_COLOR_MAPPING_INDEX: i32 = 4;
+pub const OWNER_INDEX: i32 = 5;
+pub const BANNED_MAPPING_INDEX: i32 = 6;
+
+// Each game has its own copy of everything below, at `game_index(game, ..)`
 pub const START_TIME_INDEX: i32 = 0;
 pub const PLAYER_X_MAPPING_INDEX: i32 = 1;
 pub const PLAYER_Y_MAPPING_INDEX: i32 = 2;
 pub const PLAYER_HEADING_MAPPING_INDEX: i32 = 3;
 pub const PLAYER_TICK_MAPPING_INDEX: i32 = 4;
+pub const PLAYER_LIFE_MAPPING_INDEX: i32 = 5;
+pub const PLAYER_ALIVE_MAPPING_INDEX: i32 = 6;
+pub const PLAYER_SPAWN_TICK_MAPPING_INDEX: i32 = 7;
+pub const PLAYER_DEATH_TICK_MAPPING_INDEX: i32 = 8;
+pub const PLAYER_AREA_MAPPING_INDEX: i32 = 9;
+pub const PLAYER_KILLS_MAPPING_INDEX: i32 = 10;
+pub const PLAYER_TRAIL_LEN_MAPPING_INDEX: i32 = 11;
+pub const PLAYER_TRAIL_CELL_MAPPING_INDEX: i32 = 12;
+pub const PLAYER_MIN_COL_MAPPING_INDEX: i32 = 13;
+pub const PLAYER_MIN_ROW_MAPPING_INDEX: i32 = 14;
+pub const PLAYER_MAX_COL_MAPPING_INDEX: i32 = 15;
+pub const PLAYER_MAX_ROW_MAPPING_INDEX: i32 = 16;
+pub const CELL_OWNER_MAPPING_INDEX: i32 = 17;
+pub const CELL_LIFE_MAPPING_INDEX: i32 = 18;
+pub const TRAIL_OWNER_MAPPING_INDEX: i32 = 19;
+pub const TRAIL_LIFE_MAPPING_INDEX: i32 = 20;
+pub const LEADERBOARD_MAPPING_INDEX: i32 = 21;
+pub const LEADERBOARD_LEN_INDEX: i32 = 22;
+pub const ROUND_INDEX: i32 = 23;
+pub const PHASE_INDEX: i32 = 24;
+pub const ROUND_LENGTH_INDEX: i32 = 25;
+pub const FROZEN_TICK_INDEX: i32 = 26;
+pub const WINNER_INDEX: i32 = 27;
+pub const PLAYER_ROUND_MAPPING_INDEX: i32 = 28;
+pub const MAX_PLAYERS_INDEX: i32 = 29;
+pub const QUEUE_HEAD_INDEX: i32 = 30;
+pub const QUEUE_TAIL_INDEX: i32 = 31;
+pub const QUEUE_SLOT_MAPPING_INDEX: i32 = 32;
+pub const QUEUE_PLAYER_SLOT_MAPPING_INDEX: i32 = 33;
+pub const QUEUE_X_MAPPING_INDEX: i32 = 34;
+pub const QUEUE_Y_MAPPING_INDEX: i32 = 35;
+pub const PLAYER_BOOST_START_MAPPING_INDEX: i32 = 36;
+pub const PLAYER_BOOST_END_MAPPING_INDEX: i32 = 37;
+pub const PLAYER_BOOST_READY_MAPPING_INDEX: i32 = 38;
+pub const PLAYER_TEAM_MAPPING_INDEX: i32 = 39;
+pub const TEAM_COUNT_INDEX: i32 = 40;
+pub const WINNING_TEAM_INDEX: i32 = 41;
+pub const PLAYER_INPUT_LEN_MAPPING_INDEX: i32 = 42;
+pub const PLAYER_INPUT_TICK_MAPPING_INDEX: i32 = 43;
+pub const PLAYER_INPUT_HEADING_MAPPING_INDEX: i32 = 44;
+pub const PLAYER_ADVANCED_TICK_MAPPING_INDEX: i32 = 45;
+pub const PAUSED_INDEX: i32 = 46;
+pub const PAUSED_AT_INDEX: i32 = 47;
+pub const PAUSED_TOTAL_INDEX: i32 = 48;
+pub const ROSTER_MAPPING_INDEX: i32 = 49;
+pub const ROSTER_SLOT_MAPPING_INDEX: i32 = 50;
+pub const ROSTER_LEN_INDEX: i32 = 51;
+pub const PLAYER_SPAWN_TIME_MAPPING_INDEX: i32 = 52;
+pub const LATEST_TIME_INDEX: i32 = 53;
+pub const PLAYER_TRAIL_TICK_MAPPING_INDEX: i32 = 54;
+pub const PLAYER_SETTLED_TICK_MAPPING_INDEX: i32 = 55;
+pub const PLAYER_INPUT_HASH_MAPPING_INDEX: i32 = 56;
+
+const GAME_NAMESPACE_BASE: i32 = 64;
+const GAME_NAMESPACE_STRIDE: i32 = 64;
+
+/// Number of games that fit in the index space
+pub const MAX_GAMES: i64 = ((i32::MAX - GAME_NAMESPACE_BASE) / GAME_NAMESPACE_STRIDE) as i64;
+
+/// Storage index of the per-game global or mapping at `offset` for `game`
+pub fn game_index(game: i64, offset: i32) -> i32 {
+    debug_assert!(0 <= game && game < MAX_GAMES, "Game {} out of range", game);
+    debug_assert!(offset < GAME_NAMESPACE_STRIDE);
+    GAME_NAMESPACE_BASE + game as i32 * GAME_NAMESPACE_STRIDE + offset
+}
+
+/// Combine two values into a single mapping key, for mappings indexed by a
+/// pair such as (player, trail position)
+pub fn compound_key(a: i64, b: i64) -> i64 {
+    // splitmix64 finalizer over both halves
+    let mut z = (a as u64) ^ (b as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (z ^ (z >> 31)) as i64
+}
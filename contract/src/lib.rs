@@ -1,97 +1,1098 @@
 use rustdag_wasm_api::contract::Contract;
-use rustdag_wasm_api::globals::{
-    api_get_sender_address, api_get_timestamp, GlobalI64,
-};
 
+#[cfg(feature = "extern")]
 use rustdag_wasm_api::contract_extern;
 
 mod player;
-mod heading;
+mod admin;
+pub mod error;
+mod host;
+pub mod heading;
 mod index;
+mod grid;
+mod score;
+mod game;
+mod queue;
+mod profile;
+pub mod movement;
+mod team;
 
-use index::START_TIME_INDEX;
+// Board geometry, for replaying moves outside of the contract
+pub use grid::{cells_between, Cell, CELL_WIDTH, GRID_COUNT, GRID_ORIGIN};
+#[cfg(feature = "mock-host")]
+pub use host::TestHost;
+
+use admin::Admin;
+use error::{result_code, ContractError};
+use game::{Game, Games};
+use grid::{Board, Cell};
+use heading::PlayerHeading;
+use host::{api_get_sender_address, api_get_timestamp};
 use player::Player;
+use profile::{unpack_name, Profile};
+use queue::WaitingQueue;
+use score::{Leaderboard, Roster};
+use team::{TeamScore, Teams};
 
+#[derive(Default)]
 pub struct P2PIOContract {
-    start_time: GlobalI64,
-}
-
-impl Default for P2PIOContract {
-    fn default() -> Self {
-        P2PIOContract {
-            start_time: GlobalI64(START_TIME_INDEX),
-        }
-    }
+    games: Games,
+    admin: Admin,
 }
 
 impl Contract for P2PIOContract {
+    /// Open game 0, which players are in until they join another, and make
+    /// the deployer the owner
     fn init(&mut self) {
-        self.start_time.set(api_get_timestamp());
+        self.games.create(0, 0, 0);
+        self.admin.set_owner(api_get_sender_address());
     }
 }
 
-#[contract_extern]
+#[cfg_attr(feature = "extern", contract_extern)]
 impl P2PIOContract {
-    pub fn get_start_time(&self) -> i64 {
-        self.start_time.get()
+    /// Open a new game with its own board, leaderboard and rounds, and return
+    /// its id. The first round starts straight away and lasts `round_length`
+    /// ticks, or forever if it is 0. At most `max_players` can be alive at
+    /// once, or the default of 70 if it is 0. Players are split into
+    /// `team_count` teams, up to 8, or play on their own if it is 0. Owner
+    /// only. Returns the error code negated if no game was opened, with
+    /// `NotAccepting` meaning there is no room for more.
+    pub fn create_game(&mut self, round_length: i64, max_players: i64, team_count: i64) -> i64 {
+        let mut id = 0;
+        let result = self.as_owner(|contract| {
+            let game = contract.games.create(round_length, max_players, team_count)
+                .ok_or(ContractError::NotAccepting)?;
+            id = game.id();
+            Ok(())
+        });
+        match result {
+            Ok(()) => id,
+            Err(e) => -e.code(),
+        }
     }
 
-    pub fn spawn_player(&self, x: i64, y: i64) {
-        Player::spawn(api_get_sender_address(), x, y, self.get_current_game_tick());
+    /// Move the sender to another game. They leave their current game
+    /// straight away, and have to spawn to take part in the new one.
+    pub fn join_game(&mut self, game_id: i64) {
+        let game = match self.games.get(game_id) {
+            Some(game) => game,
+            None => return,
+        };
+        let id = api_get_sender_address();
+        let current = self.games.game_of(id);
+        if current.id() == game.id() {
+            return;
+        }
+
+        self.remove(id, &current);
+        self.games.join(id, &game);
+    }
+
+    pub fn get_game_count(&self) -> i64 {
+        self.games.len()
+    }
+
+    pub fn get_player_game(&self, id: i64) -> i64 {
+        self.games.game_of(id).id()
+    }
+
+    pub fn get_max_players(&self, game_id: i64) -> i64 {
+        self.game(game_id).map_or(0, |game| game.get_max_players())
+    }
+
+    /// Put the sender on one of their game's teams. Players who spawn without
+    /// picking one are put on the smallest team. Teams can only be switched
+    /// between lives.
+    pub fn join_team(&mut self, team: i64) {
+        let id = api_get_sender_address();
+        let game = self.games.game_of(id);
+        let mut player = Player::load(game.id(), id);
+        if !Teams::load(&game).contains(team) || player.is_alive() {
+            return;
+        }
+        player.set_team(team);
+    }
+
+    pub fn get_team_count(&self, game_id: i64) -> i64 {
+        self.game(game_id).map_or(0, |game| game.get_team_count())
+    }
+
+    /// Team a player is on in their game, or 0 if it has no teams
+    pub fn get_player_team(&self, id: i64) -> i64 {
+        self.player(id).0.get_team()
+    }
+
+    /// Territory held by a team's live players
+    pub fn get_team_area(&self, game_id: i64, team: i64) -> i64 {
+        self.team_score(game_id, team).area
+    }
+
+    pub fn get_team_kills(&self, game_id: i64, team: i64) -> i64 {
+        self.team_score(game_id, team).kills
+    }
+
+    /// Number of live players on a team
+    pub fn get_team_players(&self, game_id: i64, team: i64) -> i64 {
+        self.team_score(game_id, team).players
+    }
+
+    /// Team at `rank` in a game, counting from zero, or 0 past the last team
+    pub fn get_team_leaderboard(&self, game_id: i64, rank: i64) -> i64 {
+        let ranking = match self.game(game_id) {
+            Some(game) => Teams::load(&game).ranking(),
+            None => return 0,
+        };
+        if rank < 0 {
+            return 0;
+        }
+        ranking.get(rank as usize).map_or(0, |score| score.team)
+    }
+
+    /// Team that won the last finished round, or 0 if the game has no teams
+    pub fn get_winning_team(&self, game_id: i64) -> i64 {
+        self.game(game_id).map_or(0, |game| game.get_winning_team())
+    }
+
+    /// Place of a player in their game's waiting queue counting from 1, or 0
+    /// if they aren't waiting
+    pub fn get_queue_position(&self, id: i64) -> i64 {
+        let game = self.games.game_of(id);
+        WaitingQueue::load(game.id()).position(id)
+    }
+
+    /// Set the sender's name and color. The name is `name_len` bytes of UTF-8
+    /// packed little endian into `name_0` to `name_2`, and is rejected along
+    /// with the color if it is invalid. `color` is 0xRRGGBB, and anything
+    /// outside that range goes back to the default color.
+    pub fn set_profile(&mut self, name_len: i64, name_0: i64, name_1: i64, name_2: i64, color: i64) {
+        let name = match unpack_name(name_len, [name_0, name_1, name_2]) {
+            Some(name) => name,
+            None => return,
+        };
+        let mut profile = Profile::load(api_get_sender_address());
+        profile.set_name(&name);
+        profile.set_color(if 0 <= color && color <= 0xFF_FFFF {
+            Some(color)
+        } else {
+            None
+        });
+    }
+
+    /// Length in bytes of a player's name, or 0 if they haven't set one
+    pub fn get_player_name_len(&self, id: i64) -> i64 {
+        Profile::load(id).name_len()
+    }
+
+    /// One of the words a player's name is packed into
+    pub fn get_player_name_word(&self, id: i64, word: i64) -> i64 {
+        Profile::load(id).name_word(word)
+    }
+
+    /// A player's color as 0xRRGGBB
+    pub fn get_player_color(&self, id: i64) -> i64 {
+        Profile::load(id).color()
+    }
+
+    /// Time the current round of a game started
+    pub fn get_start_time(&self, game_id: i64) -> i64 {
+        self.game(game_id).map_or(0, |game| game.get_start_time())
+    }
+
+    pub fn get_round(&self, game_id: i64) -> i64 {
+        self.game(game_id).map_or(0, |game| game.get_round())
+    }
+
+    /// 0 in the lobby, 1 while running and 2 once the winner is declared
+    pub fn get_phase(&self, game_id: i64) -> i64 {
+        self.game(game_id).map_or(0, |game| game.get_phase().into())
+    }
+
+    pub fn get_round_length(&self, game_id: i64) -> i64 {
+        self.game(game_id).map_or(0, |game| game.get_round_length())
+    }
+
+    /// Winner of the last finished round, or 0 if nobody was alive at the end
+    pub fn get_winner(&self, game_id: i64) -> i64 {
+        self.game(game_id).map_or(0, |game| game.get_winner())
+    }
+
+    /// Address of the contract's owner, who deployed it unless ownership has
+    /// been transferred since
+    pub fn get_owner(&self) -> i64 {
+        self.admin.get_owner()
+    }
+
+    /// Hand the contract over to `new_owner`. Owner only.
+    pub fn transfer_ownership(&mut self, new_owner: i64) -> i64 {
+        result_code(self.as_owner(|contract| {
+            contract.admin.set_owner(new_owner);
+            Ok(())
+        }))
+    }
+
+    /// Set the length of a game's rounds in ticks, or 0 for rounds that never
+    /// end, and the most players alive at once, or 0 for the default. Both
+    /// apply to the current round too. Owner only.
+    pub fn configure(&mut self, game_id: i64, round_length: i64, max_players: i64) -> i64 {
+        result_code(self.as_owner(|contract| {
+            let mut game = contract.game(game_id).ok_or(ContractError::NotAccepting)?;
+            game.set_round_length(round_length);
+            game.set_max_players(max_players);
+            Ok(())
+        }))
+    }
+
+    /// End a game's running round now, declaring the winner, and start the
+    /// next one. Owner only.
+    pub fn reset_round(&mut self, game_id: i64) -> i64 {
+        result_code(self.as_owner(|contract| {
+            let mut game = contract.game(game_id).ok_or(ContractError::NotAccepting)?;
+            if !game.cut_round() {
+                return Err(ContractError::NotAccepting);
+            }
+            WaitingQueue::load(game.id()).admit(&game);
+            game.start_round();
+            Ok(())
+        }))
+    }
+
+    /// Stop players spawning and steering in a game until it is resumed.
+    /// Owner only.
+    pub fn pause_game(&mut self, game_id: i64) -> i64 {
+        result_code(self.as_owner(|contract| {
+            let mut game = contract.game(game_id).ok_or(ContractError::NotAccepting)?;
+            if game.pause() {
+                Ok(())
+            } else {
+                Err(ContractError::NotAccepting)
+            }
+        }))
+    }
+
+    /// Owner only
+    pub fn resume_game(&mut self, game_id: i64) -> i64 {
+        result_code(self.as_owner(|contract| {
+            let mut game = contract.game(game_id).ok_or(ContractError::NotAccepting)?;
+            if game.resume() {
+                Ok(())
+            } else {
+                Err(ContractError::NotAccepting)
+            }
+        }))
+    }
+
+    pub fn get_paused(&self, game_id: i64) -> i64 {
+        self.game(game_id).map_or(0, |game| game.is_paused() as i64)
+    }
+
+    /// Milliseconds the current round spent paused, which don't count as
+    /// ticks. A pause still going isn't counted until it ends.
+    pub fn get_paused_ms(&self, game_id: i64) -> i64 {
+        self.game(game_id).map_or(0, |game| game.get_paused_ms())
+    }
+
+    /// Kill a player and take them out of the waiting queue. They can spawn
+    /// again straight away. Owner only.
+    pub fn kick_player(&mut self, id: i64) -> i64 {
+        result_code(self.as_owner(|contract| {
+            contract.remove(id, &contract.games.game_of(id));
+            Ok(())
+        }))
+    }
+
+    /// Kick a player, and keep them from spawning or steering in any game
+    /// until they are unbanned. Owner only.
+    pub fn ban_player(&mut self, id: i64) -> i64 {
+        result_code(self.as_owner(|contract| {
+            contract.admin.set_banned(id, true);
+            contract.remove(id, &contract.games.game_of(id));
+            Ok(())
+        }))
+    }
+
+    /// Owner only
+    pub fn unban_player(&mut self, id: i64) -> i64 {
+        result_code(self.as_owner(|contract| {
+            contract.admin.set_banned(id, false);
+            Ok(())
+        }))
+    }
+
+    pub fn get_player_banned(&self, id: i64) -> i64 {
+        self.admin.is_banned(id) as i64
+    }
+
+    /// Declare the winner of a round that has run its length. Anyone can
+    /// call this, since it only ever does what the round length already
+    /// decided, and a round shouldn't stay open because its owner is away.
+    pub fn end_round(&mut self, game_id: i64) -> i64 {
+        let mut game = match self.game(game_id) {
+            Some(game) => game,
+            None => return ContractError::NotAccepting.code(),
+        };
+        result_code(if game.end_round() {
+            Ok(())
+        } else {
+            Err(ContractError::NotAccepting)
+        })
+    }
+
+    /// Clear the board after a finished round and let players spawn for the
+    /// next one. Owner only.
+    pub fn open_lobby(&mut self, game_id: i64) -> i64 {
+        result_code(self.as_owner(|contract| {
+            let mut game = contract.game(game_id).ok_or(ContractError::NotAccepting)?;
+            if !game.open_lobby() {
+                return Err(ContractError::NotAccepting);
+            }
+            // Players who waited through the last round go first
+            WaitingQueue::load(game.id()).admit(&game);
+            Ok(())
+        }))
+    }
+
+    /// Start the clock on the round waiting in the lobby. Owner only.
+    pub fn start_round(&mut self, game_id: i64) -> i64 {
+        result_code(self.as_owner(|contract| {
+            let mut game = contract.game(game_id).ok_or(ContractError::NotAccepting)?;
+            if game.start_round() {
+                Ok(())
+            } else {
+                Err(ContractError::NotAccepting)
+            }
+        }))
+    }
+
+    /// Spawn the sender at (x, y), or put them in the waiting queue if their
+    /// game is full. Waiting players are spawned at the point they last asked
+    /// for once someone dies. A live sender can't spawn again. Returns 0, or
+    /// the `ContractError` code of why nothing happened.
+    pub fn spawn_player(&self, x: i64, y: i64) -> i64 {
+        result_code(self.spawn(api_get_sender_address(), x, y))
     }
 
     pub fn get_player_x(&self, id: i64) -> i64 {
-        let player = Player::load(id);
-        let (x, _) = player.get_position(self.get_current_game_tick());
+        let (player, now_tick) = self.player(id);
+        let (x, _) = player.get_position(now_tick);
         x
     }
 
     pub fn get_player_y(&self, id: i64) -> i64 {
-        let player = Player::load(id);
-        let (_, y) = player.get_position(self.get_current_game_tick());
+        let (player, now_tick) = self.player(id);
+        let (_, y) = player.get_position(now_tick);
         y
     }
 
     pub fn get_player_heading(&self, id: i64) -> i64 {
-        let player = Player::load(id);
+        let (player, _) = self.player(id);
         player.get_heading().into()
     }
 
-    pub fn apply_input(&mut self, heading: i64) {
-        let now_tick = self.get_current_game_tick();
-        let mut player = Player::load(api_get_sender_address());
-        player.apply_input(heading.into(), now_tick);
+    pub fn get_player_area(&self, id: i64) -> i64 {
+        self.player(id).0.get_area()
+    }
+
+    pub fn get_player_kills(&self, id: i64) -> i64 {
+        self.player(id).0.get_kills()
+    }
+
+    /// Ticks the player's current or most recent life has lasted
+    pub fn get_player_survival(&self, id: i64) -> i64 {
+        let (player, now_tick) = self.player(id);
+        player.get_survival(now_tick)
+    }
+
+    /// Timestamp of the transaction that spawned the player's current or
+    /// most recent life. For a player who waited in the queue, that is the
+    /// one that let them in.
+    pub fn get_player_spawn_time(&self, id: i64) -> i64 {
+        self.player(id).0.get_spawn_time()
+    }
+
+    pub fn get_player_alive(&self, id: i64) -> i64 {
+        self.player(id).0.is_alive() as i64
+    }
+
+    /// Tick the player's latest boost ends on, which is in the past once it
+    /// has run out
+    pub fn get_player_boost_end(&self, id: i64) -> i64 {
+        self.player(id).0.get_boost().end
+    }
+
+    /// First tick the player can boost again
+    pub fn get_player_boost_ready(&self, id: i64) -> i64 {
+        self.player(id).0.get_boost_ready()
+    }
+
+    /// Cells of trail the player has laid since they last captured
+    pub fn get_player_trail_len(&self, id: i64) -> i64 {
+        self.player(id).0.trail_len()
+    }
+
+    /// Grid index of the `i`th cell of the player's trail, counting from
+    /// where they left their territory, or -1 past the end
+    pub fn get_player_trail_cell(&self, id: i64, i: i64) -> i64 {
+        let (player, _) = self.player(id);
+        if i < 0 || i >= player.trail_len() {
+            return -1;
+        }
+        player.trail_cell(i).index()
+    }
+
+    /// Owner of the territory at a grid cell, or 0 if it is unclaimed
+    pub fn get_cell_owner(&self, game_id: i64, col: i64, row: i64) -> i64 {
+        let cell = Cell { col, row };
+        if self.game(game_id).is_none() || !cell.in_bounds() {
+            return 0;
+        }
+        Board::load(game_id).owner(cell).unwrap_or(0)
+    }
+
+    /// Number of live players in a game
+    pub fn get_player_count(&self, game_id: i64) -> i64 {
+        match self.game(game_id) {
+            Some(game) => Roster::load(game.id()).len(),
+            None => 0,
+        }
+    }
+
+    /// One of a game's live players, in no particular order, for going
+    /// through all `get_player_count` of them. 0 past the end.
+    pub fn get_live_player(&self, game_id: i64, index: i64) -> i64 {
+        match self.game(game_id) {
+            Some(game) => Roster::load(game.id()).get(index),
+            None => 0,
+        }
+    }
+
+    /// Players on a game's leaderboard: every live player, up to `LEADERBOARD_NUM`
+    pub fn get_leaderboard_size(&self, game_id: i64) -> i64 {
+        match self.game(game_id) {
+            Some(game) => Leaderboard::load(game.id()).len(),
+            None => 0,
+        }
+    }
+
+    /// Address of the live player at `rank` (from zero) in a game when sorted
+    /// by territory, then kills, or 0 below the leaderboard
+    pub fn get_leaderboard(&self, game_id: i64, rank: i64) -> i64 {
+        match self.game(game_id) {
+            Some(game) => Leaderboard::load(game.id()).get(rank),
+            None => 0,
+        }
+    }
+
+    /// Turn the sender. Returns 0, or the `ContractError` code of why they
+    /// didn't turn.
+    pub fn apply_input(&mut self, heading: i64) -> i64 {
+        if heading < 0 || heading > PlayerHeading::NoHeading.as_i64() {
+            return ContractError::InvalidHeading.code();
+        }
+        result_code(self.act(api_get_sender_address(), |player, now_tick| {
+            player.apply_input(heading.into(), now_tick);
+            Ok(())
+        }))
+    }
+
+    /// Speed the sender up for a while. Returns 0, or the `ContractError`
+    /// code of why they didn't, such as their last boost cooling down.
+    pub fn activate_boost(&mut self) -> i64 {
+        result_code(self.act(api_get_sender_address(), |player, now_tick| {
+            if player.activate_boost(now_tick) {
+                Ok(())
+            } else {
+                Err(ContractError::BoostCoolingDown)
+            }
+        }))
     }
 
-    pub fn get_current_game_tick(&self) -> i64 {
-        let start_time = self.get_start_time();
-        let now_time = api_get_timestamp();
-        ms_delta_to_tick(now_time - start_time)
+    pub fn get_current_game_tick(&self, game_id: i64) -> i64 {
+        self.game(game_id).map_or(0, |game| game.get_current_tick())
     }
 }
 
-/// Convert a delta in milliseconds to a game tick incremented from zero
-fn ms_delta_to_tick(delta_t: i64) -> i64 {
-    let tick = ((delta_t as f64) / 1000.0) * 60.0;
-    tick.floor() as i64
+impl P2PIOContract {
+    fn game(&self, game_id: i64) -> Option<Game> {
+        self.games.get(game_id)
+    }
+
+    fn team_score(&self, game_id: i64, team: i64) -> TeamScore {
+        self.game(game_id)
+            .and_then(|game| Teams::load(&game).score(team))
+            .unwrap_or_default()
+    }
+
+    /// Run `action` if the sender owns the contract
+    fn as_owner<F>(&mut self, action: F) -> Result<(), ContractError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), ContractError>,
+    {
+        if !self.admin.is_owner(api_get_sender_address()) {
+            return Err(ContractError::NotOwner);
+        }
+        action(self)
+    }
+
+    /// Take a player off the board of `game` and out of its waiting queue
+    fn remove(&self, id: i64, game: &Game) {
+        let mut queue = WaitingQueue::load(game.id());
+        queue.remove(id);
+        let mut player = Player::load(game.id(), id);
+        if player.is_alive() {
+            let mut board = Board::load(game.id());
+            board.kill(&mut player, game.get_current_tick());
+            board.commit_scores();
+            queue.admit(game);
+        }
+    }
+
+    /// Whether `id` may spawn or steer in `game` at all
+    fn check_access(&self, id: i64, game: &Game) -> Result<(), ContractError> {
+        if self.admin.is_banned(id) {
+            Err(ContractError::Banned)
+        } else if game.is_paused_at(api_get_timestamp()) {
+            Err(ContractError::Paused)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Spawn `id` at (x, y) in the game they are in, or queue them if it is
+    /// full
+    fn spawn(&self, id: i64, x: i64, y: i64) -> Result<(), ContractError> {
+        let mut game = self.games.game_of(id);
+        self.check_access(id, &game)?;
+        if !game.accepts_spawns() {
+            return Err(ContractError::NotAccepting);
+        }
+        let now_tick = game.checked_tick(id)?;
+        if Player::load(game.id(), id).is_alive() {
+            return Err(ContractError::AlreadyAlive);
+        }
+        game.observe();
+        let mut queue = WaitingQueue::load(game.id());
+        if game.is_full() {
+            queue.push(id, x, y);
+            return Ok(());
+        }
+        queue.remove(id);
+
+        let mut board = Board::load(game.id());
+        let mut player = Player::spawn(game.id(), id, x, y, now_tick);
+        board.spawn(&mut player, x, y);
+        board.commit_scores();
+        Ok(())
+    }
+
+    /// Move a live player up to the transaction's tick, then have them
+    /// `action` if they survived. Their clock is checked against the round
+    /// first, so stale or future timestamps change nothing. An action from
+    /// behind where the board had moved them has their trail laid again.
+    fn act<F>(&mut self, id: i64, action: F) -> Result<(), ContractError>
+    where
+        F: FnOnce(&mut Player, i64) -> Result<(), ContractError>,
+    {
+        let mut game = self.games.game_of(id);
+        self.check_access(id, &game)?;
+        if !game.accepts_input() {
+            return Err(ContractError::NotAccepting);
+        }
+        let mut player = Player::load(game.id(), id);
+        if !player.is_alive() {
+            return Err(ContractError::NotAlive);
+        }
+        let now_tick = player.checked_tick(game.checked_tick(id)?);
+        game.observe();
+        let mut board = Board::load(game.id());
+        board.advance(&mut player, now_tick);
+        let result = if player.is_alive() {
+            action(&mut player, now_tick)
+        } else {
+            Err(ContractError::NotAlive)
+        };
+        if result.is_ok() {
+            board.relay(&mut player, now_tick);
+        }
+        board.commit_scores();
+        WaitingQueue::load(game.id()).admit(&game);
+        result
+    }
+
+    /// A player in the game they are in, with that game's current tick
+    fn player(&self, id: i64) -> (Player, i64) {
+        let game = self.games.game_of(id);
+        (Player::load(game.id(), id), game.get_current_tick())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    #[test]
-    fn test_ms_delta_to_tick() {
-        assert_eq!(ms_delta_to_tick(0), 0);
-        assert_eq!(ms_delta_to_tick(6000), 360);
-    }
+    use crate::host::TestHost;
 
     #[test]
     fn test_spawn_player() {
+        let _host = TestHost::new();
         let mut contract = P2PIOContract::default();
         contract.init();
         contract.spawn_player(0, 15);
         assert_eq!(contract.get_player_x(0), 0);
         assert_eq!(contract.get_player_y(0), 15);
     }
-}
\ No newline at end of file
+
+    const START: i64 = 1_600_000_000_000;
+
+    #[test]
+    fn test_spawning_again_while_alive() {
+        let (owner, id) = (0, 15);
+        let host = TestHost::new();
+        host.at(START).sender(owner);
+        let mut contract = P2PIOContract::default();
+        contract.init();
+        host.sender(id);
+        let (x, y) = point(100, 100);
+        assert_eq!(contract.spawn_player(x, y), 0);
+        assert_eq!(contract.apply_input(PlayerHeading::Right.as_i64()), 0);
+
+        // A live player can't jump elsewhere by spawning
+        host.wait(1000);
+        let (fx, fy) = point(200, 200);
+        assert_eq!(contract.spawn_player(fx, fy), ContractError::AlreadyAlive.code());
+        assert_eq!(position(&contract, id), (x + 60, y));
+        assert_eq!(contract.get_player_heading(id), PlayerHeading::Right.as_i64());
+
+        // Once dead they spawn where they ask
+        host.sender(owner);
+        assert_eq!(contract.kick_player(id), 0);
+        host.sender(id);
+        assert_eq!(contract.spawn_player(fx, fy), 0);
+        assert_eq!(position(&contract, id), (fx, fy));
+    }
+
+    fn point(col: i64, row: i64) -> (i64, i64) {
+        (GRID_ORIGIN + col * CELL_WIDTH, GRID_ORIGIN + row * CELL_WIDTH)
+    }
+
+    fn position(contract: &P2PIOContract, id: i64) -> (i64, i64) {
+        (contract.get_player_x(id), contract.get_player_y(id))
+    }
+
+    #[test]
+    fn test_current_game_tick() {
+        let host = TestHost::new();
+        host.at(START);
+        let mut contract = P2PIOContract::default();
+        contract.init();
+        assert_eq!(contract.get_current_game_tick(0), 0);
+        host.wait(1000);
+        assert_eq!(contract.get_current_game_tick(0), 60);
+        // A clock behind the start of the round reads as its first tick
+        host.at(START - 5000);
+        assert_eq!(contract.get_current_game_tick(0), 0);
+
+        host.at(START + 10_000);
+        let timed = contract.create_game(120, 0, 0);
+        host.wait(500);
+        assert_eq!(contract.get_current_game_tick(timed), 30);
+        assert_eq!(contract.get_current_game_tick(0), 630);
+        // Held at the round length until the round is ended, then frozen
+        host.wait(5000);
+        assert_eq!(contract.get_current_game_tick(timed), 120);
+        assert_eq!(contract.end_round(timed), 0);
+        host.wait(60_000);
+        assert_eq!(contract.get_current_game_tick(timed), 120);
+        assert_eq!(contract.get_current_game_tick(0), 4530);
+    }
+
+    #[test]
+    fn test_players_turn_on_their_own_ticks() {
+        let (a, b) = (11, 12);
+        let host = TestHost::new();
+        host.at(START);
+        let mut contract = P2PIOContract::default();
+        contract.init();
+
+        let (ax, ay) = point(100, 100);
+        let (bx, by) = point(200, 100);
+        host.sender(a);
+        assert_eq!(contract.spawn_player(ax, ay), 0);
+        host.wait(500).sender(b);
+        assert_eq!(contract.spawn_player(bx, by), 0);
+        host.sender(a);
+        assert_eq!(contract.apply_input(PlayerHeading::Right.as_i64()), 0);
+        host.wait(500).sender(b);
+        assert_eq!(contract.apply_input(PlayerHeading::Up.as_i64()), 0);
+
+        host.wait(1000);
+        assert_eq!(contract.get_current_game_tick(0), 120);
+        assert_eq!(position(&contract, a), (ax + 90, ay));
+        assert_eq!(position(&contract, b), (bx, by + 60));
+
+        host.sender(a);
+        assert_eq!(contract.apply_input(PlayerHeading::Down.as_i64()), 0);
+        host.wait(500);
+        assert_eq!(position(&contract, a), (ax + 90, ay - 30));
+        assert_eq!(position(&contract, b), (bx, by + 90));
+        assert_eq!(contract.get_player_heading(a), PlayerHeading::Down.as_i64());
+    }
+
+    #[test]
+    fn test_crossing_trail_kills() {
+        let (prey, hunter) = (21, 22);
+        let host = TestHost::new();
+        host.at(START);
+        let mut contract = P2PIOContract::default();
+        contract.init();
+
+        let (x, y) = point(50, 50);
+        host.sender(prey);
+        contract.spawn_player(x, y);
+        contract.apply_input(PlayerHeading::Right.as_i64());
+        let (x, y) = point(53, 47);
+        host.sender(hunter);
+        contract.spawn_player(x, y);
+        contract.apply_input(PlayerHeading::Up.as_i64());
+
+        // The prey lays trail up to column 54, then the hunter runs through it
+        host.wait(3000).sender(prey);
+        assert_eq!(contract.apply_input(PlayerHeading::Right.as_i64()), 0);
+        host.sender(hunter);
+        assert_eq!(contract.apply_input(PlayerHeading::Up.as_i64()), 0);
+
+        assert_eq!(contract.get_player_alive(prey), 0);
+        assert_eq!(contract.get_player_kills(hunter), 1);
+        host.wait(500).sender(prey);
+        assert_eq!(contract.apply_input(PlayerHeading::Up.as_i64()), ContractError::NotAlive.code());
+    }
+
+    #[test]
+    fn test_skewed_clocks_are_rejected() {
+        use crate::movement::MAX_FUTURE_MS;
+
+        let (id, rival) = (31, 32);
+        let host = TestHost::new();
+        host.at(START).sender(id);
+        let mut contract = P2PIOContract::default();
+        contract.init();
+        let (x, y) = point(100, 100);
+        contract.spawn_player(x, y);
+        let (rx, ry) = point(250, 250);
+        host.sender(rival);
+        contract.spawn_player(rx, ry);
+        host.wait(1000).sender(id);
+        assert_eq!(contract.apply_input(PlayerHeading::Up.as_i64()), 0);
+
+        let stale = ContractError::StaleTimestamp.code();
+        host.at(START - 1);
+        assert_eq!(contract.spawn_player(x, y), stale);
+        // Further ahead of the latest call in the game than clocks drift
+        host.at(START + 1000 + MAX_FUTURE_MS + 1000);
+        let future = ContractError::FutureTimestamp.code();
+        assert_eq!(contract.apply_input(PlayerHeading::Left.as_i64()), future);
+
+        // None of them changed anything
+        host.at(START + 2000);
+        assert_eq!(contract.get_player_heading(id), PlayerHeading::Up.as_i64());
+        assert_eq!(position(&contract, id), (x, y + 60));
+    }
+
+    #[test]
+    fn test_late_turns_lay_the_same_trail() {
+        use crate::grid::Cell;
+
+        // The same player turning on two peers that can't see each other,
+        // each turn well over a second from the last
+        let id = 35;
+        let turns = [
+            (1000, PlayerHeading::Right),
+            (2500, PlayerHeading::Down),
+            (4000, PlayerHeading::Left),
+            (5500, PlayerHeading::Down),
+        ];
+        let (x, y) = point(100, 100);
+        let state = |order: &[usize]| {
+            let host = TestHost::new();
+            host.at(START).sender(id);
+            let mut contract = P2PIOContract::default();
+            contract.init();
+            assert_eq!(contract.spawn_player(x, y), 0);
+            for &turn in order {
+                let (ms, heading) = turns[turn];
+                host.at(START + ms);
+                assert_eq!(contract.apply_input(heading.as_i64()), 0);
+            }
+
+            host.at(START + 7000);
+            let board = Board::load(0);
+            let cells = (90..110)
+                .flat_map(|row| (90..110).map(move |col| Cell { col, row }))
+                .map(|cell| (board.owner(cell), board.trail(cell)))
+                .collect::<Vec<_>>();
+            let trail = (0..contract.get_player_trail_len(id))
+                .map(|i| contract.get_player_trail_cell(id, i))
+                .collect::<Vec<_>>();
+            (position(&contract, id), contract.get_player_heading(id), trail, cells)
+        };
+
+        // One branch merged before, after and in between the other
+        let in_order = state(&[0, 1, 2, 3]);
+        assert_eq!(in_order.0, (x, y - 180));
+        assert!(!in_order.2.is_empty());
+        for order in [[1, 3, 0, 2], [0, 2, 1, 3], [0, 1, 3, 2], [3, 2, 1, 0]].iter() {
+            assert_eq!(state(order), in_order, "Order {:?}", order);
+        }
+    }
+
+    #[test]
+    fn test_quiet_games_catch_up() {
+        use crate::movement::MAX_FUTURE_MS;
+
+        let (id, rival) = (33, 34);
+        let host = TestHost::new();
+        host.at(START).sender(id);
+        let mut contract = P2PIOContract::default();
+        contract.init();
+        contract.spawn_player(point(100, 100).0, point(100, 100).1);
+        assert_eq!(contract.apply_input(PlayerHeading::Right.as_i64()), 0);
+
+        // Alone in the game, however long they go without turning
+        host.wait(2 * MAX_FUTURE_MS);
+        assert_eq!(contract.apply_input(PlayerHeading::Up.as_i64()), 0);
+
+        let (rx, ry) = point(250, 250);
+        host.sender(rival);
+        assert_eq!(contract.spawn_player(rx, ry), 0);
+
+        // Everyone goes quiet for longer than the round had run. Sending the
+        // call again doesn't get it through.
+        host.wait(2 * MAX_FUTURE_MS + 1000).sender(id);
+        let future = ContractError::FutureTimestamp.code();
+        for _ in 0..5 {
+            assert_eq!(contract.apply_input(PlayerHeading::Left.as_i64()), future);
+        }
+        // Quiet for as long as the round had run, which it catches up on
+        host.at(START + 4 * MAX_FUTURE_MS);
+        assert_eq!(contract.apply_input(PlayerHeading::Left.as_i64()), 0);
+        // And the next call may be ahead by the longer round
+        host.wait(4 * MAX_FUTURE_MS).sender(rival);
+        assert_eq!(contract.apply_input(PlayerHeading::Down.as_i64()), 0);
+    }
+
+    #[test]
+    fn test_owner_only_calls() {
+        let (owner, player, heir) = (41, 42, 43);
+        let host = TestHost::new();
+        host.at(START).sender(owner);
+        let mut contract = P2PIOContract::default();
+        contract.init();
+        assert_eq!(contract.get_owner(), owner);
+
+        let not_owner = ContractError::NotOwner.code();
+        host.sender(player);
+        assert_eq!(contract.configure(0, 600, 2), not_owner);
+        assert_eq!(contract.pause_game(0), not_owner);
+        assert_eq!(contract.ban_player(owner), not_owner);
+        assert_eq!(contract.transfer_ownership(player), not_owner);
+        assert_eq!(contract.get_round_length(0), 0);
+
+        host.sender(owner);
+        assert_eq!(contract.configure(0, 600, 2), 0);
+        assert_eq!(contract.get_round_length(0), 600);
+        assert_eq!(contract.get_max_players(0), 2);
+
+        let (x, y) = point(100, 100);
+        assert_eq!(contract.pause_game(0), 0);
+        host.sender(player);
+        assert_eq!(contract.spawn_player(x, y), ContractError::Paused.code());
+        host.sender(owner);
+        assert_eq!(contract.resume_game(0), 0);
+        host.sender(player);
+        assert_eq!(contract.spawn_player(x, y), 0);
+
+        host.wait(1000).sender(owner);
+        assert_eq!(contract.kick_player(player), 0);
+        assert_eq!(contract.get_player_alive(player), 0);
+        host.sender(player);
+        assert_eq!(contract.spawn_player(x, y), 0);
+
+        host.sender(owner);
+        assert_eq!(contract.ban_player(player), 0);
+        assert_eq!(contract.get_player_alive(player), 0);
+        host.sender(player);
+        assert_eq!(contract.spawn_player(x, y), ContractError::Banned.code());
+        host.sender(owner);
+        assert_eq!(contract.unban_player(player), 0);
+        host.sender(player);
+        assert_eq!(contract.spawn_player(x, y), 0);
+
+        // Cut short with the player alive, who wins the round
+        host.wait(1000).sender(owner);
+        assert_eq!(contract.reset_round(0), 0);
+        assert_eq!(contract.get_round(0), 2);
+        assert_eq!(contract.get_winner(0), player);
+        assert_eq!(contract.get_phase(0), 1);
+
+        assert_eq!(contract.transfer_ownership(heir), 0);
+        assert_eq!(contract.get_owner(), heir);
+        assert_eq!(contract.pause_game(0), not_owner);
+    }
+
+    #[test]
+    fn test_round_calls() {
+        let (owner, player) = (61, 62);
+        let host = TestHost::new();
+        host.at(START).sender(owner);
+        let mut contract = P2PIOContract::default();
+        contract.init();
+        let game = contract.create_game(120, 0, 0);
+
+        let not_accepting = ContractError::NotAccepting.code();
+        let not_owner = ContractError::NotOwner.code();
+        host.sender(player);
+        assert_eq!(contract.create_game(120, 0, 0), -not_owner);
+        assert_eq!(contract.get_game_count(), 2);
+        assert_eq!(contract.end_round(game), not_accepting);
+        assert_eq!(contract.end_round(game + 1), not_accepting);
+        host.wait(2000);
+        assert_eq!(contract.end_round(game), 0);
+        assert_eq!(contract.get_phase(game), 2);
+        assert_eq!(contract.end_round(game), not_accepting);
+
+        assert_eq!(contract.open_lobby(game), not_owner);
+        host.sender(owner);
+        assert_eq!(contract.start_round(game), not_accepting);
+        assert_eq!(contract.open_lobby(game), 0);
+        assert_eq!(contract.get_phase(game), 0);
+        assert_eq!(contract.get_round(game), 2);
+        assert_eq!(contract.open_lobby(game), not_accepting);
+
+        host.sender(player);
+        assert_eq!(contract.start_round(game), not_owner);
+        host.sender(owner);
+        assert_eq!(contract.start_round(game), 0);
+        assert_eq!(contract.get_phase(game), 1);
+        assert_eq!(contract.start_round(game), not_accepting);
+    }
+
+    #[test]
+    fn test_calls_from_before_a_pause_count() {
+        let (owner, player) = (53, 54);
+        let host = TestHost::new();
+        host.at(START).sender(owner);
+        let mut contract = P2PIOContract::default();
+        contract.init();
+
+        let (x, y) = point(100, 100);
+        host.sender(player);
+        assert_eq!(contract.spawn_player(x, y), 0);
+        assert_eq!(contract.apply_input(PlayerHeading::Right.as_i64()), 0);
+        host.wait(1000).sender(owner);
+        assert_eq!(contract.pause_game(0), 0);
+
+        // Made before the pause but committed after it, so taken at its tick
+        host.at(START + 500).sender(player);
+        assert_eq!(contract.apply_input(PlayerHeading::Up.as_i64()), 0);
+        host.at(START + 5000);
+        assert_eq!(position(&contract, player), (x + 30, y + 30));
+        assert_eq!(contract.apply_input(PlayerHeading::Left.as_i64()), ContractError::Paused.code());
+    }
+
+    #[test]
+    fn test_pause_freezes_players() {
+        let (owner, player) = (51, 52);
+        let host = TestHost::new();
+        host.at(START).sender(owner);
+        let mut contract = P2PIOContract::default();
+        contract.init();
+
+        let (x, y) = point(100, 100);
+        host.sender(player);
+        contract.spawn_player(x, y);
+        contract.apply_input(PlayerHeading::Right.as_i64());
+        host.wait(1000).sender(owner);
+        assert_eq!(contract.pause_game(0), 0);
+        assert_eq!(contract.get_paused(0), 1);
+
+        host.wait(5000);
+        assert_eq!(contract.get_current_game_tick(0), 60);
+        assert_eq!(position(&contract, player), (x + 60, y));
+        host.sender(player);
+        assert_eq!(contract.apply_input(PlayerHeading::Up.as_i64()), ContractError::Paused.code());
+
+        host.sender(owner);
+        assert_eq!(contract.get_paused_ms(0), 0);
+        assert_eq!(contract.resume_game(0), 0);
+        assert_eq!(contract.get_paused_ms(0), 5000);
+        host.wait(500);
+        assert_eq!(contract.get_current_game_tick(0), 90);
+        assert_eq!(position(&contract, player), (x + 90, y));
+    }
+
+    /// Random walks through the entry points, checking positions against
+    /// ones worked out a tick at a time from the spawn point
+    #[test]
+    fn test_movement_properties() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        // One unit a tick along the heading, and none without one
+        let velocity = |heading: PlayerHeading| match heading {
+            PlayerHeading::Up => (0, 1),
+            PlayerHeading::Down => (0, -1),
+            PlayerHeading::Left => (-1, 0),
+            PlayerHeading::Right => (1, 0),
+            PlayerHeading::NoHeading => (0, 0),
+        };
+
+        let (owner, host) = (0, TestHost::new());
+        let mut contract = P2PIOContract::default();
+        contract.init();
+        let middle = GRID_ORIGIN + GRID_COUNT / 2 * CELL_WIDTH;
+
+        for seed in 0..32 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            // A game each, so walks don't run into each other
+            host.at(START).sender(owner);
+            let game = contract.create_game(0, 0, 0);
+            let id = 5000 + seed as i64;
+            let observe = |contract: &P2PIOContract| {
+                let heading = PlayerHeading::from(contract.get_player_heading(id));
+                (contract.get_player_x(id), contract.get_player_y(id), heading)
+            };
+            host.sender(id);
+            contract.join_game(game);
+            host.at(START + rng.gen_range(0, 1000));
+            assert_eq!(contract.spawn_player(middle, middle), 0);
+
+            let mut tick = contract.get_current_game_tick(game);
+            let (mut x, mut y, mut heading) = observe(&contract);
+            assert_eq!((x, y), (middle, middle));
+            for _ in 0..50 {
+                host.wait(rng.gen_range(0, 500));
+                let now = contract.get_current_game_tick(game);
+                let (dx, dy) = velocity(heading);
+                for _ in tick..now {
+                    x += dx;
+                    y += dy;
+                }
+                let seen = observe(&contract);
+                assert_eq!((seen.0, seen.1), (x, y), "Seed {}", seed);
+                assert_eq!(seen.2, heading);
+
+                let code = contract.apply_input(rng.gen_range(0, 5));
+                if contract.get_player_alive(id) == 0 {
+                    // Ran into their own trail
+                    assert_eq!(code, ContractError::NotAlive.code());
+                    break;
+                }
+                assert_eq!(code, 0);
+                // Turning doesn't move the player on the tick they turn
+                let turned = observe(&contract);
+                assert_eq!((turned.0, turned.1), (x, y), "Seed {}", seed);
+
+                tick = now;
+                heading = turned.2;
+            }
+        }
+    }
+}
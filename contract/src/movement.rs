@@ -0,0 +1,108 @@
+use crate::heading::PlayerHeading;
+
+/// How many times faster a boosted player moves
+pub const BOOST_MULTIPLIER: i64 = 2;
+/// Ticks a boost lasts
+pub const BOOST_DURATION: i64 = 120;
+/// Ticks from activating a boost until the next one can be activated
+pub const BOOST_COOLDOWN: i64 = 600;
+/// Furthest, in milliseconds, a call can be timestamped ahead of the latest
+/// one its game accepted. Someone in a game being played turns well within
+/// this, so a call further ahead is a broken or malicious clock.
+pub const MAX_FUTURE_MS: i64 = 30_000;
+
+/// Convert a delta in milliseconds to a game tick incremented from zero
+pub fn ms_delta_to_tick(delta_t: i64) -> i64 {
+    let tick = ((delta_t as f64) / 1000.0) * 60.0;
+    tick.floor() as i64
+}
+
+/// Ticks from `start` up to, but not including, `end` during which a player
+/// moves `BOOST_MULTIPLIER` times as fast
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+pub struct Boost {
+    pub start: i64,
+    pub end: i64,
+}
+
+impl Boost {
+    pub fn starting_at(tick: i64) -> Self {
+        Boost {
+            start: tick,
+            end: tick + BOOST_DURATION,
+        }
+    }
+
+    /// Number of boosted ticks between `from` and `to`
+    pub fn overlap(&self, from: i64, to: i64) -> i64 {
+        (to.min(self.end) - from.max(self.start)).max(0)
+    }
+}
+
+/// Distance covered between two ticks, moving one unit a tick outside of
+/// `boost`
+pub fn distance(from_tick: i64, to_tick: i64, boost: Boost) -> i64 {
+    (to_tick - from_tick) + (BOOST_MULTIPLIER - 1) * boost.overlap(from_tick, to_tick)
+}
+
+/// Move `distance` from (x, y) along `heading`
+pub fn step(x: i64, y: i64, heading: PlayerHeading, distance: i64) -> (i64, i64) {
+    match heading {
+        PlayerHeading::Up => (x, y + distance),
+        PlayerHeading::Down => (x, y - distance),
+        PlayerHeading::Left => (x - distance, y),
+        PlayerHeading::Right => (x + distance, y),
+        PlayerHeading::NoHeading => (x, y),
+    }
+}
+
+/// Position at `now_tick` of a player that was at (x, y) on `tick`, given
+/// their heading and latest boost. Speed only changes at the boost edges, so
+/// this is exact however far `now_tick` is from the anchor, and anything
+/// replaying the DAG gets the same answer as the contract.
+pub fn position_at(
+    (x, y, tick): (i64, i64, i64),
+    heading: PlayerHeading,
+    boost: Boost,
+    now_tick: i64,
+) -> (i64, i64) {
+    step(x, y, heading, distance(tick, now_tick, boost))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ms_delta_to_tick() {
+        assert_eq!(ms_delta_to_tick(0), 0);
+        assert_eq!(ms_delta_to_tick(6000), 360);
+    }
+
+    #[test]
+    fn test_distance_across_boost() {
+        let boost = Boost::starting_at(100);
+        assert_eq!(distance(0, 50, boost), 50);
+        // Half of the span is boosted
+        assert_eq!(distance(40, 160, boost), 120 + 60);
+        // Entirely inside the boost
+        assert_eq!(distance(110, 130, boost), 40);
+        assert_eq!(distance(0, 1000, boost), 1000 + BOOST_DURATION);
+        assert_eq!(distance(500, 600, boost), 100);
+    }
+
+    #[test]
+    fn test_position_is_piecewise() {
+        let boost = Boost::starting_at(10);
+        let anchor = (0, 0, 0);
+        let direct = position_at(anchor, PlayerHeading::Right, boost, 200);
+
+        // Re-anchoring part way gives the same result
+        let (x, y) = position_at(anchor, PlayerHeading::Right, boost, 70);
+        assert_eq!(
+            position_at((x, y, 70), PlayerHeading::Right, boost, 200),
+            direct
+        );
+        assert_eq!(direct, (200 + BOOST_DURATION, 0));
+    }
+}
@@ -1,76 +1,489 @@
-use rustdag_wasm_api::globals::{
-    api_get_sender_address,
+use crate::host::{api_get_hash, api_get_timestamp, Mapping};
+use crate::index::{
+    compound_key, game_index, PLAYER_X_MAPPING_INDEX, PLAYER_Y_MAPPING_INDEX, PLAYER_HEADING_MAPPING_INDEX,
+    PLAYER_TICK_MAPPING_INDEX, PLAYER_LIFE_MAPPING_INDEX, PLAYER_ALIVE_MAPPING_INDEX,
+    PLAYER_SPAWN_TICK_MAPPING_INDEX, PLAYER_DEATH_TICK_MAPPING_INDEX, PLAYER_AREA_MAPPING_INDEX,
+    PLAYER_KILLS_MAPPING_INDEX, PLAYER_TRAIL_LEN_MAPPING_INDEX, PLAYER_TRAIL_CELL_MAPPING_INDEX,
+    PLAYER_MIN_COL_MAPPING_INDEX, PLAYER_MIN_ROW_MAPPING_INDEX, PLAYER_MAX_COL_MAPPING_INDEX,
+    PLAYER_MAX_ROW_MAPPING_INDEX, PLAYER_ROUND_MAPPING_INDEX, PLAYER_BOOST_START_MAPPING_INDEX,
+    PLAYER_BOOST_END_MAPPING_INDEX, PLAYER_BOOST_READY_MAPPING_INDEX, PLAYER_TEAM_MAPPING_INDEX,
+    PLAYER_INPUT_LEN_MAPPING_INDEX, PLAYER_INPUT_TICK_MAPPING_INDEX, PLAYER_INPUT_HEADING_MAPPING_INDEX,
+    PLAYER_ADVANCED_TICK_MAPPING_INDEX, PLAYER_SPAWN_TIME_MAPPING_INDEX, PLAYER_TRAIL_TICK_MAPPING_INDEX,
+    PLAYER_SETTLED_TICK_MAPPING_INDEX, PLAYER_INPUT_HASH_MAPPING_INDEX,
 };
-use rustdag_wasm_api::mapping::Mapping;
-
-use crate::index::{PLAYER_X_MAPPING_INDEX, PLAYER_Y_MAPPING_INDEX, PLAYER_HEADING_MAPPING_INDEX, PLAYER_TICK_MAPPING_INDEX};
 
+use crate::game::Game;
+use crate::grid::Cell;
 use crate::heading::PlayerHeading;
+use crate::movement::{position_at, Boost, BOOST_COOLDOWN};
+use crate::team::Teams;
+
+/// A turn that hasn't been folded into the player's anchor yet. Turns are
+/// ordered by tick, then by the hash of the transaction that sent them, so
+/// turns on the same tick always apply in the same order.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+pub struct Input {
+    pub tick: i64,
+    pub hash: i64,
+    pub heading: i64,
+}
 
+/// A player's state within one game
 pub struct Player {
     id: i64,
+    game: i64,
     x_mapping: Mapping,
     y_mapping: Mapping,
     heading_mapping: Mapping,
     tick_mapping: Mapping,
-}
-
-impl Default for Player {
-    fn default() -> Self {
-        Self::load(api_get_sender_address())
-    }
+    life_mapping: Mapping,
+    alive_mapping: Mapping,
+    spawn_tick_mapping: Mapping,
+    /// Timestamp of the transaction that spawned the current life, which is
+    /// later than the player asked if they had to wait for a place
+    spawn_time_mapping: Mapping,
+    death_tick_mapping: Mapping,
+    area_mapping: Mapping,
+    kills_mapping: Mapping,
+    trail_len_mapping: Mapping,
+    trail_cell_mapping: Mapping,
+    /// Tick of the leg of the path each trail cell was laid on
+    trail_tick_mapping: Mapping,
+    min_col_mapping: Mapping,
+    min_row_mapping: Mapping,
+    max_col_mapping: Mapping,
+    max_row_mapping: Mapping,
+    round_mapping: Mapping,
+    boost_start_mapping: Mapping,
+    boost_end_mapping: Mapping,
+    /// Tick from which the player can boost again
+    boost_ready_mapping: Mapping,
+    team_mapping: Mapping,
+    input_len_mapping: Mapping,
+    input_tick_mapping: Mapping,
+    input_heading_mapping: Mapping,
+    input_hash_mapping: Mapping,
+    /// Tick the board has laid the player's trail up to
+    advanced_tick_mapping: Mapping,
+    /// Tick up to which the board did something with the player's path
+    /// that can't be undone, like capturing or killing
+    settled_tick_mapping: Mapping,
 }
 
 impl Player {
-    pub fn load(id: i64) -> Self {
+    pub fn load(game: i64, id: i64) -> Self {
         Player {
             id,
-            x_mapping: Mapping(PLAYER_X_MAPPING_INDEX),
-            y_mapping: Mapping(PLAYER_Y_MAPPING_INDEX),
-            heading_mapping: Mapping(PLAYER_HEADING_MAPPING_INDEX),
-            tick_mapping: Mapping(PLAYER_TICK_MAPPING_INDEX),
+            game,
+            x_mapping: Mapping(game_index(game, PLAYER_X_MAPPING_INDEX)),
+            y_mapping: Mapping(game_index(game, PLAYER_Y_MAPPING_INDEX)),
+            heading_mapping: Mapping(game_index(game, PLAYER_HEADING_MAPPING_INDEX)),
+            tick_mapping: Mapping(game_index(game, PLAYER_TICK_MAPPING_INDEX)),
+            life_mapping: Mapping(game_index(game, PLAYER_LIFE_MAPPING_INDEX)),
+            alive_mapping: Mapping(game_index(game, PLAYER_ALIVE_MAPPING_INDEX)),
+            spawn_tick_mapping: Mapping(game_index(game, PLAYER_SPAWN_TICK_MAPPING_INDEX)),
+            spawn_time_mapping: Mapping(game_index(game, PLAYER_SPAWN_TIME_MAPPING_INDEX)),
+            death_tick_mapping: Mapping(game_index(game, PLAYER_DEATH_TICK_MAPPING_INDEX)),
+            area_mapping: Mapping(game_index(game, PLAYER_AREA_MAPPING_INDEX)),
+            kills_mapping: Mapping(game_index(game, PLAYER_KILLS_MAPPING_INDEX)),
+            trail_len_mapping: Mapping(game_index(game, PLAYER_TRAIL_LEN_MAPPING_INDEX)),
+            trail_cell_mapping: Mapping(game_index(game, PLAYER_TRAIL_CELL_MAPPING_INDEX)),
+            trail_tick_mapping: Mapping(game_index(game, PLAYER_TRAIL_TICK_MAPPING_INDEX)),
+            min_col_mapping: Mapping(game_index(game, PLAYER_MIN_COL_MAPPING_INDEX)),
+            min_row_mapping: Mapping(game_index(game, PLAYER_MIN_ROW_MAPPING_INDEX)),
+            max_col_mapping: Mapping(game_index(game, PLAYER_MAX_COL_MAPPING_INDEX)),
+            max_row_mapping: Mapping(game_index(game, PLAYER_MAX_ROW_MAPPING_INDEX)),
+            round_mapping: Mapping(game_index(game, PLAYER_ROUND_MAPPING_INDEX)),
+            boost_start_mapping: Mapping(game_index(game, PLAYER_BOOST_START_MAPPING_INDEX)),
+            boost_end_mapping: Mapping(game_index(game, PLAYER_BOOST_END_MAPPING_INDEX)),
+            boost_ready_mapping: Mapping(game_index(game, PLAYER_BOOST_READY_MAPPING_INDEX)),
+            team_mapping: Mapping(game_index(game, PLAYER_TEAM_MAPPING_INDEX)),
+            input_len_mapping: Mapping(game_index(game, PLAYER_INPUT_LEN_MAPPING_INDEX)),
+            input_tick_mapping: Mapping(game_index(game, PLAYER_INPUT_TICK_MAPPING_INDEX)),
+            input_heading_mapping: Mapping(game_index(game, PLAYER_INPUT_HEADING_MAPPING_INDEX)),
+            input_hash_mapping: Mapping(game_index(game, PLAYER_INPUT_HASH_MAPPING_INDEX)),
+            advanced_tick_mapping: Mapping(game_index(game, PLAYER_ADVANCED_TICK_MAPPING_INDEX)),
+            settled_tick_mapping: Mapping(game_index(game, PLAYER_SETTLED_TICK_MAPPING_INDEX)),
         }
     }
 
-    pub fn spawn(id: i64, x: i64, y: i64, tick: i64) -> Self {
-        let mut player = Self::load(id);
+    pub fn spawn(game: i64, id: i64, x: i64, y: i64, tick: i64) -> Self {
+        let mut player = Self::load(game, id);
         player.set_position(x, y, tick);
         player.set_heading(PlayerHeading::NoHeading);
+        player.set_inputs(&[]);
+        player.advanced_tick_mapping.set(id, tick);
+        player.settled_tick_mapping.set(id, tick);
+
+        // Each life gets a new number, so territory and trails left by a
+        // previous life stop counting without having to clear them
+        player.life_mapping.set(id, player.get_life() + 1);
+        player.alive_mapping.set(id, 1);
+        player.round_mapping.set(id, Game::load(game).get_round());
+        let teams = Teams::load(&Game::load(game));
+        if teams.count() > 0 && !teams.contains(player.get_team()) {
+            player.set_team(teams.smallest());
+        }
+        player.spawn_tick_mapping.set(id, tick);
+        player.spawn_time_mapping.set(id, api_get_timestamp());
+        player.area_mapping.set(id, 0);
+        player.kills_mapping.set(id, 0);
+        player.clear_trail();
+        player.set_boost(Boost::default());
+        player.boost_ready_mapping.set(id, tick);
+        if let Some(cell) = Cell::at(x, y) {
+            player.set_bounds(cell, cell);
+        }
 
         player
     }
 
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    pub fn game(&self) -> i64 {
+        self.game
+    }
+
+    pub fn get_life(&self) -> i64 {
+        self.life_mapping.get(self.id)
+    }
+
+    /// Whether the player is alive in the current round. Starting a new
+    /// round kills everyone without touching their entries.
+    pub fn is_alive(&self) -> bool {
+        self.alive_mapping.get(self.id) == 1
+            && self.round_mapping.get(self.id) == Game::load(self.game).get_round()
+    }
+
+    /// Whether territory or trail claimed during `life` still belongs to this player
+    pub fn holds(&self, life: i64) -> bool {
+        self.is_alive() && self.get_life() == life
+    }
+
+    /// Team the player is on, or 0 if their game has no teams
+    pub fn get_team(&self) -> i64 {
+        self.team_mapping.get(self.id)
+    }
+
+    pub fn set_team(&mut self, team: i64) {
+        self.team_mapping.set(self.id, team);
+    }
+
+    /// Whether `other` is this player or one of their teammates
+    pub fn is_ally(&self, other: i64) -> bool {
+        if other == self.id {
+            return true;
+        }
+        let team = self.get_team();
+        team != 0 && Player::load(self.game, other).get_team() == team
+    }
+
+    /// Freeze the player where they are at `now_tick`
+    pub fn kill(&mut self, now_tick: i64) {
+        self.anchor_at(now_tick);
+        self.set_inputs(&[]);
+        self.set_heading(PlayerHeading::NoHeading);
+        self.alive_mapping.set(self.id, 0);
+        self.death_tick_mapping.set(self.id, now_tick);
+        self.area_mapping.set(self.id, 0);
+        self.clear_trail();
+    }
+
+    pub fn get_area(&self) -> i64 {
+        self.area_mapping.get(self.id)
+    }
+
+    pub fn add_area(&mut self, delta: i64) {
+        self.area_mapping.set(self.id, self.get_area() + delta);
+    }
+
+    pub fn get_kills(&self) -> i64 {
+        self.kills_mapping.get(self.id)
+    }
+
+    pub fn add_kill(&mut self) {
+        self.kills_mapping.set(self.id, self.get_kills() + 1);
+    }
+
+    pub fn get_spawn_time(&self) -> i64 {
+        self.spawn_time_mapping.get(self.id)
+    }
+
+    /// Ticks survived by the current or most recent life
+    pub fn get_survival(&self, now_tick: i64) -> i64 {
+        let end = if self.is_alive() {
+            now_tick
+        } else {
+            self.death_tick_mapping.get(self.id)
+        };
+        end - self.spawn_tick_mapping.get(self.id)
+    }
+
+    pub fn trail_len(&self) -> i64 {
+        self.trail_len_mapping.get(self.id)
+    }
+
+    pub fn trail_cell(&self, i: i64) -> Cell {
+        Cell::from_index(self.trail_cell_mapping.get(compound_key(self.id, i)))
+    }
+
+    /// Tick of the leg of the path the trail cell was laid on
+    pub fn trail_tick(&self, i: i64) -> i64 {
+        self.trail_tick_mapping.get(compound_key(self.id, i))
+    }
+
+    pub fn push_trail(&mut self, cell: Cell, tick: i64) {
+        let len = self.trail_len();
+        self.trail_cell_mapping.set(compound_key(self.id, len), cell.index());
+        self.trail_tick_mapping.set(compound_key(self.id, len), tick);
+        self.trail_len_mapping.set(self.id, len + 1);
+    }
+
+    /// Take back the trail laid from the leg of the path `tick` is on, so a
+    /// turn that landed there can be laid again. Nothing before the settled
+    /// tick is taken back. Returns the cells taken back.
+    pub fn rewind_trail(&mut self, tick: i64) -> Vec<Cell> {
+        let settled = self.get_settled_tick();
+        let len = self.trail_len();
+        let from = (0..len)
+            .rev()
+            .map(|i| self.trail_tick(i))
+            .find(|leg| *leg <= tick)
+            .map_or(settled, |leg| leg.max(settled));
+        let kept = (0..len).find(|i| self.trail_tick(*i) >= from).unwrap_or(len);
+        let rewound = (kept..len).map(|i| self.trail_cell(i)).collect();
+        self.trail_len_mapping.set(self.id, kept);
+        self.advanced_tick_mapping.set(self.id, from);
+        rewound
+    }
+
+    pub fn clear_trail(&mut self) {
+        self.trail_len_mapping.set(self.id, 0);
+    }
+
+    /// Inclusive (min, max) corners of the cells claimed this life. Claims
+    /// are never shrunk, so this can cover cells since lost to others.
+    pub fn get_bounds(&self) -> (Cell, Cell) {
+        (
+            Cell {
+                col: self.min_col_mapping.get(self.id),
+                row: self.min_row_mapping.get(self.id),
+            },
+            Cell {
+                col: self.max_col_mapping.get(self.id),
+                row: self.max_row_mapping.get(self.id),
+            },
+        )
+    }
+
+    pub fn extend_bounds(&mut self, cell: Cell) {
+        let (min, max) = self.get_bounds();
+        self.set_bounds(
+            Cell { col: min.col.min(cell.col), row: min.row.min(cell.row) },
+            Cell { col: max.col.max(cell.col), row: max.row.max(cell.row) },
+        );
+    }
+
+    fn set_bounds(&mut self, min: Cell, max: Cell) {
+        self.min_col_mapping.set(self.id, min.col);
+        self.min_row_mapping.set(self.id, min.row);
+        self.max_col_mapping.set(self.id, max.col);
+        self.max_row_mapping.set(self.id, max.row);
+    }
+
+    /// Position the player was last anchored at, and the tick of the anchor.
+    /// Turns after the anchor are kept in `inputs` until the board settles
+    /// the path past them.
+    pub fn get_anchor(&self) -> (i64, i64, i64) {
+        (
+            self.x_mapping.get(self.id),
+            self.y_mapping.get(self.id),
+            self.tick_mapping.get(self.id),
+        )
+    }
+
+    /// Turns since the anchor, in order
+    pub fn inputs(&self) -> Vec<Input> {
+        (0..self.input_len_mapping.get(self.id))
+            .map(|i| Input {
+                tick: self.input_tick_mapping.get(compound_key(self.id, i)),
+                hash: self.input_hash_mapping.get(compound_key(self.id, i)),
+                heading: self.input_heading_mapping.get(compound_key(self.id, i)),
+            })
+            .collect()
+    }
+
+    /// Tick the board has laid the player's trail up to
+    pub fn get_advanced_tick(&self) -> i64 {
+        self.advanced_tick_mapping.get(self.id)
+    }
+
+    /// Tick before which the player's path can no longer change
+    pub fn get_settled_tick(&self) -> i64 {
+        self.settled_tick_mapping.get(self.id)
+    }
+
     pub fn get_position(&self, now_tick: i64) -> (i64, i64) {
-        let tick = self.tick_mapping.get(self.id);
+        if !self.is_alive() {
+            // Dead players, and players left over from an earlier round,
+            // stay where they were last anchored
+            let (x, y, _) = self.get_anchor();
+            return (x, y);
+        }
+        let (x, y, tick, heading) = self.last_turn(now_tick);
+        // Ticks before the anchor, from a clock running behind whoever
+        // moved the player last, stay at the anchor
+        position_at((x, y, tick), heading, self.get_boost(), now_tick.max(tick))
+    }
 
-        let delta_t = now_tick - tick;
-        if delta_t < 0 {
-            // Called on a tick before position was set
-            std::process::abort()
+    /// Points the player passes through from where the board last left them
+    /// up to `now_tick`, with the tick they pass each one: where they were,
+    /// where they turned and where they are now. The board won't be taken
+    /// over the same ticks again unless the trail is rewound.
+    pub fn advance_path(&mut self, now_tick: i64) -> Vec<(i64, i64, i64)> {
+        let from = self.get_advanced_tick();
+        let point = |tick| {
+            let (x, y) = self.get_position(tick);
+            (x, y, tick)
+        };
+        let mut path = vec![point(from)];
+        if now_tick <= from {
+            return path;
+        }
+        for input in self.inputs() {
+            if from < input.tick && input.tick < now_tick {
+                path.push(point(input.tick));
+            }
         }
-        let mut x = self.x_mapping.get(self.id);
-        let mut y = self.y_mapping.get(self.id);
-        let heading = self.get_heading();
+        path.push(point(now_tick));
+        self.advanced_tick_mapping.set(self.id, now_tick);
+        path
+    }
+
+    /// Tick a call from this player at `now_tick` takes effect on. A turn
+    /// from a clock running behind, or from a branch of the DAG merged late,
+    /// is placed in order however far behind the player's other turns it
+    /// is, so whether it is accepted doesn't depend on which arrived first.
+    /// Only turns from before the settled tick are caught up to it.
+    pub fn checked_tick(&self, now_tick: i64) -> i64 {
+        now_tick.max(self.get_settled_tick())
+    }
 
-        match heading {
-            PlayerHeading::Up => y += delta_t,
-            PlayerHeading::Down => y -= delta_t,
-            PlayerHeading::Left => x -= delta_t,
-            PlayerHeading::Right => x += delta_t,
-            PlayerHeading::NoHeading => (),
+    /// The player's latest boost, which may be long over
+    pub fn get_boost(&self) -> Boost {
+        Boost {
+            start: self.boost_start_mapping.get(self.id),
+            end: self.boost_end_mapping.get(self.id),
         }
+    }
+
+    /// First tick on which the player can boost again
+    pub fn get_boost_ready(&self) -> i64 {
+        self.boost_ready_mapping.get(self.id)
+    }
 
-        (x, y)
+    /// Start a boost at `now_tick`, unless the last one is still cooling down.
+    /// The player is anchored first so the boost only speeds up movement from
+    /// here on. Trail already laid isn't sped up, so a boost from behind the
+    /// board starts where the board is, and settles the path up to there.
+    pub fn activate_boost(&mut self, now_tick: i64) -> bool {
+        let now_tick = now_tick.max(self.get_advanced_tick());
+        if now_tick < self.get_boost_ready() {
+            return false;
+        }
+        self.anchor_at(now_tick);
+        self.set_boost(Boost::starting_at(now_tick));
+        self.boost_ready_mapping.set(self.id, now_tick + BOOST_COOLDOWN);
+        self.settled_tick_mapping.set(self.id, now_tick);
+        true
     }
 
+    /// Heading after the player's latest turn
     pub fn get_heading(&self) -> PlayerHeading {
-        PlayerHeading::from(self.heading_mapping.get(self.id))
+        self.inputs()
+            .last()
+            .map_or(self.anchor_heading(), |input| input.heading.into())
     }
 
+    /// Turn the player at `now_tick`. Turns are recorded at their own tick
+    /// and applied in order, rather than in the order they are committed,
+    /// so branches of the DAG merged in any order give the same path. A
+    /// turn behind the board needs its trail laid again with
+    /// `Board::relay`.
     pub fn apply_input(&mut self, heading: PlayerHeading, now_tick: i64) {
+        let input = Input {
+            tick: now_tick,
+            hash: api_get_hash(),
+            heading: heading.as_i64(),
+        };
+        let mut inputs = self.inputs();
+        if let Err(i) = inputs.binary_search(&input) {
+            inputs.insert(i, input);
+            self.set_inputs(&inputs);
+        }
+    }
+
+    /// Where the player was on their last turn at or before `now_tick`, or
+    /// the anchor, and the heading they took
+    fn last_turn(&self, now_tick: i64) -> (i64, i64, i64, PlayerHeading) {
+        let (mut x, mut y, mut tick) = self.get_anchor();
+        let mut heading = self.anchor_heading();
+        let boost = self.get_boost();
+        for input in self.inputs().into_iter().take_while(|input| input.tick <= now_tick) {
+            let (turn_x, turn_y) = position_at((x, y, tick), heading, boost, input.tick);
+            x = turn_x;
+            y = turn_y;
+            tick = input.tick;
+            heading = input.heading.into();
+        }
+        (x, y, tick, heading)
+    }
+
+    /// Fix the player's path up to `now_tick`, once the board has done
+    /// something there it can't take back, like capturing or killing. Turns
+    /// up to it are folded into the anchor, and later calls from before it
+    /// are caught up to it.
+    pub fn settle(&mut self, now_tick: i64) {
+        let inputs = self.inputs();
+        let settled = inputs.iter().take_while(|input| input.tick <= now_tick).count();
+        if settled > 0 {
+            let (x, y, tick, heading) = self.last_turn(inputs[settled - 1].tick);
+            self.set_position(x, y, tick);
+            self.set_heading(heading);
+            self.set_inputs(&inputs[settled..]);
+        }
+        self.settled_tick_mapping.set(self.id, now_tick.max(self.get_settled_tick()));
+    }
+
+    /// Move the anchor to where the player is at `now_tick`, keeping any
+    /// later turns
+    fn anchor_at(&mut self, now_tick: i64) {
         let (x, y) = self.get_position(now_tick);
+        let (_, _, _, heading) = self.last_turn(now_tick);
+        let later = self
+            .inputs()
+            .into_iter()
+            .filter(|input| input.tick > now_tick)
+            .collect::<Vec<_>>();
         self.set_position(x, y, now_tick);
         self.set_heading(heading);
+        self.set_inputs(&later);
+    }
+
+    fn anchor_heading(&self) -> PlayerHeading {
+        PlayerHeading::from(self.heading_mapping.get(self.id))
+    }
+
+    fn set_inputs(&mut self, inputs: &[Input]) {
+        for (i, input) in inputs.iter().enumerate() {
+            let key = compound_key(self.id, i as i64);
+            self.input_tick_mapping.set(key, input.tick);
+            self.input_hash_mapping.set(key, input.hash);
+            self.input_heading_mapping.set(key, input.heading);
+        }
+        self.input_len_mapping.set(self.id, inputs.len() as i64);
     }
 
     fn set_position(&mut self, x: i64, y: i64, tick: i64) {
@@ -82,6 +495,11 @@ impl Player {
     fn set_heading(&mut self, heading: PlayerHeading) {
         self.heading_mapping.set(self.id, heading.as_i64());
     }
+
+    fn set_boost(&mut self, boost: Boost) {
+        self.boost_start_mapping.set(self.id, boost.start);
+        self.boost_end_mapping.set(self.id, boost.end);
+    }
 }
 
 #[cfg(test)]
@@ -90,7 +508,7 @@ mod tests {
 
     #[test]
     fn test_player_get_position() {
-        let mut player = Player::spawn(3, 10, 10, 0);
+        let mut player = Player::spawn(0, 3, 10, 10, 0);
 
         assert_eq!(player.get_position(0), (10, 10));
         for (heading, expected_position) in [
@@ -107,13 +525,113 @@ mod tests {
 
     #[test]
     fn test_spawn_players() {
-        let player1 = Player::spawn(1, 10, 10, 0);
-        let player2 = Player::spawn(2, 10, 42, 5);
+        let mut player1 = Player::spawn(0, 1, 10, 10, 0);
+        let mut player2 = Player::spawn(0, 2, 10, 42, 5);
+        // Nobody moves until they pick a heading
+        assert_eq!(player1.get_position(5), (10, 10));
 
+        player1.set_heading(PlayerHeading::Right);
+        player2.set_heading(PlayerHeading::Right);
         assert_eq!(player1.get_position(5), (15, 10));
         assert_eq!(player2.get_position(5), (10, 42));
 
         assert_eq!(player1.get_position(10), (20, 10));
         assert_eq!(player2.get_position(10), (15, 42));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_boost() {
+        use crate::movement::BOOST_DURATION;
+
+        let mut player = Player::spawn(0, 4, 0, 0, 0);
+        player.set_heading(PlayerHeading::Right);
+        assert!(player.activate_boost(10));
+        assert_eq!(player.get_position(10), (10, 0));
+        assert_eq!(player.get_position(20), (30, 0));
+        assert_eq!(player.get_position(200), (200 + BOOST_DURATION, 0));
+
+        // Turning mid-boost keeps the speed up until the boost runs out
+        player.apply_input(PlayerHeading::Up, 50);
+        assert_eq!(player.get_position(50), (90, 0));
+        let boosted = 10 + BOOST_DURATION - 50;
+        assert_eq!(player.get_position(200), (90, 150 + boosted));
+
+        // Still cooling down
+        assert!(!player.activate_boost(200));
+        assert!(player.activate_boost(10 + BOOST_COOLDOWN));
+    }
+
+    #[test]
+    fn test_clock_behind_last_move() {
+        let mut player = Player::spawn(0, 5, 0, 0, 100);
+        player.set_heading(PlayerHeading::Right);
+        // Asked about a tick before the anchor, the player stays put
+        assert_eq!(player.get_position(40), (0, 0));
+        assert_eq!(player.get_position(110), (10, 0));
+
+        assert_eq!(player.checked_tick(90), 100);
+        // However far behind the player's latest turn
+        player.apply_input(PlayerHeading::Up, 300);
+        assert_eq!(player.checked_tick(130), 130);
+        // Until the board settles the path past it
+        player.settle(150);
+        assert_eq!(player.checked_tick(130), 150);
+        assert_eq!(player.inputs().len(), 1);
+        player.settle(300);
+        assert_eq!(player.get_anchor(), (200, 0, 300));
+        assert!(player.inputs().is_empty());
+    }
+
+    /// Every ordering of `items`
+    fn permutations(items: &[Input]) -> Vec<Vec<Input>> {
+        if items.is_empty() {
+            return vec![Vec::new()];
+        }
+        let mut orders = Vec::new();
+        for (i, first) in items.iter().enumerate() {
+            let mut rest = items.to_vec();
+            rest.remove(i);
+            for mut order in permutations(&rest) {
+                order.insert(0, *first);
+                orders.push(order);
+            }
+        }
+        orders
+    }
+
+    #[test]
+    fn test_inputs_commute() {
+        let host = crate::host::TestHost::new();
+        // Left sorts after Down by heading, but its transaction sorts first
+        let turns = [
+            Input { tick: 100, hash: 5, heading: PlayerHeading::Up.as_i64() },
+            Input { tick: 110, hash: 1, heading: PlayerHeading::Left.as_i64() },
+            Input { tick: 110, hash: 2, heading: PlayerHeading::Down.as_i64() },
+            Input { tick: 120, hash: 4, heading: PlayerHeading::Right.as_i64() },
+            Input { tick: 125, hash: 3, heading: PlayerHeading::Up.as_i64() },
+        ];
+        let state = |player: &Player| {
+            let positions = (90..200).step_by(5).map(|t| player.get_position(t)).collect::<Vec<_>>();
+            (positions, player.get_heading())
+        };
+
+        let orders = permutations(&turns);
+        let states = orders.iter().enumerate().map(|(i, order)| {
+            let mut player = Player::spawn(0, 1000 + i as i64, 0, 0, 90);
+            for input in order {
+                let tick = player.checked_tick(input.tick);
+                host.hash(input.hash);
+                player.apply_input(input.heading.into(), tick);
+            }
+            state(&player)
+        }).collect::<Vec<_>>();
+        for (order, state) in orders.iter().zip(states.iter()) {
+            assert_eq!(state, &states[0], "Order {:?}", order);
+        }
+        // Turns on the same tick apply in transaction order, so Left then Down
+        let (positions, heading) = &states[0];
+        assert_eq!(positions[6], (0, 0));
+        assert_eq!(positions[8], (5, 5));
+        assert_eq!(*heading, PlayerHeading::Up);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::host::Mapping;
+use crate::index::{
+    compound_key, PROFILE_COLOR_MAPPING_INDEX, PROFILE_NAME_LEN_MAPPING_INDEX,
+    PROFILE_NAME_MAPPING_INDEX,
+};
+
+/// Longest name in bytes
+pub const MAX_NAME_LEN: i64 = 24;
+/// Names are passed and stored as little endian bytes packed into this many
+/// words
+pub const NAME_WORDS: usize = 3;
+
+/// Saturations and hues of the default colors, in the 240ths used by the
+/// client's `Color.possColors`
+const DEFAULT_SATS: [i64; 3] = [192, 150, 100];
+const DEFAULT_HUES: [i64; 27] = [
+    0, 10, 20, 25, 30, 35, 40, 45, 50, 60, 70, 100, 110, 120, 125, 130, 135, 140, 145, 150, 160,
+    170, 180, 190, 200, 210, 220,
+];
+
+/// Whether `name` can be shown to other players: 1 to `MAX_NAME_LEN` bytes of
+/// UTF-8 with no control characters and something besides whitespace
+pub fn is_valid_name(name: &[u8]) -> bool {
+    if name.is_empty() || name.len() as i64 > MAX_NAME_LEN {
+        return false;
+    }
+    match std::str::from_utf8(name) {
+        Ok(name) => {
+            !name.chars().any(char::is_control) && !name.chars().all(char::is_whitespace)
+        }
+        Err(_) => false,
+    }
+}
+
+pub fn pack_name(name: &[u8]) -> [i64; NAME_WORDS] {
+    let mut words = [0; NAME_WORDS];
+    for (i, chunk) in name.chunks(8).take(NAME_WORDS).enumerate() {
+        let mut bytes = [0; 8];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+        words[i] = i64::from_le_bytes(bytes);
+    }
+    words
+}
+
+/// The name packed in `words`, if it is `len` bytes long, valid, and has
+/// nothing stray packed after it
+pub fn unpack_name(len: i64, words: [i64; NAME_WORDS]) -> Option<Vec<u8>> {
+    if len < 0 || len > MAX_NAME_LEN {
+        return None;
+    }
+    let bytes = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .collect::<Vec<_>>();
+    let (name, rest) = bytes.split_at(len as usize);
+    if rest.iter().any(|b| *b != 0) || !is_valid_name(name) {
+        return None;
+    }
+    Some(name.to_vec())
+}
+
+/// Color a player gets until they pick one, as 0xRRGGBB. Every peer derives
+/// the same one from the address.
+pub fn default_color(id: i64) -> i64 {
+    let hash = compound_key(id, 0) as u64;
+    let palette = (DEFAULT_SATS.len() * DEFAULT_HUES.len()) as u64;
+    let choice = (hash % palette) as usize;
+    let hue = DEFAULT_HUES[choice % DEFAULT_HUES.len()];
+    let sat = DEFAULT_SATS[choice / DEFAULT_HUES.len()];
+    hsl_to_rgb(hue as f64 / 240.0, sat as f64 / 240.0, 0.5)
+}
+
+/// Same conversion as `hslToRgb` in src/core/color.js
+fn hsl_to_rgb(h: f64, s: f64, l: f64) -> i64 {
+    let hue_to_rgb = |p: f64, q: f64, mut t: f64| {
+        if t < 0.0 {
+            t += 1.0;
+        }
+        if t > 1.0 {
+            t -= 1.0;
+        }
+        if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        } else if t < 1.0 / 2.0 {
+            q
+        } else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        } else {
+            p
+        }
+    };
+    let q = if l < 0.5 { l * (1.0 + s) } else { l + s - l * s };
+    let p = 2.0 * l - q;
+    let channel = |t: f64| (hue_to_rgb(p, q, t) * 255.0).round() as i64;
+    (channel(h + 1.0 / 3.0) << 16) | (channel(h) << 8) | channel(h - 1.0 / 3.0)
+}
+
+/// A player's name and color. Unlike the rest of a player's state these are
+/// shared by every game in the contract.
+pub struct Profile {
+    id: i64,
+    name_len_mapping: Mapping,
+    name_mapping: Mapping,
+    /// One more than the chosen color, or 0 for the default
+    color_mapping: Mapping,
+}
+
+impl Profile {
+    pub fn load(id: i64) -> Self {
+        Profile {
+            id,
+            name_len_mapping: Mapping(PROFILE_NAME_LEN_MAPPING_INDEX),
+            name_mapping: Mapping(PROFILE_NAME_MAPPING_INDEX),
+            color_mapping: Mapping(PROFILE_COLOR_MAPPING_INDEX),
+        }
+    }
+
+    /// Length of the name in bytes, 0 if the player hasn't set one
+    pub fn name_len(&self) -> i64 {
+        self.name_len_mapping.get(self.id)
+    }
+
+    pub fn name_word(&self, word: i64) -> i64 {
+        if word < 0 || word >= NAME_WORDS as i64 {
+            return 0;
+        }
+        self.name_mapping.get(compound_key(self.id, word))
+    }
+
+    pub fn set_name(&mut self, name: &[u8]) {
+        for (i, word) in pack_name(name).iter().enumerate() {
+            self.name_mapping.set(compound_key(self.id, i as i64), *word);
+        }
+        self.name_len_mapping.set(self.id, name.len() as i64);
+    }
+
+    pub fn color(&self) -> i64 {
+        match self.color_mapping.get(self.id) {
+            0 => default_color(self.id),
+            color => color - 1,
+        }
+    }
+
+    /// Pick a color as 0xRRGGBB, or go back to the default with `None`
+    pub fn set_color(&mut self, color: Option<i64>) {
+        self.color_mapping.set(self.id, color.map_or(0, |c| c + 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_name_round_trip() {
+        for name in ["a", "Paper Tiger", "exactly twenty four byte", "ünïcödé"].iter() {
+            let packed = pack_name(name.as_bytes());
+            assert_eq!(
+                unpack_name(name.len() as i64, packed),
+                Some(name.as_bytes().to_vec())
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        assert!(!is_valid_name(b""));
+        assert!(!is_valid_name(b"   "));
+        assert!(!is_valid_name(b"tab\there"));
+        assert!(!is_valid_name(&[0xff, 0xfe]));
+        assert!(!is_valid_name(&[b'a'; 25]));
+
+        // Bytes packed past the given length
+        assert_eq!(unpack_name(2, pack_name(b"abc")), None);
+        assert_eq!(unpack_name(25, [0; NAME_WORDS]), None);
+        assert_eq!(unpack_name(-1, [0; NAME_WORDS]), None);
+    }
+
+    #[test]
+    fn test_default_color() {
+        assert_eq!(default_color(1234), default_color(1234));
+        for id in 0..100 {
+            let color = default_color(id);
+            assert!(0 <= color && color <= 0xFF_FFFF);
+        }
+        // Hue 0 at full saturation is pure red
+        assert_eq!(hsl_to_rgb(0.0, 1.0, 0.5), 0xFF_0000);
+    }
+
+    #[test]
+    fn test_profile() {
+        let mut profile = Profile::load(801);
+        assert_eq!(profile.name_len(), 0);
+        assert_eq!(profile.color(), default_color(801));
+
+        profile.set_name(b"Nimble Fox");
+        profile.set_color(Some(0x00_8000));
+        assert_eq!(profile.name_len(), 10);
+        let words = [profile.name_word(0), profile.name_word(1), profile.name_word(2)];
+        assert_eq!(unpack_name(10, words), Some(b"Nimble Fox".to_vec()));
+        assert_eq!(profile.color(), 0x00_8000);
+
+        profile.set_color(None);
+        assert_eq!(profile.color(), default_color(801));
+    }
+}
@@ -0,0 +1,164 @@
+use crate::game::Game;
+use crate::grid::Board;
+use crate::host::{GlobalI64, Mapping};
+use crate::index::{
+    game_index, QUEUE_HEAD_INDEX, QUEUE_PLAYER_SLOT_MAPPING_INDEX, QUEUE_SLOT_MAPPING_INDEX,
+    QUEUE_TAIL_INDEX, QUEUE_X_MAPPING_INDEX, QUEUE_Y_MAPPING_INDEX,
+};
+use crate::player::Player;
+
+/// Players waiting to spawn in a full game, first come first served.
+///
+/// Entries live in slots from `head` to `tail`. A player who leaves the
+/// queue just forgets their slot, and the hole is skipped when it reaches
+/// the head.
+pub struct WaitingQueue {
+    game: i64,
+    head: GlobalI64,
+    tail: GlobalI64,
+    slot_mapping: Mapping,
+    /// One more than the player's slot, or 0 if they aren't waiting
+    player_slot_mapping: Mapping,
+    x_mapping: Mapping,
+    y_mapping: Mapping,
+}
+
+impl WaitingQueue {
+    pub fn load(game: i64) -> Self {
+        WaitingQueue {
+            game,
+            head: GlobalI64(game_index(game, QUEUE_HEAD_INDEX)),
+            tail: GlobalI64(game_index(game, QUEUE_TAIL_INDEX)),
+            slot_mapping: Mapping(game_index(game, QUEUE_SLOT_MAPPING_INDEX)),
+            player_slot_mapping: Mapping(game_index(game, QUEUE_PLAYER_SLOT_MAPPING_INDEX)),
+            x_mapping: Mapping(game_index(game, QUEUE_X_MAPPING_INDEX)),
+            y_mapping: Mapping(game_index(game, QUEUE_Y_MAPPING_INDEX)),
+        }
+    }
+
+    /// Place in the queue counting from 1, or 0 if the player isn't waiting
+    pub fn position(&self, player: i64) -> i64 {
+        let slot = self.player_slot_mapping.get(player) - 1;
+        if slot < 0 {
+            return 0;
+        }
+        (self.head.get()..=slot)
+            .filter(|s| self.holds(*s))
+            .count() as i64
+    }
+
+    /// Queue a player to spawn at (x, y). Players already waiting keep their
+    /// place and just move their spawn point.
+    pub fn push(&mut self, player: i64, x: i64, y: i64) {
+        self.x_mapping.set(player, x);
+        self.y_mapping.set(player, y);
+        if self.position(player) > 0 {
+            return;
+        }
+        let slot = self.tail.get();
+        self.slot_mapping.set(slot, player);
+        self.player_slot_mapping.set(player, slot + 1);
+        self.tail.set(slot + 1);
+    }
+
+    pub fn remove(&mut self, player: i64) {
+        self.player_slot_mapping.set(player, 0);
+    }
+
+    /// Take the longest waiting player and where they asked to spawn
+    pub fn pop(&mut self) -> Option<(i64, i64, i64)> {
+        let tail = self.tail.get();
+        let mut head = self.head.get();
+        let mut next = None;
+        while head < tail && next.is_none() {
+            if self.holds(head) {
+                let player = self.slot_mapping.get(head);
+                self.remove(player);
+                next = Some((player, self.x_mapping.get(player), self.y_mapping.get(player)));
+            }
+            head += 1;
+        }
+        self.head.set(head);
+        next
+    }
+
+    /// Spawn waiting players until the game is full again
+    pub fn admit(&mut self, game: &Game) {
+        if !game.accepts_spawns() {
+            return;
+        }
+        let mut board = Board::load(self.game);
+        while !game.is_full() {
+            let (id, x, y) = match self.pop() {
+                Some(entry) => entry,
+                None => break,
+            };
+            let mut player = Player::spawn(self.game, id, x, y, game.get_current_tick());
+            board.spawn(&mut player, x, y);
+            board.commit_scores();
+        }
+    }
+
+    /// Whether the entry in `slot` is still waiting
+    fn holds(&self, slot: i64) -> bool {
+        let player = self.slot_mapping.get(slot);
+        self.player_slot_mapping.get(player) == slot + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::{CELL_WIDTH, GRID_ORIGIN};
+    use crate::host::TestHost;
+
+    #[test]
+    fn test_queue_is_fifo() {
+        let mut queue = WaitingQueue::load(50);
+        queue.push(11, 0, 0);
+        queue.push(12, 0, 0);
+        queue.push(13, 5, 6);
+        queue.push(11, 1, 2); // Already waiting, keeps their place
+        assert_eq!(queue.position(11), 1);
+        assert_eq!(queue.position(13), 3);
+
+        queue.remove(12);
+        assert_eq!(queue.position(12), 0);
+        assert_eq!(queue.position(13), 2);
+
+        assert_eq!(queue.pop(), Some((11, 1, 2)));
+        assert_eq!(queue.pop(), Some((13, 5, 6)));
+        assert_eq!(queue.pop(), None);
+        assert_eq!(queue.position(13), 0);
+    }
+
+    #[test]
+    fn test_waiting_player_admitted_on_death() {
+        let host = TestHost::new();
+        let mut game = Game::load(51);
+        game.init(0, 1, 0);
+        let point = |col: i64| GRID_ORIGIN + col * CELL_WIDTH;
+
+        let mut board = Board::load(game.id());
+        let mut first = Player::spawn(game.id(), 21, point(5), point(5), 0);
+        board.spawn(&mut first, point(5), point(5));
+        board.commit_scores();
+        assert!(game.is_full());
+
+        let mut queue = WaitingQueue::load(game.id());
+        host.at(1000);
+        queue.push(22, point(20), point(20));
+        queue.admit(&game);
+        assert_eq!(queue.position(22), 1);
+
+        // Let in by the call that killed the first player, not when they asked
+        host.at(5000);
+        board.kill(&mut first, 0);
+        board.commit_scores();
+        queue.admit(&game);
+        assert_eq!(queue.position(22), 0);
+        assert!(Player::load(game.id(), 22).is_alive());
+        assert_eq!(Player::load(game.id(), 22).get_spawn_time(), 5000);
+        assert!(game.is_full());
+    }
+}
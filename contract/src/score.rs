@@ -0,0 +1,275 @@
+use crate::host::{GlobalI64, Mapping};
+use crate::index::{
+    game_index, LEADERBOARD_LEN_INDEX, LEADERBOARD_MAPPING_INDEX, ROSTER_LEN_INDEX, ROSTER_MAPPING_INDEX,
+    ROSTER_SLOT_MAPPING_INDEX,
+};
+use crate::player::Player;
+
+/// Players shown on a game's leaderboard, matching `LEADERBOARD_NUM` in
+/// config.json
+pub const LEADERBOARD_NUM: i64 = 5;
+
+/// Whether `a` ranks above `b`. More territory wins, then more kills, and
+/// the lower address breaks ties so every peer sorts the same way.
+fn ranks_above(a: &Player, b: &Player) -> bool {
+    (b.get_area(), b.get_kills(), a.id()) < (a.get_area(), a.get_kills(), b.id())
+}
+
+/// Every live player in a game, in no particular order. Whoever leaves is
+/// replaced by the last entry, so joining and leaving don't move the rest.
+pub struct Roster {
+    entries_mapping: Mapping,
+    /// One more than each player's slot, so 0 means they were never listed
+    slot_mapping: Mapping,
+    len: GlobalI64,
+}
+
+impl Roster {
+    pub fn load(game: i64) -> Self {
+        Roster {
+            entries_mapping: Mapping(game_index(game, ROSTER_MAPPING_INDEX)),
+            slot_mapping: Mapping(game_index(game, ROSTER_SLOT_MAPPING_INDEX)),
+            len: GlobalI64(game_index(game, ROSTER_LEN_INDEX)),
+        }
+    }
+
+    pub fn len(&self) -> i64 {
+        self.len.get()
+    }
+
+    /// Address of the player in `slot`, or 0 past the end
+    pub fn get(&self, slot: i64) -> i64 {
+        if slot < 0 || slot >= self.len() {
+            return 0;
+        }
+        self.entries_mapping.get(slot)
+    }
+
+    pub fn members(&self) -> Vec<i64> {
+        (0..self.len()).map(|slot| self.get(slot)).collect()
+    }
+
+    /// Slots are left behind by `clear`, so only trust one that still holds the player
+    pub fn contains(&self, id: i64) -> bool {
+        let slot = self.slot_mapping.get(id) - 1;
+        slot >= 0 && self.get(slot) == id
+    }
+
+    /// List a player while they're alive
+    pub fn update(&mut self, player: &Player) {
+        if player.is_alive() {
+            self.insert(player.id());
+        } else {
+            self.remove(player.id());
+        }
+    }
+
+    /// Forget every entry, for a new round where nobody has spawned yet
+    pub fn clear(&mut self) {
+        self.len.set(0);
+    }
+
+    fn insert(&mut self, id: i64) {
+        if self.contains(id) {
+            return;
+        }
+        let len = self.len();
+        self.entries_mapping.set(len, id);
+        self.slot_mapping.set(id, len + 1);
+        self.len.set(len + 1);
+    }
+
+    fn remove(&mut self, id: i64) {
+        if !self.contains(id) {
+            return;
+        }
+        let slot = self.slot_mapping.get(id) - 1;
+        let last = self.len() - 1;
+        let moved = self.get(last);
+        self.entries_mapping.set(slot, moved);
+        self.slot_mapping.set(moved, slot + 1);
+        self.slot_mapping.set(id, 0);
+        self.len.set(last);
+    }
+}
+
+/// The best `LEADERBOARD_NUM` live players in a game, sorted by rank
+pub struct Leaderboard {
+    game: i64,
+    entries_mapping: Mapping,
+    len: GlobalI64,
+}
+
+impl Leaderboard {
+    pub fn load(game: i64) -> Self {
+        Leaderboard {
+            game,
+            entries_mapping: Mapping(game_index(game, LEADERBOARD_MAPPING_INDEX)),
+            len: GlobalI64(game_index(game, LEADERBOARD_LEN_INDEX)),
+        }
+    }
+
+    pub fn len(&self) -> i64 {
+        self.len.get()
+    }
+
+    /// Address of the player at `rank`, counting from zero, or 0 past the end
+    pub fn get(&self, rank: i64) -> i64 {
+        if rank < 0 || rank >= self.len() {
+            return 0;
+        }
+        self.entries_mapping.get(rank)
+    }
+
+    /// Move a player to their current rank, or drop them if they died.
+    /// Players below the board are only looked through when someone leaves
+    /// it or falls to the bottom, since one of them may now rank higher.
+    pub fn update(&mut self, player: &Player, roster: &Roster) {
+        let listed = self.remove(player.id());
+        if !player.is_alive() {
+            if listed {
+                self.refill(roster);
+            }
+            return;
+        }
+        let rank = self.insert(player);
+        if listed && rank == Some(self.len() - 1) && roster.len() > self.len() {
+            self.remove(player.id());
+            self.refill(roster);
+        }
+    }
+
+    /// Forget every entry, for a new round where nobody has spawned yet
+    pub fn clear(&mut self) {
+        self.len.set(0);
+    }
+
+    /// Top up the board with the best players not on it
+    fn refill(&mut self, roster: &Roster) {
+        while self.len() < roster.len().min(LEADERBOARD_NUM) {
+            let listed = (0..self.len()).map(|rank| self.get(rank)).collect::<Vec<_>>();
+            let best = roster.members().into_iter()
+                .filter(|id| !listed.contains(id))
+                .map(|id| Player::load(self.game, id))
+                .fold(None, |best: Option<Player>, player| match best {
+                    Some(best) if !ranks_above(&player, &best) => Some(best),
+                    _ => Some(player),
+                });
+            match best {
+                Some(player) => self.insert(&player),
+                None => return,
+            };
+        }
+    }
+
+    /// Whether the player was on the board
+    fn remove(&mut self, id: i64) -> bool {
+        let len = self.len();
+        let rank = match (0..len).find(|rank| self.get(*rank) == id) {
+            Some(rank) => rank,
+            None => return false,
+        };
+        for r in rank..len - 1 {
+            let next = self.get(r + 1);
+            self.entries_mapping.set(r, next);
+        }
+        self.len.set(len - 1);
+        true
+    }
+
+    /// Where the player landed, if they made the board. A full board drops
+    /// its last entry to make room.
+    fn insert(&mut self, player: &Player) -> Option<i64> {
+        let len = self.len();
+        let rank = (0..len)
+            .find(|rank| ranks_above(player, &Player::load(self.game, self.get(*rank))))
+            .unwrap_or(len);
+        if rank >= LEADERBOARD_NUM {
+            return None;
+        }
+        let last = len.min(LEADERBOARD_NUM - 1);
+        for r in (rank..last).rev() {
+            let prev = self.get(r);
+            self.entries_mapping.set(r + 1, prev);
+        }
+        self.entries_mapping.set(rank, player.id());
+        self.len.set(last + 1);
+        Some(rank)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::TestHost;
+
+    /// Spawn players 1..=count, each with as much territory as their address
+    fn spawn_ranked(count: i64) -> (Roster, Leaderboard) {
+        let mut roster = Roster::load(0);
+        let mut leaderboard = Leaderboard::load(0);
+        for id in 1..=count {
+            let mut player = Player::spawn(0, id, 10, 10 * id, 0);
+            player.add_area(id);
+            roster.update(&player);
+            leaderboard.update(&player, &roster);
+        }
+        (roster, leaderboard)
+    }
+
+    fn ranks(leaderboard: &Leaderboard) -> Vec<i64> {
+        (0..leaderboard.len()).map(|rank| leaderboard.get(rank)).collect()
+    }
+
+    #[test]
+    fn test_leaderboard_keeps_the_top_players() {
+        let _host = TestHost::new();
+        let (roster, leaderboard) = spawn_ranked(LEADERBOARD_NUM + 2);
+
+        assert_eq!(roster.len(), LEADERBOARD_NUM + 2);
+        assert_eq!(ranks(&leaderboard), vec![7, 6, 5, 4, 3]);
+        assert_eq!(leaderboard.get(LEADERBOARD_NUM), 0);
+    }
+
+    #[test]
+    fn test_leaderboard_refills_from_the_roster() {
+        let _host = TestHost::new();
+        let (mut roster, mut leaderboard) = spawn_ranked(LEADERBOARD_NUM + 2);
+
+        // The leader dies, and the best player below the board takes their place
+        let mut leader = Player::load(0, 7);
+        leader.kill(1);
+        roster.update(&leader);
+        leaderboard.update(&leader, &roster);
+        assert!(!roster.contains(7));
+        assert_eq!(ranks(&leaderboard), vec![6, 5, 4, 3, 2]);
+
+        // Losing territory to someone below the board drops a player off it
+        let mut falling = Player::load(0, 5);
+        falling.add_area(-5);
+        roster.update(&falling);
+        leaderboard.update(&falling, &roster);
+        assert_eq!(ranks(&leaderboard), vec![6, 4, 3, 2, 1]);
+
+        // Climbing from below the board pushes the last player off
+        falling.add_area(10);
+        leaderboard.update(&falling, &roster);
+        assert_eq!(ranks(&leaderboard), vec![5, 6, 4, 3, 2]);
+    }
+
+    #[test]
+    fn test_roster_swaps_in_the_last_entry() {
+        let _host = TestHost::new();
+        let (mut roster, _) = spawn_ranked(3);
+
+        let mut first = Player::load(0, 1);
+        first.kill(1);
+        roster.update(&first);
+        assert_eq!(roster.members(), vec![3, 2]);
+        assert!(!roster.contains(1));
+
+        roster.clear();
+        assert!(!roster.contains(3));
+        roster.update(&Player::load(0, 2));
+        assert_eq!(roster.members(), vec![2]);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::game::Game;
+use crate::player::Player;
+use crate::score::Roster;
+
+/// Most teams a game can be split into
+pub const MAX_TEAMS: i64 = 8;
+
+/// Combined score of a team's live players
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+pub struct TeamScore {
+    pub team: i64,
+    pub area: i64,
+    pub kills: i64,
+    pub players: i64,
+}
+
+/// Teams of a game, numbered from 1. Games without teams have a count of 0
+/// and every player is on team 0, on their own.
+///
+/// Players keep their team between lives. Scores are summed from the live
+/// players on the roster, so they reset along with it.
+pub struct Teams {
+    game: i64,
+    count: i64,
+}
+
+impl Teams {
+    pub fn load(game: &Game) -> Self {
+        Teams {
+            game: game.id(),
+            count: game.get_team_count(),
+        }
+    }
+
+    pub fn count(&self) -> i64 {
+        self.count
+    }
+
+    pub fn contains(&self, team: i64) -> bool {
+        1 <= team && team <= self.count
+    }
+
+    /// Live players on a team
+    pub fn members(&self, team: i64) -> Vec<Player> {
+        if !self.contains(team) {
+            return Vec::new();
+        }
+        Roster::load(self.game).members().into_iter()
+            .map(|id| Player::load(self.game, id))
+            .filter(|player| player.get_team() == team)
+            .collect()
+    }
+
+    /// Scores of every team, best first. More territory wins, then more
+    /// kills, and the lower team number breaks ties.
+    pub fn ranking(&self) -> Vec<TeamScore> {
+        let mut scores = (1..=self.count)
+            .map(|team| TeamScore {
+                team,
+                ..TeamScore::default()
+            })
+            .collect::<Vec<_>>();
+        for id in Roster::load(self.game).members() {
+            let player = Player::load(self.game, id);
+            if !self.contains(player.get_team()) {
+                continue;
+            }
+            let score = &mut scores[(player.get_team() - 1) as usize];
+            score.area += player.get_area();
+            score.kills += player.get_kills();
+            score.players += 1;
+        }
+        scores.sort_by(|a, b| (b.area, b.kills, a.team).cmp(&(a.area, a.kills, b.team)));
+        scores
+    }
+
+    pub fn score(&self, team: i64) -> Option<TeamScore> {
+        self.ranking().into_iter().find(|score| score.team == team)
+    }
+
+    /// Team with the fewest live players, which players who haven't picked
+    /// one are put on
+    pub fn smallest(&self) -> i64 {
+        self.ranking()
+            .into_iter()
+            .min_by_key(|score| (score.players, score.team))
+            .map_or(0, |score| score.team)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Games;
+    use crate::grid::{Board, Cell, CELL_WIDTH, GRID_ORIGIN};
+    use crate::heading::PlayerHeading;
+
+    fn point(col: i64, row: i64) -> (i64, i64) {
+        (GRID_ORIGIN + col * CELL_WIDTH, GRID_ORIGIN + row * CELL_WIDTH)
+    }
+
+    #[test]
+    fn test_players_are_spread_across_teams() {
+        let game = Games::default().create(0, 0, 2).unwrap();
+        let mut board = Board::load(game.id());
+        for (i, id) in [801, 802, 803].iter().enumerate() {
+            let (x, y) = point(10 + 10 * i as i64, 10);
+            let mut player = Player::spawn(game.id(), *id, x, y, 0);
+            board.spawn(&mut player, x, y);
+            board.commit_scores();
+        }
+
+        let teams = Teams::load(&game);
+        assert_eq!(Player::load(game.id(), 801).get_team(), 1);
+        assert_eq!(Player::load(game.id(), 802).get_team(), 2);
+        assert_eq!(Player::load(game.id(), 803).get_team(), 1);
+        assert_eq!(teams.members(1).len(), 2);
+
+        let ranking = teams.ranking();
+        assert_eq!(ranking[0], TeamScore { team: 1, area: 18, kills: 0, players: 2 });
+        assert_eq!(ranking[1], TeamScore { team: 2, area: 9, kills: 0, players: 1 });
+    }
+
+    #[test]
+    fn test_teammates_cross_trails_and_share_territory() {
+        let game = Games::default().create(0, 0, 2).unwrap();
+        let mut board = Board::load(game.id());
+
+        let (x, y) = point(40, 40);
+        let mut runner = Player::spawn(game.id(), 811, x, y, 0);
+        board.spawn(&mut runner, x, y);
+        board.commit_scores();
+        runner.apply_input(PlayerHeading::Right, 0);
+        board.advance(&mut runner, 4 * CELL_WIDTH);
+
+        // Spawned on the same team as the runner, right under their trail
+        let (x, y) = point(43, 37);
+        let mut teammate = Player::spawn(game.id(), 812, x, y, 0);
+        teammate.set_team(runner.get_team());
+        board.spawn(&mut teammate, x, y);
+        teammate.apply_input(PlayerHeading::Up, 0);
+        board.advance(&mut teammate, 4 * CELL_WIDTH);
+
+        assert!(Player::load(game.id(), 811).is_alive());
+        assert_eq!(teammate.get_kills(), 0);
+
+        // Turning into the runner's territory closes the trail
+        teammate.apply_input(PlayerHeading::Left, 4 * CELL_WIDTH);
+        board.advance(&mut teammate, 6 * CELL_WIDTH);
+        assert_eq!(teammate.trail_len(), 0);
+        assert_eq!(board.owner(Cell { col: 42, row: 41 }), Some(812));
+        // Enclosed between the trail and the runner's territory
+        assert_eq!(board.owner(Cell { col: 42, row: 40 }), Some(812));
+        assert_eq!(board.owner(Cell { col: 40, row: 40 }), Some(811));
+    }
+}
@@ -0,0 +1,199 @@
+//! Simulated players for load testing a p2pio contract, either on a DAG in
+//! this process or on a rustdag node over HTTP
+
+mod stats;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::{value_t, App, Arg};
+use rand::{rngs::StdRng, SeedableRng};
+
+use p2pio::tools::{code, contract_wasm, Backend, Bot, InProcess, Remote};
+
+use rustdag_lib::security::keys::eddsa::{get_address, get_public_key, new_key_pair};
+
+use stats::Latencies;
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+/// Cells from the middle of the grid players spawn within, keeping them away
+/// from the edges of the board
+const SPAWN_SPREAD: i64 = 130;
+
+fn main() {
+    let matches = App::new("p2pio-load")
+        .about("Drives simulated players against a p2pio contract and reports latency")
+        .arg(
+            Arg::with_name("players")
+                .long("players")
+                .short("p")
+                .takes_value(true)
+                .default_value("10")
+                .help("Number of simulated players, each with their own key"),
+        )
+        .arg(
+            Arg::with_name("rate")
+                .long("rate")
+                .short("r")
+                .takes_value(true)
+                .default_value("2")
+                .help("Calls per second made by each player"),
+        )
+        .arg(
+            Arg::with_name("duration")
+                .long("duration")
+                .short("d")
+                .takes_value(true)
+                .default_value("30")
+                .help("Seconds to run for"),
+        )
+        .arg(
+            Arg::with_name("node")
+                .long("node")
+                .short("n")
+                .takes_value(true)
+                .requires("contract")
+                .help("URL of a rustdag node. Without one, a DAG is run in this process."),
+        )
+        .arg(
+            Arg::with_name("contract")
+                .long("contract")
+                .short("c")
+                .takes_value(true)
+                .help("Address of the contract deployed on the node"),
+        )
+        .arg(
+            Arg::with_name("wasm")
+                .long("wasm")
+                .takes_value(true)
+                .help("Contract to deploy on the in-process DAG. Defaults to the module `p2pio build` leaves."),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .help("Seed for spawn points and turns"),
+        )
+        .get_matches();
+
+    let _ = simple_logger::init_with_level(log::Level::Warn);
+    let players = value_t!(matches, "players", usize).unwrap_or_else(|e| e.exit());
+    let rate = value_t!(matches, "rate", f64).unwrap_or_else(|e| e.exit());
+    let duration = value_t!(matches, "duration", u64).unwrap_or_else(|e| e.exit());
+    let seed = if matches.is_present("seed") {
+        value_t!(matches, "seed", u64).unwrap_or_else(|e| e.exit())
+    } else {
+        rand::random()
+    };
+
+    let mut target: Box<dyn Backend> = match matches.value_of("node") {
+        Some(url) => {
+            let contract = value_t!(matches, "contract", u64).unwrap_or_else(|e| e.exit());
+            Box::new(Remote::new(url, contract))
+        }
+        None => match InProcess::deploy(
+            &matches
+                .value_of("wasm")
+                .map_or_else(contract_wasm, PathBuf::from),
+        ) {
+            Ok(target) => Box::new(target),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    println!(
+        "{} players at {} calls/sec each for {}s, seed {}",
+        players, rate, duration, seed
+    );
+    run(
+        target.as_mut(),
+        players,
+        rate,
+        Duration::from_secs(duration),
+        seed,
+    );
+}
+
+fn run(target: &mut dyn Backend, players: usize, rate: f64, duration: Duration, seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut sims = (0..players)
+        .map(|_| Bot::new(new_key_pair(), SPAWN_SPREAD))
+        .collect::<Vec<_>>();
+
+    // Calls are due every `interval` for each player, staggered so they
+    // don't all land together. Falling behind shows up as backlog rather
+    // than a lower rate.
+    let interval = Duration::from_secs_f64(1.0 / rate);
+    let start = Instant::now();
+    let mut due = (0..players)
+        .map(|i| Reverse((start + interval.mul_f64(i as f64 / players as f64), i)))
+        .collect::<BinaryHeap<_>>();
+
+    let mut window = Latencies::default();
+    let mut total = Latencies::default();
+    let mut next_report = start + REPORT_INTERVAL;
+    while let Some(Reverse((at, i))) = due.pop() {
+        let now = Instant::now();
+        if now >= start + duration {
+            break;
+        }
+        if now >= next_report {
+            // Calls that should have been made by now but haven't
+            let waiting = due.iter().filter(|Reverse((at, _))| *at <= now).count();
+            let backlog = waiting + (at <= now) as usize;
+            report(now - start, &window, backlog);
+            total.merge(&window);
+            window = Latencies::default();
+            next_report += REPORT_INTERVAL;
+        }
+        if at > now {
+            thread::sleep(at - now);
+        }
+
+        let sim = &mut sims[i];
+        let (func_name, args) = sim.next_call(&mut rng);
+        let sent = Instant::now();
+        match target.call(sim.key(), func_name, &args) {
+            Ok(value) => {
+                window.record(sent.elapsed());
+                sim.observe(func_name, &args, code(value));
+            }
+            Err(e) => {
+                log::warn!(
+                    "{} from {} failed: {}",
+                    func_name,
+                    get_address(&get_public_key(sim.key())),
+                    e
+                );
+                window.record_failure();
+            }
+        }
+        due.push(Reverse((at + interval, i)));
+    }
+
+    total.merge(&window);
+    println!(
+        "total: {} calls, {} failed, {:.1} calls/sec, {}",
+        total.len(),
+        total.failures(),
+        total.len() as f64 / start.elapsed().as_secs_f64(),
+        total.summary()
+    );
+}
+
+fn report(elapsed: Duration, window: &Latencies, backlog: usize) {
+    println!(
+        "{:>5}s {:>6} calls {:>4} failed  {}  backlog {}",
+        elapsed.as_secs(),
+        window.len(),
+        window.failures(),
+        window.summary(),
+        backlog
+    );
+}
@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+/// Latencies of the calls made in some window
+#[derive(Default)]
+pub struct Latencies {
+    samples: Vec<Duration>,
+    failures: usize,
+}
+
+impl Latencies {
+    pub fn record(&mut self, latency: Duration) {
+        self.samples.push(latency);
+    }
+
+    pub fn record_failure(&mut self) {
+        self.failures += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn failures(&self) -> usize {
+        self.failures
+    }
+
+    /// Latency `p` percent of calls were at or under, or zero with no calls
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::default();
+        }
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        let rank = (p / 100.0 * (sorted.len() - 1) as f64).round() as usize;
+        sorted[rank.min(sorted.len() - 1)]
+    }
+
+    /// Fold another window into this one
+    pub fn merge(&mut self, other: &Latencies) {
+        self.samples.extend_from_slice(&other.samples);
+        self.failures += other.failures;
+    }
+
+    /// One line summary of `p50/p90/p99/max` in milliseconds
+    pub fn summary(&self) -> String {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        format!(
+            "p50 {:>7.1}ms  p90 {:>7.1}ms  p99 {:>7.1}ms  max {:>7.1}ms",
+            ms(self.percentile(50.0)),
+            ms(self.percentile(90.0)),
+            ms(self.percentile(99.0)),
+            ms(self.percentile(100.0)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let mut latencies = Latencies::default();
+        assert_eq!(latencies.percentile(50.0), Duration::default());
+        for ms in (1..=100).rev() {
+            latencies.record(Duration::from_millis(ms));
+        }
+        assert_eq!(latencies.percentile(0.0), Duration::from_millis(1));
+        assert_eq!(latencies.percentile(50.0), Duration::from_millis(51));
+        assert_eq!(latencies.percentile(99.0), Duration::from_millis(99));
+        assert_eq!(latencies.percentile(100.0), Duration::from_millis(100));
+    }
+}
@@ -42,13 +42,13 @@ client.renderer = {
 };
 
 // rust.init();
-// var context = new rust.Context("http://localhost:8090", config.contractID);
+// var context = new rust.Context(config.nodes, config.contractID);
 // connect(context);
 
 rust
   .then(m => {
     m.init();
-    return new m.Context("http://localhost:8090", config.contractID);
+    return new m.Context(config.nodes, config.contractID);
   })
   .then(connect)
   .catch(console.error);
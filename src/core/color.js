@@ -50,6 +50,24 @@ function Color(h, s, l, a) {
 Color.fromData = data => {
 	return new Color(data.hue, data.sat, data.lum, data.alpha);
 };
+//From a 0xRRGGBB number, like the colors stored by the contract
+Color.fromRgb = rgb => {
+	var r = ((rgb >> 16) & 0xff) / 255;
+	var g = ((rgb >> 8) & 0xff) / 255;
+	var b = (rgb & 0xff) / 255;
+	var max = Math.max(r, g, b), min = Math.min(r, g, b);
+	var l = (max + min) / 2;
+	var h = 0, s = 0;
+	if (max !== min) {
+		var d = max - min;
+		s = l > 0.5 ? d / (2 - max - min) : d / (max + min);
+		if (max === r) h = (g - b) / d + (g < b ? 6 : 0);
+		else if (max === g) h = (b - r) / d + 2;
+		else h = (r - g) / d + 4;
+		h /= 6;
+	}
+	return new Color(h, s, l, 1);
+};
 Color.prototype.interpolateToString = function(color, amount) {
 	var rgbThis = hslToRgb(this.hue, this.sat, this.lum);
 	var rgbThat = hslToRgb(color.hue, color.sat, color.lum);
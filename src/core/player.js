@@ -321,6 +321,7 @@ function Player(grid, sdata) {
 	data.waitLag = sdata.waitLag || 0;
   data.dead = false;
   data.referenceTime = null;
+  data.boostStart = data.boostEnd = null;
 
 	//Only need colors for client side
 	var base;
@@ -345,7 +346,28 @@ function Player(grid, sdata) {
     this.reConfigure = reConfigure.bind(data);
   this.move = move.bind(this, data);
   this.updateReferencePoint = updateReferencePoint.bind(this, data);
+	this.shiftReferencePoint = (millis) => {
+		//Time paused doesn't count towards movement or boosts
+		if (data.referenceTime != null) data.referenceTime = new Date(data.referenceTime.getTime() + millis);
+		if (data.boostStart != null) {
+			data.boostStart += millis;
+			data.boostEnd += millis;
+		}
+	};
 	this.die = () => { data.dead = true; };
+	this.boost = (start, end) => {
+		//Mirrors the contract's cooldown, which ignores the boost
+		if (data.boostStart != null && start < data.boostStart + consts.BOOST_COOLDOWN) return;
+		data.boostStart = start;
+		data.boostEnd = end;
+	};
+	this.setProfile = (name, base) => {
+		data.name = name;
+		this.baseColor = base;
+		this.lightBaseColor = base.deriveLumination(.1);
+		this.shadowColor = base.deriveLumination(-.3);
+		this.tailColor = base.deriveLumination(.2).deriveAlpha(0.98);
+	};
 
 	//Read-only Properties
 	defineAccessorProperties(this, data, "currentHeading", "dead", "name", "num", "posX", "posY", "grid", "tail", "waitLag");
@@ -402,10 +424,20 @@ function reConfigure(x, y, lag, heading) {
     this.heading = this.currentHeading = heading;
 }
 
+//Distance covered between two times, going BOOST_MULTIPLIER times as fast during a boost
+//Same piecewise sum as the contract's movement::distance
+function travelled(data, from, to) {
+  var difTime = to - from;
+  if (data.boostStart != null) {
+    var boosted = Math.min(to, data.boostEnd) - Math.max(from, data.boostStart);
+    difTime += (consts.BOOST_MULTIPLIER - 1) * Math.max(0, boosted);
+  }
+  return difTime / (1000 / consts.SPEEDFPS) * consts.SPEED;
+}
+
 function updateReferencePoint(data, referenceTime) {
   if (data.referenceTime != null) {
-    var difTime = referenceTime - data.referenceTime;
-    var positionOffset = difTime / (1000 / consts.SPEEDFPS) * consts.SPEED;
+    var positionOffset = travelled(data, data.referenceTime, referenceTime);
 
     var { heading } = this;
 
@@ -465,8 +497,7 @@ function move(data, currentTime) {
   // else data.currentHeading = heading;
   data.currentHeading = heading;
 
-  var difTime = currentTime - data.referenceTime;
-  var offset = difTime / (1000 / consts.SPEEDFPS) * consts.SPEED;
+  var offset = travelled(data, data.referenceTime, currentTime);
   // console.log(offset);
 
 	switch (heading) {
@@ -19,7 +19,6 @@ var frameBufferSize = 60 * 4;
 var frameBuffer = new Array(frameBufferSize);
 var bufferIndex = 0;
 
-var possColors = core.Color.possColors();
 
 var rctx = undefined;
 var address = undefined;
@@ -34,6 +33,7 @@ var timelineIndex = 0;
 var timeline = new Array(timelineSize);
 
 var inputHeading, pushedHeading;
+var pausedAt = null; //Time the game was paused, while it is
 inputHeading = pushedHeading = 4;
 
 function giveContext(context) {
@@ -69,6 +69,12 @@ async function spawnIn() {
   var y = start.row * consts.CELL_WIDTH;
   await rctx.spawn_player(x, y);
   address = rctx.get_address();
+
+  //Full games queue the spawn until someone dies
+  var position = rctx.queue_position();
+  if (position !== undefined) {
+    console.log("Game is full, waiting at position " + position);
+  }
 }
 
 //Public API
@@ -99,6 +105,8 @@ async function connectGame(url, name, callback, flag) {
   }
   var serialGrid = gridSerialData(grid, players);
 
+  //Published again for each spawn, so peers all see the same name and color
+  rctx.set_profile(name).catch(console.error);
   spawnIn();
 
   // invokeRenderer("paint", []);
@@ -125,6 +133,13 @@ function changeHeading(newHeading) {
   }
 }
 
+//The contract ignores boosts that are still cooling down
+function activateBoost() {
+  if (!user)
+    return;
+  rctx.activate_boost().catch(console.error);
+}
+
 function getUser() {
   return user;
 }
@@ -141,6 +156,43 @@ function getOthers() {
   return ret;
 }
 
+//The last rows are kept while the replica fetches missing history
+var leaderboard = [];
+var teamLeaderboard = [];
+
+//Ranked by the contract, so every peer agrees on the order
+function getLeaderboard(n) {
+  if (!rctx) return [];
+  try {
+    leaderboard = rctx.get_leaderboard(n).map((row) => ({
+      id: row.id(),
+      area: row.area(),
+      kills: row.kills(),
+      survival: row.survival(),
+      team: row.team(),
+    }));
+  } catch (e) {
+    console.warn(e);
+  }
+  return leaderboard;
+}
+
+//Combined scores of each team in a team game, best first
+function getTeamLeaderboard() {
+  if (!rctx) return [];
+  try {
+    teamLeaderboard = rctx.get_team_leaderboard().map((row) => ({
+      team: row.team(),
+      area: row.area(),
+      kills: row.kills(),
+      players: row.players(),
+    }));
+  } catch (e) {
+    console.warn(e);
+  }
+  return teamLeaderboard;
+}
+
 function disconnect() {
   socket.disconnect();
   running = false;
@@ -194,6 +246,7 @@ function processFrame(processTime) {
     spawnIn();
   }
 
+  takeEvents();
   var events = []
   while (eventQueue[eventQueueHead] != null && processTime >= eventQueue[eventQueueHead].get_timestamp()) {
     events.push(eventQueue[eventQueueHead]);
@@ -203,6 +256,7 @@ function processFrame(processTime) {
 
   var newPlayers = [];
   var moves = [];
+  var pauses = [];
 
   for (var i = 0; i < events.length; i++) {
     if (events[i].is_input()) {
@@ -226,9 +280,9 @@ function processFrame(processTime) {
         posX: x,
         posY: y,
         currentHeading: 4,
-        name: id.toString(),
+        name: events[i].get_name() || id.toString(),
         num: id,
-        base: possColors.shift(),
+        base: core.Color.fromRgb(events[i].get_color()),
       };
       newPlayers.push(params);
 
@@ -238,6 +292,32 @@ function processFrame(processTime) {
         heading: 4,
         referenceTime: new Date(birthMillis)
       });
+    } else if (events[i].is_boost()) {
+      //Re-anchor like an input that keeps the heading, then speed up
+      var player = allPlayers[events[i].get_id()];
+      if (player != undefined) {
+        moves.push({
+          num: events[i].get_id(),
+          left: false,
+          heading: player.heading,
+          referenceTime: new Date(Number(events[i].get_timestamp())),
+          boost: true
+        });
+      }
+    } else if (events[i].is_profile()) {
+      var player = allPlayers[events[i].get_id()];
+      if (player != undefined) {
+        player.setProfile(events[i].get_name(), core.Color.fromRgb(events[i].get_color()));
+      }
+    } else if (events[i].is_round_start()) {
+      invokeRenderer("roundStart", [rctx.get_round()]);
+    } else if (events[i].is_round_end()) {
+      invokeRenderer("roundEnd", [rctx.get_round()]);
+    } else if (events[i].is_paused() || events[i].is_resumed()) {
+      pauses.push({
+        paused: events[i].is_paused(),
+        time: Number(events[i].get_timestamp())
+      });
     }
   }
 
@@ -255,6 +335,21 @@ function processFrame(processTime) {
     if (player != undefined) {
       player.updateReferencePoint(val.referenceTime);
       player.heading = val.heading;
+      if (val.boost) {
+        var start = val.referenceTime.getTime();
+        player.boost(start, start + consts.BOOST_DURATION);
+      }
+    }
+  });
+  //Players stop where they are for the pause, and carry on from there after
+  pauses.forEach((val) => {
+    if (val.paused && pausedAt == null) {
+      pausedAt = val.time;
+      players.forEach((player) => player.updateReferencePoint(new Date(pausedAt)));
+    } else if (!val.paused && pausedAt != null) {
+      var length = Math.max(0, val.time - pausedAt);
+      players.forEach((player) => player.shiftReferencePoint(length));
+      pausedAt = null;
     }
   });
   update();
@@ -317,7 +412,7 @@ function setUser(player) {
 }
 
 function update() {
-  var num = Date.now()
+  var num = pausedAt != null ? pausedAt : Date.now();
   var currentTime = new Date(num);
   var dead = [];
   core.updateFrame(grid, players, dead, (killer, other) => {
@@ -332,16 +427,20 @@ function update() {
   invokeRenderer("update", [frame]);
 }
 
-async function syncTick() {
-  await rctx.tips_sync();
-
-  var events = await rctx.take_events();
+//Queue events from tips_sync and the subscription socket
+function takeEvents() {
+  var events = rctx.take_events();
   for (var i=events.length-1; i>=0; i--) {
     eventQueue[eventQueueTail] = events[i];
     eventQueueTail = eventQueueTail + 1 % eventQueueSize;
   }
+}
+
+async function syncTick() {
+  await rctx.tips_sync();
 
-  setTimeout(syncTick, 50);
+  // Events are pushed while the subscription is open, so only the tips need refreshing
+  setTimeout(syncTick, rctx.is_streaming() ? 1000 : 50);
 }
 
 function gridSerialData(grid, players) {
@@ -390,9 +489,12 @@ function findEmpty(grid) {
 [
   connectGame,
   changeHeading,
+  activateBoost,
   getUser,
   getPlayers,
   getOthers,
+  getLeaderboard,
+  getTeamLeaderboard,
   disconnect,
   giveContext,
   syncTick,
@@ -0,0 +1,404 @@
+use serde::{Deserialize, Serialize};
+
+use rustdag_lib::dag::{
+    blockdag::BlockDAG,
+    consensus::key::KeyTipManager,
+    contract::{runtime::wasmi::WasmiRuntime, source::ContractSource, ContractValue},
+    generic_blockdag::GenericBlockDAG,
+    transaction::{updates::TransactionUpdates, Transaction},
+};
+use rustdag_lib::security::keys::eddsa::EdDSAKeyPair;
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Debug;
+use std::rc::Rc;
+
+use log::{debug, warn};
+
+pub type PeerId = u64;
+
+/// Peers only exchange transactions. Each peer executes them itself to get
+/// their updates, so nobody can hand out state the contract wouldn't produce.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum GossipMessage {
+    /// A single newly created transaction
+    Transaction(Transaction),
+    /// Every transaction the sender knows about, in commit order. Sent to
+    /// peers as soon as a connection to them opens.
+    Sync(Vec<Transaction>),
+}
+
+/// A way of exchanging messages with other peers
+pub trait Transport {
+    /// Send to every connected peer
+    fn broadcast(&mut self, message: &[u8]);
+
+    /// Send to a single peer
+    fn send(&mut self, peer: PeerId, message: &[u8]);
+
+    /// Messages received since the last call, with the peer that sent them
+    fn receive(&mut self) -> Vec<(PeerId, Vec<u8>)>;
+
+    /// Peers whose connection opened since the last call
+    fn take_new_peers(&mut self) -> Vec<PeerId>;
+}
+
+/// Object safe view of a `GossipNode`, so wasm types don't need to name the
+/// DAG type
+pub trait LocalNode {
+    fn deploy(&mut self, key: &EdDSAKeyPair, source: ContractSource) -> Result<(), String>;
+
+    /// Execute a contract call, commit it locally and gossip it to peers
+    fn execute(
+        &mut self,
+        key: &EdDSAKeyPair,
+        contract: u64,
+        func_name: &str,
+        args: &[ContractValue],
+    ) -> Result<(Option<ContractValue>, Transaction), String>;
+
+    /// Execute a contract call against local state without committing it
+    fn query(
+        &mut self,
+        key: &EdDSAKeyPair,
+        contract: u64,
+        func_name: &str,
+        args: &[ContractValue],
+    ) -> Result<Option<ContractValue>, String>;
+
+    /// Handle incoming messages, returning transactions newly committed from peers
+    fn poll(&mut self) -> Vec<Transaction>;
+
+    /// Handle incoming messages like `poll`, but hand each transaction
+    /// committed from peers to `on_commit` straight away, so it can read
+    /// state as that transaction left it
+    fn poll_each(&mut self, on_commit: &mut dyn FnMut(&mut dyn LocalNode, Transaction));
+
+    /// Commit a transaction made elsewhere, executing its contract call
+    /// against local state to derive the updates. One that can't be executed
+    /// yet, such as one whose parents haven't arrived, is held and retried
+    /// as more come in. Returns whether it was committed straight away.
+    fn replicate(&mut self, trans: Transaction) -> bool;
+
+    /// Every committed transaction, in commit order
+    fn history(&self) -> Vec<Transaction>;
+
+    /// Hashes of parents that held transactions are waiting for and that
+    /// haven't arrived in any form. State read while there are any is
+    /// missing that part of the history.
+    fn missing_parents(&self) -> Vec<u64>;
+}
+
+/// Create an empty in-process DAG running contracts with wasmi
+pub fn local_dag(address: u64) -> impl for<'a> BlockDAG<'a> {
+    GenericBlockDAG::<_, _, _, WasmiRuntime, _>::new(
+        HashMap::new(),
+        HashMap::new(),
+        HashMap::new(),
+        KeyTipManager::new(address),
+    )
+}
+
+/// A DAG replica that shares its transactions with peers over a `Transport`
+pub struct GossipNode<DAG, T> {
+    dag: DAG,
+    transport: T,
+    seen: HashSet<u64>,
+    log: Vec<Transaction>,
+    /// Transactions that couldn't be executed or committed yet, usually
+    /// because they arrived before their parents
+    pending: VecDeque<Transaction>,
+}
+
+impl<DAG, T> GossipNode<DAG, T>
+where
+    DAG: for<'a> BlockDAG<'a>,
+    T: Transport,
+{
+    pub fn new(dag: DAG, transport: T) -> Self {
+        GossipNode {
+            dag,
+            transport,
+            seen: HashSet::new(),
+            log: Vec::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn dag_mut(&mut self) -> &mut DAG {
+        &mut self.dag
+    }
+
+    /// Number of transactions committed to the local DAG
+    pub fn len(&self) -> usize {
+        self.log.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.log.is_empty()
+    }
+
+    /// Commit a transaction along with the updates executing it produced
+    fn commit(&mut self, trans: Transaction, updates: TransactionUpdates) -> bool {
+        let hash = trans.get_hash();
+        if self.seen.contains(&hash) {
+            return false;
+        }
+        match self.dag.commit_transaction(trans.clone(), updates) {
+            Ok(_) => {
+                self.seen.insert(hash);
+                self.log.push(trans);
+                true
+            }
+            Err(e) => {
+                self.defer(trans, e);
+                false
+            }
+        }
+    }
+
+    /// Execute a transaction made elsewhere against local state and commit
+    /// it, or hold on to it until it can be
+    fn accept(&mut self, trans: Transaction) -> bool {
+        if self.seen.contains(&trans.get_hash()) {
+            return false;
+        }
+        match self.dag.execute_transaction(&trans) {
+            Ok(updates) => self.commit(trans, updates),
+            Err(e) => {
+                self.defer(trans, e);
+                false
+            }
+        }
+    }
+
+    fn defer<E: Debug>(&mut self, trans: Transaction, reason: E) {
+        let hash = trans.get_hash();
+        if !self.pending.iter().any(|t| t.get_hash() == hash) {
+            debug!("Deferring transaction {}: {:?}", hash, reason);
+            self.pending.push_back(trans);
+        }
+    }
+
+    /// Retry deferred transactions until no more of them can be committed
+    fn drain_pending<F: FnMut(&mut Self, Transaction)>(&mut self, on_commit: &mut F) {
+        loop {
+            let before = self.pending.len();
+            for _ in 0..before {
+                let trans = self.pending.pop_front().expect("Pending length changed");
+                if self.accept(trans.clone()) {
+                    on_commit(self, trans);
+                }
+            }
+            if self.pending.len() >= before {
+                break;
+            }
+        }
+    }
+
+    /// Send our history to newly connected peers and commit what peers
+    /// sent, handing each transaction committed to `on_commit`
+    fn handle_messages<F: FnMut(&mut Self, Transaction)>(&mut self, on_commit: &mut F) {
+        for peer in self.transport.take_new_peers() {
+            let bytes = serde_json::to_vec(&GossipMessage::Sync(self.log.clone()))
+                .expect("Failed to serialize gossip message");
+            self.transport.send(peer, &bytes);
+        }
+
+        for (peer, bytes) in self.transport.receive() {
+            let batch = match serde_json::from_slice(&bytes) {
+                Ok(GossipMessage::Transaction(trans)) => vec![trans],
+                Ok(GossipMessage::Sync(log)) => log,
+                Err(e) => {
+                    warn!("Dropping malformed message from peer {}: {}", peer, e);
+                    continue;
+                }
+            };
+            for trans in batch {
+                if self.accept(trans.clone()) {
+                    // Flood to peers that may not be connected to the author
+                    self.gossip(&GossipMessage::Transaction(trans.clone()));
+                    on_commit(self, trans);
+                }
+            }
+        }
+        self.drain_pending(on_commit);
+    }
+
+    fn gossip(&mut self, message: &GossipMessage) {
+        let bytes = serde_json::to_vec(message).expect("Failed to serialize gossip message");
+        self.transport.broadcast(&bytes);
+    }
+}
+
+impl<DAG, T> LocalNode for GossipNode<DAG, T>
+where
+    DAG: for<'a> BlockDAG<'a>,
+    T: Transport,
+{
+    fn deploy(&mut self, key: &EdDSAKeyPair, source: ContractSource) -> Result<(), String> {
+        let (trans, updates) = self
+            .dag
+            .deploy_contract::<rand::rngs::ThreadRng>(key, source)
+            .map_err(|e| format!("{:?}", e))?;
+        self.commit(trans.clone(), updates);
+        self.gossip(&GossipMessage::Transaction(trans));
+        Ok(())
+    }
+
+    fn execute(
+        &mut self,
+        key: &EdDSAKeyPair,
+        contract: u64,
+        func_name: &str,
+        args: &[ContractValue],
+    ) -> Result<(Option<ContractValue>, Transaction), String> {
+        let (value, result) = self
+            .dag
+            .execute_contract::<rand::rngs::ThreadRng>(key, contract, func_name, args)
+            .map_err(|e| format!("{:?}", e))?;
+        let (trans, updates) = result.map_err(|e| format!("{:?}", e))?;
+        self.commit(trans.clone(), updates);
+        self.gossip(&GossipMessage::Transaction(trans.clone()));
+        Ok((value, trans))
+    }
+
+    fn query(
+        &mut self,
+        key: &EdDSAKeyPair,
+        contract: u64,
+        func_name: &str,
+        args: &[ContractValue],
+    ) -> Result<Option<ContractValue>, String> {
+        let (value, _) = self
+            .dag
+            .execute_contract::<rand::rngs::ThreadRng>(key, contract, func_name, args)
+            .map_err(|e| format!("{:?}", e))?;
+        Ok(value)
+    }
+
+    fn poll(&mut self) -> Vec<Transaction> {
+        let mut committed = Vec::new();
+        self.handle_messages(&mut |_, trans| committed.push(trans));
+        committed
+    }
+
+    fn poll_each(&mut self, on_commit: &mut dyn FnMut(&mut dyn LocalNode, Transaction)) {
+        self.handle_messages(&mut |node: &mut Self, trans| on_commit(node, trans));
+    }
+
+    fn replicate(&mut self, trans: Transaction) -> bool {
+        let committed = self.accept(trans);
+        self.drain_pending(&mut |_, _| ());
+        committed
+    }
+
+    fn history(&self) -> Vec<Transaction> {
+        self.log.clone()
+    }
+
+    fn missing_parents(&self) -> Vec<u64> {
+        let held = self
+            .pending
+            .iter()
+            .map(|trans| trans.get_hash())
+            .collect::<HashSet<_>>();
+        let mut missing = self
+            .pending
+            .iter()
+            .flat_map(|trans| vec![trans.get_trunk_hash(), trans.get_branch_hash()])
+            .filter(|hash| !self.seen.contains(hash) && !held.contains(hash))
+            .collect::<Vec<_>>();
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+}
+
+/// Transport with no peers, for a replica that is only fed through `replicate`
+pub struct NullTransport;
+
+impl Transport for NullTransport {
+    fn broadcast(&mut self, _message: &[u8]) {}
+
+    fn send(&mut self, _peer: PeerId, _message: &[u8]) {}
+
+    fn receive(&mut self) -> Vec<(PeerId, Vec<u8>)> {
+        Vec::new()
+    }
+
+    fn take_new_peers(&mut self) -> Vec<PeerId> {
+        Vec::new()
+    }
+}
+
+/// In-memory network of peers, for running gossip without a browser
+#[derive(Clone, Default)]
+pub struct MemoryNetwork {
+    inboxes: Rc<RefCell<Vec<VecDeque<(PeerId, Vec<u8>)>>>>,
+    new_peers: Rc<RefCell<Vec<Vec<PeerId>>>>,
+}
+
+impl MemoryNetwork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connect a new peer to every existing peer
+    pub fn join(&self) -> MemoryTransport {
+        let mut inboxes = self.inboxes.borrow_mut();
+        let mut new_peers = self.new_peers.borrow_mut();
+        let id = inboxes.len() as PeerId;
+        for peers in new_peers.iter_mut() {
+            peers.push(id);
+        }
+        new_peers.push((0..id).collect());
+        inboxes.push(VecDeque::new());
+
+        MemoryTransport {
+            id,
+            network: self.clone(),
+        }
+    }
+}
+
+pub struct MemoryTransport {
+    id: PeerId,
+    network: MemoryNetwork,
+}
+
+impl MemoryTransport {
+    pub fn id(&self) -> PeerId {
+        self.id
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn broadcast(&mut self, message: &[u8]) {
+        let peers = self.network.inboxes.borrow().len() as PeerId;
+        for peer in (0..peers).filter(|p| *p != self.id) {
+            self.send(peer, message);
+        }
+    }
+
+    fn send(&mut self, peer: PeerId, message: &[u8]) {
+        self.network.inboxes.borrow_mut()[peer as usize].push_back((self.id, message.to_vec()));
+    }
+
+    fn receive(&mut self) -> Vec<(PeerId, Vec<u8>)> {
+        self.network.inboxes.borrow_mut()[self.id as usize]
+            .drain(..)
+            .collect()
+    }
+
+    fn take_new_peers(&mut self) -> Vec<PeerId> {
+        self.network.new_peers.borrow_mut()[self.id as usize]
+            .drain(..)
+            .collect()
+    }
+}
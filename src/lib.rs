@@ -4,16 +4,36 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::future_to_promise;
 
 use rustdag_lib::dag::contract::ContractValue;
-use rustdag_lib::dag::transaction::data::TransactionData;
+use rustdag_lib::dag::transaction::{data::TransactionData, Transaction};
 use rustdag_lib::security::keys::eddsa::{get_address, get_public_key, new_key_pair, EdDSAKeyPair};
 
 use rustdag_wasm::blockdag::BlockDAG;
 
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::mpsc::{channel, Receiver};
 
-use std::{panic, rc::Rc, sync::RwLock};
+use std::{
+    cell::{Cell, RefCell},
+    panic,
+    rc::Rc,
+    sync::RwLock,
+};
 
-use log::info;
+use log::{info, warn};
+
+pub mod gossip;
+mod nodes;
+mod peer;
+mod rounds;
+mod rtc;
+mod spectator;
+mod stream;
+#[cfg(feature = "tools")]
+pub mod tools;
+pub mod verify;
+
+use gossip::{local_dag, GossipNode, LocalNode, NullTransport};
+use nodes::NodePool;
+use stream::{EventSink, Subscription};
 
 #[wasm_bindgen]
 pub fn init() -> Result<(), JsValue> {
@@ -28,26 +48,65 @@ pub fn init() -> Result<(), JsValue> {
 
 #[wasm_bindgen]
 pub struct Context {
-    blockdag: BlockDAG,
-    keypair: Rc<RwLock<Option<EdDSAKeyPair>>>,
-    event_sender: Sender<Event>,
+    nodes: Rc<RefCell<NodePool<BlockDAG>>>,
+    keypair: Rc<RwLock<Option<Rc<EdDSAKeyPair>>>>,
+    events: EventSink,
     event_receiver: Receiver<Event>,
+    subscription: RefCell<Option<Subscription>>,
+    streaming: Rc<Cell<bool>>,
+    replica: Rc<RefCell<Box<dyn LocalNode>>>,
+    /// Set while the replica is built again from the whole history, when
+    /// its state can't be read
+    rebuilding: Rc<Cell<bool>>,
+    query_key: Rc<EdDSAKeyPair>,
     contract_address: u64,
+    /// Game within the contract this context plays in and reports events for
+    game: Rc<Cell<u64>>,
+    /// Spawns of players still in the waiting queue, held back until the
+    /// contract admits them
+    waiting: RefCell<Vec<Event>>,
+    /// Name and color to publish for every player this context spawns
+    profile: Rc<RefCell<Option<(String, Option<u32>)>>>,
+    /// Team to join with every player this context spawns in a team game, or
+    /// 0 to be put on the smallest team
+    team: Rc<Cell<u64>>,
+    /// Key admin calls are signed with, kept apart from the player's own so
+    /// playing never uses the owner's key by accident
+    admin_keypair: Rc<RwLock<Option<Rc<EdDSAKeyPair>>>>,
 }
 
 #[wasm_bindgen]
 impl Context {
+    /// Create a context talking to the given rustdag nodes. Requests go to one
+    /// active node, and move on to the others in turn while it can't be
+    /// reached.
     #[wasm_bindgen(constructor)]
-    pub fn new(url: String, contract_address: String) -> Self {
+    pub fn new(urls: Box<[JsValue]>, contract_address: String) -> Self {
+        let urls = urls
+            .iter()
+            .map(|url| url.as_string().expect("Node urls must be strings."))
+            .collect();
         let (send, recv) = channel();
+        let query_key = new_key_pair();
+        let replica = new_replica(&query_key);
         Context {
-            blockdag: BlockDAG::new(url),
+            nodes: Rc::from(RefCell::from(NodePool::new(urls, BlockDAG::new))),
             keypair: Rc::from(RwLock::from(None)),
-            event_sender: send,
+            events: EventSink::new(send),
             event_receiver: recv,
+            subscription: RefCell::from(None),
+            streaming: Rc::from(Cell::from(false)),
+            replica: Rc::from(RefCell::from(replica)),
+            rebuilding: Rc::from(Cell::from(false)),
+            query_key: Rc::from(query_key),
             contract_address: contract_address
                 .parse()
                 .expect("Failed to parse contract address."),
+            game: Rc::from(Cell::from(0)),
+            waiting: RefCell::from(Vec::new()),
+            profile: Rc::from(RefCell::from(None)),
+            team: Rc::from(Cell::from(0)),
+            admin_keypair: Rc::from(RwLock::from(None)),
         }
     }
 
@@ -55,56 +114,51 @@ impl Context {
         rustdag_lib::util::epoch_time().to_string()
     }
 
+    /// Url of the node requests are currently sent to
+    pub fn active_node(&self) -> String {
+        self.nodes.borrow().active().url.clone()
+    }
+
+    /// Fetch new transactions from the active node, replicating them into the
+    /// local DAG and queueing their events. A dropped subscription is opened
+    /// again from here once it has waited long enough.
     pub fn tips_sync(&self) -> Promise {
-        let blockdag = self.blockdag.clone();
-        let contract_address = self.contract_address;
-        let sender = self.event_sender.clone();
+        self.resubscribe();
+        let link = self.link();
 
         future_to_promise(async move {
-            BlockDAG::tips_sync(blockdag.clone(), move |trans| match trans.get_data() {
-                TransactionData::ExecContract {
-                    func_name,
-                    args,
-                    contract,
-                } if *contract == contract_address => {
-                    let event = match &func_name[..] {
-                        "spawn_player" => match &args[..] {
-                            [x, y] => Some(Event::spawn(
-                                trans.get_address().to_string(),
-                                contract_val_to_i32(*x),
-                                contract_val_to_i32(*y),
-                                trans.get_timestamp(),
-                            )),
-                            _ => panic!(
-                                "Unexpected number of arguments. Got {}, expected 2",
-                                args.len()
-                            ),
-                        },
-                        "apply_input" => match &args[..] {
-                            [heading] => Some(Event::input(
-                                trans.get_address().to_string(),
-                                unwrap_contract_u64(*heading) as u32,
-                                trans.get_timestamp(),
-                            )),
-                            _ => panic!(
-                                "Unexpected number of arguments. Got {}, expected 2",
-                                args.len()
-                            ),
-                        },
-                        _ => None,
-                    };
-                    if let Some(e) = event {
-                        sender.send(e).expect("Failed to send in the event mpsc");
-                    }
-                }
-                _ => (),
-            })
-            .await?;
-
+            let index = link.nodes.borrow().attempt_order()[0];
+            link.sync_tips(index).await?;
             Ok(0.into())
         })
     }
 
+    /// Subscribe to pushed transactions for the contract over a WebSocket.
+    /// Resolves to `false` if the node has no subscription endpoint, in which
+    /// case events keep arriving through `tips_sync` polling.
+    pub fn subscribe(&self) -> Promise {
+        let url = stream::subscribe_url(&self.active_node(), self.contract_address);
+        match Subscription::open(
+            &url,
+            self.contract_address,
+            self.events.clone(),
+            self.streaming.clone(),
+        ) {
+            Ok((subscription, opened)) => {
+                self.subscription.borrow_mut().replace(subscription);
+                opened
+            }
+            Err(e) => {
+                warn!("Failed to open subscription socket: {:?}", e);
+                Promise::resolve(&JsValue::FALSE)
+            }
+        }
+    }
+
+    pub fn is_streaming(&self) -> bool {
+        self.streaming.get()
+    }
+
     pub fn get_address(&self) -> String {
         get_address(&get_public_key(
             self.keypair
@@ -117,127 +171,330 @@ impl Context {
     }
 
     pub fn spawn_player(&self, x: i32, y: i32) -> Promise {
-        let inner = self.blockdag.clone_inner();
-        let new_key_pair_val = new_key_pair();
-        let addr = get_address(&get_public_key(&new_key_pair_val));
         self.keypair
             .write()
             .expect("Failed to acquire lock.")
-            .replace(new_key_pair_val); // create new keypair
+            .replace(Rc::from(new_key_pair())); // create new keypair
+        let link = self.link();
         let keypair = self.keypair.clone();
-        let contract_address = self.contract_address;
-        let event_sender = self.event_sender.clone();
+        let game = self.game.get();
+        let team = self.team.get();
+        let profile = self.profile.borrow().clone();
 
         future_to_promise(async move {
-            let mut remote = inner.lock().await;
+            // A new keypair starts out in game 0
+            if game != 0 {
+                link.execute_and_replicate(&keypair, "join_game", &[ContractValue::U64(game)])
+                    .await?;
+            }
 
-            remote.update_address(addr); // tip consensus manager will use our new address to ensure our tip selection includes our own transactions
+            // Teams can only be picked before spawning
+            if team != 0 {
+                link.execute_and_replicate(&keypair, "join_team", &[ContractValue::U64(team)])
+                    .await?;
+            }
 
-            let (_, trans) = remote
-                .execute_contract(
-                    keypair
-                        .read()
-                        .expect("Failed to acquire lock")
-                        .as_ref()
-                        .expect("Failed to call spawn_player before applying input."),
-                    contract_address,
+            let (code, trans) = link
+                .execute(
+                    &keypair,
                     "spawn_player",
                     &[i32_to_contract_val(x), i32_to_contract_val(y)],
                 )
                 .await?;
 
+            // The new keypair has no profile yet
+            if let Some((name, color)) = profile {
+                link.execute_and_replicate(
+                    &keypair,
+                    "set_profile",
+                    &profile_args(&name, color).map_err(|e| JsValue::from_str(&e))?,
+                )
+                .await?;
+            }
+
             let trans =
-                trans.expect("apply_input contract execution failed to produce a transaction.");
+                trans.expect("spawn_player contract execution failed to produce a transaction.");
+            link.replica.borrow_mut().replicate(trans.clone());
+            if let Err(e) = check_code("spawn_player", code) {
+                link.events.emit(trans.get_hash(), None);
+                return Err(e);
+            }
 
-            event_sender
-                .send(Event::spawn(
+            link.events.emit(
+                trans.get_hash(),
+                Some(Event::spawn(
                     trans.get_address().to_string(),
                     x,
                     y,
                     trans.get_timestamp(),
-                ))
-                .expect("Failed to send in the event mpsc");
+                )),
+            );
 
             Ok(1.into())
         })
     }
 
-    pub fn get_player(&self, id: String) -> Promise {
-        let id_num = id.parse().expect("Failed to parse id.");
-        let inner = self.blockdag.clone_inner();
+    /// Publish the name and color (as 0xRRGGBB) other players see. Without a
+    /// color the contract picks one from the address. The profile is sent
+    /// again for every player spawned afterwards.
+    pub fn set_profile(&self, name: String, color: Option<u32>) -> Promise {
+        let args = match profile_args(&name, color) {
+            Ok(args) => args,
+            Err(e) => return Promise::reject(&JsValue::from_str(&e)),
+        };
+        self.profile.borrow_mut().replace((name, color));
+        if self
+            .keypair
+            .read()
+            .expect("Failed to acquire lock")
+            .is_none()
+        {
+            return Promise::resolve(&JsValue::TRUE);
+        }
+
+        let link = self.link();
         let keypair = self.keypair.clone();
-        let contract_address = self.contract_address;
 
         future_to_promise(async move {
-            let (x, _) = inner
-                .lock()
-                .await
-                .execute_contract(
-                    keypair
-                        .read()
-                        .expect("Failed to acquire lock")
-                        .as_ref()
-                        .expect("Failed to call spawn_player before applying input."),
-                    contract_address,
-                    "get_player_x",
-                    &[ContractValue::U64(id_num)],
-                )
+            link.execute_and_replicate(&keypair, "set_profile", &args)
                 .await?;
-            let x = x.expect("Should return a value");
-
-            let (y, _) = inner
-                .lock()
-                .await
-                .execute_contract(
-                    keypair
-                        .read()
-                        .expect("Failed to acquire lock")
-                        .as_ref()
-                        .expect("Failed to call spawn_player before applying input."),
-                    contract_address,
-                    "get_player_y",
-                    &[ContractValue::U64(id_num)],
+            Ok(JsValue::TRUE)
+        })
+    }
+
+    /// Pick the team players spawned from now on play for, in games split
+    /// into teams. 0 goes back to being put on the smallest team.
+    pub fn join_team(&self, team: u32) {
+        self.team.set(team.into());
+    }
+
+    /// Open a new game in the contract. Resolves to its id, which can be
+    /// passed to `join_game`. A `team_count` of 0 makes it every player for
+    /// themselves. Only the owner can open games, so this is signed with the
+    /// admin key.
+    pub fn create_game(&self, round_length: u32, max_players: u32, team_count: u32) -> Promise {
+        if let Err(e) = self.check_admin_key() {
+            return Promise::reject(&e);
+        }
+        let link = self.link();
+        let keypair = self.admin_keypair.clone();
+
+        future_to_promise(async move {
+            let (value, _) = link
+                .execute_and_replicate(
+                    &keypair,
+                    "create_game",
+                    &[
+                        ContractValue::U64(round_length.into()),
+                        ContractValue::U64(max_players.into()),
+                        ContractValue::U64(team_count.into()),
+                    ],
                 )
                 .await?;
-            let y = y.expect("Should return a value");
-
-            let (heading, _) = inner
-                .lock()
-                .await
-                .execute_contract(
-                    keypair
-                        .read()
-                        .expect("Failed to acquire lock")
-                        .as_ref()
-                        .expect("Failed to call spawn_player before applying input."),
-                    contract_address,
-                    "get_player_heading",
-                    &[ContractValue::U64(id_num)],
-                )
+            let id = unwrap_contract_u64(value.expect("create_game should return the game id"));
+            // Refusals are negated error codes
+            if (id as i64) < 0 {
+                return Err(JsValue::from_str(&format!(
+                    "create_game returned error code {}",
+                    -(id as i64)
+                )));
+            }
+            Ok(id.to_string().into())
+        })
+    }
+
+    /// Play in another game. Players spawned from now on go to that game, and
+    /// the current player leaves its game straight away.
+    pub fn join_game(&self, game_id: String) -> Promise {
+        let game: u64 = match game_id.parse() {
+            Ok(game) => game,
+            Err(_) => return Promise::reject(&JsValue::from_str("Failed to parse game id.")),
+        };
+        self.game.set(game);
+        if self
+            .keypair
+            .read()
+            .expect("Failed to acquire lock")
+            .is_none()
+        {
+            return Promise::resolve(&JsValue::TRUE);
+        }
+
+        let link = self.link();
+        let keypair = self.keypair.clone();
+
+        future_to_promise(async move {
+            link.execute_and_replicate(&keypair, "join_game", &[ContractValue::U64(game)])
                 .await?;
-            let heading = heading.expect("Should return a value");
+            Ok(JsValue::TRUE)
+        })
+    }
+
+    /// Id of the game this context plays in
+    pub fn current_game(&self) -> String {
+        self.game.get().to_string()
+    }
 
-            Ok(PlayerData::from_contract(x, y, heading).into())
+    /// Place of our player in the waiting queue of a full game, counting
+    /// from 1, or undefined if they aren't waiting
+    pub fn queue_position(&self) -> JsValue {
+        let keypair = self.keypair.read().expect("Failed to acquire lock");
+        let address = match keypair.as_ref() {
+            Some(keypair) => get_address(&get_public_key(keypair)),
+            None => return JsValue::UNDEFINED,
+        };
+        match self.queue_position_of(&address.to_string()) {
+            0 => JsValue::UNDEFINED,
+            position => (position as u32).into(),
+        }
+    }
+
+    /// Every game in the contract, read from the local replica
+    pub fn list_games(&self) -> Result<JsValue, JsValue> {
+        let count = unwrap_contract_u64(self.call_local("get_game_count", &[])?);
+        let games = js_sys::Array::new();
+        for id in 0..count {
+            let get = |func_name| -> Result<u64, JsValue> {
+                Ok(unwrap_contract_u64(
+                    self.call_local(func_name, &[ContractValue::U64(id)])?,
+                ))
+            };
+            games.push(
+                &GameSummary {
+                    id,
+                    round: get("get_round")?,
+                    phase: get("get_phase")? as u32,
+                    round_length: get("get_round_length")?,
+                    players: get("get_player_count")?,
+                    max_players: get("get_max_players")?,
+                    team_count: get("get_team_count")?,
+                }
+                .into(),
+            );
+        }
+        Ok(games.into())
+    }
+
+    /// Evaluate a view function of the contract against the local replica.
+    /// Nothing is signed or committed, so this works before `spawn_player`
+    /// and for spectators. Arguments are numbers or decimal strings. The
+    /// result is a number, read as the contract's signed integers, or a
+    /// decimal string if it is too large for a number to hold exactly, as
+    /// addresses are.
+    pub fn query(&self, func_name: String, args: Box<[JsValue]>) -> Result<JsValue, JsValue> {
+        if !is_read_only(&func_name) {
+            return Err(JsValue::from_str(&format!(
+                "{} is not a view function",
+                func_name
+            )));
+        }
+        let args = args
+            .iter()
+            .map(js_to_contract_val)
+            .collect::<Result<Vec<_>, _>>()?;
+        let value = self.call_local(&func_name, &args)?;
+        Ok(match safe_integer(value) {
+            Some(n) => (n as f64).into(),
+            None => unwrap_contract_u64(value).to_string().into(),
+        })
+    }
+
+    /// Read a player's state. Runs the contract getters against the local
+    /// replica, so no request is made to the node.
+    pub fn get_player(&self, id: String) -> Result<PlayerData, JsValue> {
+        self.check_history()?;
+        let id_num = id.parse().expect("Failed to parse id.");
+        let x = self.call_local("get_player_x", &[ContractValue::U64(id_num)])?;
+        let y = self.call_local("get_player_y", &[ContractValue::U64(id_num)])?;
+        let heading = self.call_local("get_player_heading", &[ContractValue::U64(id_num)])?;
+        let (name, color) = read_profile(|f, args| self.call_local(f, args), id_num)?;
+
+        Ok(PlayerData::from_contract(x, y, heading).with_profile(name, color))
+    }
+
+    /// The top `n` players of the current game by territory, then kills, as
+    /// ranked by the contract. Every peer replaying the same DAG sees the
+    /// same order. The contract only ranks the top `LEADERBOARD_NUM`, so
+    /// asking for more returns at most that many.
+    pub fn get_leaderboard(&self, n: u32) -> Result<JsValue, JsValue> {
+        self.check_history()?;
+        let game = ContractValue::U64(self.game.get());
+        let size = unwrap_contract_u64(self.call_local("get_leaderboard_size", &[game])?);
+        let rows = js_sys::Array::new();
+        for rank in 0..size.min(n.into()) {
+            let id = self.call_local("get_leaderboard", &[game, ContractValue::U64(rank)])?;
+            let id = unwrap_contract_u64(id);
+            let area = self.call_local("get_player_area", &[ContractValue::U64(id)])?;
+            let kills = self.call_local("get_player_kills", &[ContractValue::U64(id)])?;
+            let survival = self.call_local("get_player_survival", &[ContractValue::U64(id)])?;
+            let team = self.call_local("get_player_team", &[ContractValue::U64(id)])?;
+            rows.push(
+                &LeaderboardRow {
+                    id,
+                    area: unwrap_contract_u64(area),
+                    kills: unwrap_contract_u64(kills),
+                    survival: unwrap_contract_u64(survival),
+                    team: unwrap_contract_u64(team),
+                }
+                .into(),
+            );
+        }
+        Ok(rows.into())
+    }
+
+    /// Every team of the current game with the combined score of its live
+    /// players, best first. Empty if the game has no teams.
+    pub fn get_team_leaderboard(&self) -> Result<JsValue, JsValue> {
+        self.check_history()?;
+        let game = ContractValue::U64(self.game.get());
+        let count = unwrap_contract_u64(self.call_local("get_team_count", &[game])?);
+        let rows = js_sys::Array::new();
+        for rank in 0..count {
+            let team =
+                self.call_local("get_team_leaderboard", &[game, ContractValue::U64(rank)])?;
+            let get = |func_name| -> Result<u64, JsValue> {
+                Ok(unwrap_contract_u64(
+                    self.call_local(func_name, &[game, team])?,
+                ))
+            };
+            rows.push(
+                &TeamRow {
+                    team: unwrap_contract_u64(team),
+                    area: get("get_team_area")?,
+                    kills: get("get_team_kills")?,
+                    players: get("get_team_players")?,
+                }
+                .into(),
+            );
+        }
+        Ok(rows.into())
+    }
+
+    /// The current round of the current game and its phase, read from the
+    /// local replica
+    pub fn get_round(&self) -> Result<RoundInfo, JsValue> {
+        let game = ContractValue::U64(self.game.get());
+        let get = |func_name| -> Result<u64, JsValue> {
+            Ok(unwrap_contract_u64(self.call_local(func_name, &[game])?))
+        };
+        Ok(RoundInfo {
+            round: get("get_round")?,
+            phase: get("get_phase")? as u32,
+            length: get("get_round_length")?,
+            tick: get("get_current_game_tick")?,
+            winner: get("get_winner")?,
+            winning_team: get("get_winning_team")?,
         })
     }
 
     pub fn apply_input(&self, heading: u32) -> Promise {
-        let inner = self.blockdag.clone_inner();
+        let link = self.link();
         let keypair = self.keypair.clone();
-        let contract_address = self.contract_address;
-        let event_sender = self.event_sender.clone();
 
         future_to_promise(async move {
-            let (_, trans) = inner
-                .lock()
-                .await
-                .execute_contract(
-                    keypair
-                        .read()
-                        .expect("Failed to acquire lock")
-                        .as_ref()
-                        .expect("Failed to call spawn_player before applying input."),
-                    contract_address,
+            let (code, trans) = link
+                .execute(
+                    &keypair,
                     "apply_input",
                     &[ContractValue::U64(heading.into())],
                 )
@@ -245,33 +502,542 @@ impl Context {
 
             let trans =
                 trans.expect("apply_input contract execution failed to produce a transaction.");
+            link.replica.borrow_mut().replicate(trans.clone());
+            if let Err(e) = check_code("apply_input", code) {
+                link.events.emit(trans.get_hash(), None);
+                return Err(e);
+            }
 
-            event_sender
-                .send(Event::input(
+            link.events.emit(
+                trans.get_hash(),
+                Some(Event::input(
                     trans.get_address().to_string(),
                     heading,
                     trans.get_timestamp(),
-                ))
-                .expect("Failed to send in the event mpsc");
+                )),
+            );
+
+            Ok(1.into())
+        })
+    }
+
+    /// Speed the player up for a while, if their last boost has cooled down
+    pub fn activate_boost(&self) -> Promise {
+        let link = self.link();
+        let keypair = self.keypair.clone();
+
+        future_to_promise(async move {
+            let (code, trans) = link.execute(&keypair, "activate_boost", &[]).await?;
+
+            let trans =
+                trans.expect("activate_boost contract execution failed to produce a transaction.");
+            link.replica.borrow_mut().replicate(trans.clone());
+            if let Err(e) = check_code("activate_boost", code) {
+                link.events.emit(trans.get_hash(), None);
+                return Err(e);
+            }
+
+            link.events.emit(
+                trans.get_hash(),
+                Some(Event::boost(
+                    trans.get_address().to_string(),
+                    trans.get_timestamp(),
+                )),
+            );
 
             Ok(1.into())
         })
     }
 
+    /// Sign admin calls with the PKCS#8 encoded key of the contract's owner,
+    /// as written by `rustdag key generate`. Returns the key's address.
+    pub fn set_admin_key(&self, pkcs8: Box<[u8]>) -> Result<String, JsValue> {
+        let keypair = EdDSAKeyPair::from_pkcs8(&pkcs8)
+            .map_err(|e| JsValue::from_str(&format!("Failed to load admin key: {:?}", e)))?;
+        let address = get_address(&get_public_key(&keypair));
+        self.admin_keypair
+            .write()
+            .expect("Failed to acquire lock.")
+            .replace(Rc::from(keypair));
+        Ok(address.to_string())
+    }
+
+    /// Set a game's round length in ticks, 0 for rounds that never end, and
+    /// its player limit, 0 for the default
+    pub fn configure(&self, game_id: String, round_length: u32, max_players: u32) -> Promise {
+        let game = match parse_id(&game_id) {
+            Ok(game) => game,
+            Err(e) => return Promise::reject(&e),
+        };
+        self.admin_call(
+            "configure",
+            vec![
+                ContractValue::U64(game),
+                ContractValue::U64(round_length.into()),
+                ContractValue::U64(max_players.into()),
+            ],
+        )
+    }
+
+    /// End a game's round now and start the next one
+    pub fn reset_round(&self, game_id: String) -> Promise {
+        self.admin_call_on("reset_round", &game_id)
+    }
+
+    pub fn pause_game(&self, game_id: String) -> Promise {
+        self.admin_call_on("pause_game", &game_id)
+    }
+
+    pub fn resume_game(&self, game_id: String) -> Promise {
+        self.admin_call_on("resume_game", &game_id)
+    }
+
+    /// Kill a player. They can spawn again straight away.
+    pub fn kick_player(&self, id: String) -> Promise {
+        self.admin_call_on("kick_player", &id)
+    }
+
+    /// Kill a player and keep them out of every game until unbanned
+    pub fn ban_player(&self, id: String) -> Promise {
+        self.admin_call_on("ban_player", &id)
+    }
+
+    pub fn unban_player(&self, id: String) -> Promise {
+        self.admin_call_on("unban_player", &id)
+    }
+
+    /// Hand the contract over to another address. The admin key stops
+    /// working for admin calls once this resolves.
+    pub fn transfer_ownership(&self, new_owner: String) -> Promise {
+        self.admin_call_on("transfer_ownership", &new_owner)
+    }
+
+    /// Replay every transaction in the local replica through a fresh copy of
+    /// the contract, and report on anything each address sent that a fair
+    /// client wouldn't have
+    pub fn verify(&self) -> JsValue {
+        let history = self.replica.borrow().history();
+        verify::verify_history(&history, self.contract_address)
+            .into_iter()
+            .map(|report| JsValue::from(verify::VerifyReport::from(report)))
+            .collect::<js_sys::Array>()
+            .into()
+    }
+
     pub fn take_events(&self) -> JsValue {
-        self.event_receiver
-            .try_iter()
+        self.drain_events()
+            .into_iter()
             .map(|e| JsValue::from(e))
             .collect::<js_sys::Array>()
             .into()
     }
 }
 
+impl Context {
+    /// Reopen the subscription socket if it dropped a while ago
+    fn resubscribe(&self) {
+        let due = self
+            .subscription
+            .borrow()
+            .as_ref()
+            .map_or(false, |s| s.reconnect_due(js_sys::Date::now()));
+        if due {
+            let _ = self.subscribe();
+        }
+    }
+
+    /// Events for the current game since the last call. A spawn that put its
+    /// player in the waiting queue is only returned once they are admitted.
+    fn drain_events(&self) -> Vec<Event> {
+        let held = self.waiting.replace(Vec::new());
+        let mut events = Vec::new();
+        for event in held.into_iter().chain(self.event_receiver.try_iter()) {
+            if !self.in_current_game(&event) {
+                continue;
+            }
+            if event.kind == EventKind::Spawn && self.queue_position_of(&event.id) > 0 {
+                self.waiting.borrow_mut().push(event);
+            } else {
+                events.push(self.with_profile(self.with_spawn_time(event)));
+            }
+        }
+        events
+    }
+
+    /// Move a spawn event to when the player was actually spawned. A player
+    /// let in from the waiting queue starts at the tick of the call that
+    /// admitted them, not the one where they asked. The replica still holds
+    /// an earlier life until the spawn itself is replicated, so the event's
+    /// own time is never moved back.
+    fn with_spawn_time(&self, mut event: Event) -> Event {
+        if event.kind != EventKind::Spawn {
+            return event;
+        }
+        if let Ok(id) = event.id.parse() {
+            if let Ok(time) = self.call_local("get_player_spawn_time", &[ContractValue::U64(id)]) {
+                event.timestamp = event.timestamp.max(unwrap_contract_u64(time));
+            }
+        }
+        event
+    }
+
+    /// Fill in the player's current name and color on spawn and profile
+    /// events
+    fn with_profile(&self, mut event: Event) -> Event {
+        if event.kind != EventKind::Spawn && event.kind != EventKind::Profile {
+            return event;
+        }
+        if let Ok(id) = event.id.parse() {
+            if let Ok((name, color)) = read_profile(|f, args| self.call_local(f, args), id) {
+                event.name = name;
+                event.color = color;
+            }
+        }
+        event
+    }
+
+    fn queue_position_of(&self, id: &str) -> u64 {
+        let id = match id.parse() {
+            Ok(id) => ContractValue::U64(id),
+            Err(_) => return 0,
+        };
+        self.call_local("get_queue_position", &[id])
+            .map_or(0, unwrap_contract_u64)
+    }
+
+    /// Whether an event belongs to the current game. Players are looked up
+    /// in the replica, so a player whose `join_game` hasn't been replicated
+    /// yet still counts as being in their old game.
+    fn in_current_game(&self, event: &Event) -> bool {
+        let game = self.game.get();
+        match event.kind {
+            EventKind::RoundStart
+            | EventKind::RoundEnd
+            | EventKind::Paused
+            | EventKind::Resumed => event.game == game,
+            // Profiles are shared by every game
+            EventKind::Profile => true,
+            EventKind::Spawn | EventKind::Input | EventKind::Boost => {
+                let id = match event.id.parse() {
+                    Ok(id) => ContractValue::U64(id),
+                    Err(_) => return false,
+                };
+                self.call_local("get_player_game", &[id])
+                    .map_or(game == 0, |g| unwrap_contract_u64(g) == game)
+            }
+        }
+    }
+
+    /// Admin call taking a single game or player id
+    fn admin_call_on(&self, func_name: &'static str, id: &str) -> Promise {
+        match parse_id(id) {
+            Ok(id) => self.admin_call(func_name, vec![ContractValue::U64(id)]),
+            Err(e) => Promise::reject(&e),
+        }
+    }
+
+    /// Call an owner only entry point with the admin key. Resolves to true,
+    /// or rejects with the contract's error code.
+    fn admin_call(&self, func_name: &'static str, args: Vec<ContractValue>) -> Promise {
+        if let Err(e) = self.check_admin_key() {
+            return Promise::reject(&e);
+        }
+        let link = self.link();
+        let keypair = self.admin_keypair.clone();
+
+        future_to_promise(async move {
+            let (code, trans) = link.execute(&keypair, func_name, &args).await?;
+            let trans = trans.unwrap_or_else(|| {
+                panic!(
+                    "{} contract execution failed to produce a transaction.",
+                    func_name
+                )
+            });
+            // Replicated here, so tips_sync won't see the round change
+            link.replicate(&trans);
+            check_code(func_name, code)?;
+            Ok(JsValue::TRUE)
+        })
+    }
+
+    fn check_admin_key(&self) -> Result<(), JsValue> {
+        match *self.admin_keypair.read().expect("Failed to acquire lock") {
+            Some(_) => Ok(()),
+            None => Err(JsValue::from_str(
+                "Call set_admin_key before making admin calls.",
+            )),
+        }
+    }
+
+    fn link(&self) -> Link {
+        Link {
+            nodes: self.nodes.clone(),
+            contract_address: self.contract_address,
+            replica: self.replica.clone(),
+            rebuilding: self.rebuilding.clone(),
+            query_key: self.query_key.clone(),
+            events: self.events.clone(),
+        }
+    }
+
+    /// Fail if the replica is missing part of the history, so state read
+    /// from it would be wrong. The next `tips_sync` fetches what is missing,
+    /// building the replica again if the node doesn't send it.
+    fn check_history(&self) -> Result<(), JsValue> {
+        if self.rebuilding.get() {
+            return Err(JsValue::from_str("The replica is fetching the history."));
+        }
+        let missing = self.replica.borrow().missing_parents();
+        if !missing.is_empty() {
+            return Err(JsValue::from_str(&format!(
+                "The replica is missing {} transactions of the history.",
+                missing.len()
+            )));
+        }
+        Ok(())
+    }
+
+    fn ensure_keypair(&self) {
+        let mut keypair = self.keypair.write().expect("Failed to acquire lock.");
+        if keypair.is_none() {
+            keypair.replace(Rc::from(new_key_pair()));
+        }
+    }
+
+    /// Run a read-only contract function against the local replica
+    fn call_local(
+        &self,
+        func_name: &str,
+        args: &[ContractValue],
+    ) -> Result<ContractValue, JsValue> {
+        debug_assert!(
+            is_read_only(func_name),
+            "{} changes contract state",
+            func_name
+        );
+        query_replica(
+            &self.replica,
+            &self.query_key,
+            self.contract_address,
+            func_name,
+            args,
+        )
+    }
+}
+
+/// An empty replica, fed only by syncing from the nodes
+fn new_replica(query_key: &EdDSAKeyPair) -> Box<dyn LocalNode> {
+    Box::new(GossipNode::new(
+        local_dag(get_address(&get_public_key(query_key))),
+        NullTransport,
+    ))
+}
+
+/// Run a contract function against a local replica without committing it
+fn query_replica(
+    replica: &RefCell<Box<dyn LocalNode>>,
+    key: &EdDSAKeyPair,
+    contract_address: u64,
+    func_name: &str,
+    args: &[ContractValue],
+) -> Result<ContractValue, JsValue> {
+    let value = replica
+        .borrow_mut()
+        .query(key, contract_address, func_name, args)
+        .map_err(|e| JsValue::from_str(&e))?;
+    Ok(value.expect("Should return a value"))
+}
+
+/// The nodes a `Context` sends calls to, and the replica and events it
+/// keeps of them. Cloned into the future of each call.
+#[derive(Clone)]
+struct Link {
+    nodes: Rc<RefCell<NodePool<BlockDAG>>>,
+    contract_address: u64,
+    replica: Rc<RefCell<Box<dyn LocalNode>>>,
+    rebuilding: Rc<Cell<bool>>,
+    query_key: Rc<EdDSAKeyPair>,
+    events: EventSink,
+}
+
+impl Link {
+    /// Fetch new transactions from the node at `index`. Parents that were
+    /// missing before and a whole sync didn't bring are never coming, so
+    /// the replica is built again from the node's full history.
+    async fn sync_tips(&self, index: usize) -> Result<(), JsValue> {
+        let missing = self.replica.borrow().missing_parents();
+        self.fetch_tips(index).await?;
+        let stuck = self
+            .replica
+            .borrow()
+            .missing_parents()
+            .iter()
+            .any(|hash| missing.contains(hash));
+        if stuck {
+            self.rebuild(index).await?;
+        }
+        Ok(())
+    }
+
+    /// Start the replica again from nothing, with a new connection to the
+    /// node at `index` so it sends the whole history
+    async fn rebuild(&self, index: usize) -> Result<(), JsValue> {
+        let url = self.nodes.borrow().get(index).url.clone();
+        warn!(
+            "Replica is missing history, fetching all of it from {}",
+            url
+        );
+        self.rebuilding.set(true);
+        *self.replica.borrow_mut() = new_replica(&self.query_key);
+        self.nodes.borrow_mut().reconnect(index, BlockDAG::new);
+        let result = self.fetch_tips(index).await;
+        self.rebuilding.set(false);
+        result
+    }
+
+    /// Fetch new transactions from the node at `index`, replicating them
+    /// into the local DAG and queueing their events
+    async fn fetch_tips(&self, index: usize) -> Result<(), JsValue> {
+        let blockdag = self.nodes.borrow().get(index).handle.clone();
+        let start = js_sys::Date::now();
+        let link = self.clone();
+        match BlockDAG::tips_sync(blockdag, move |trans| link.replicate(trans)).await {
+            Ok(_) => {
+                self.nodes
+                    .borrow_mut()
+                    .record_sync(index, js_sys::Date::now() - start);
+                Ok(())
+            }
+            Err(e) => {
+                self.nodes.borrow_mut().record_failure(index);
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Replicate a transaction and queue its events, along with the round
+    /// events only the replica can tell
+    fn replicate(&self, trans: &Transaction) {
+        let round_events = rounds::round_events(
+            trans,
+            self.contract_address,
+            |f, args| {
+                query_replica(
+                    &self.replica,
+                    &self.query_key,
+                    self.contract_address,
+                    f,
+                    args,
+                )
+            },
+            || {
+                self.replica.borrow_mut().replicate(trans.clone());
+            },
+        );
+        if is_contract_call(trans, self.contract_address) {
+            self.events.emit_all(
+                trans.get_hash(),
+                event_from_transaction(trans, self.contract_address)
+                    .into_iter()
+                    .chain(round_events)
+                    .collect(),
+            );
+        }
+    }
+
+    /// Execute a contract call signed with `keypair`, on the active node or
+    /// the next one that can be reached. A node whose tips weren't the last
+    /// synced is synced first, and is passed over if that fails, before
+    /// anything is sent. Once the call is sent it isn't sent again, since a
+    /// node that fails after that may already have taken it.
+    async fn execute(
+        &self,
+        keypair: &RwLock<Option<Rc<EdDSAKeyPair>>>,
+        func_name: &str,
+        args: &[ContractValue],
+    ) -> Result<(Option<ContractValue>, Option<Transaction>), JsValue> {
+        let keypair = keypair
+            .read()
+            .expect("Failed to acquire lock")
+            .clone()
+            .expect("Failed to call spawn_player before applying input.");
+        let address = get_address(&get_public_key(&keypair));
+        let order = self.nodes.borrow().attempt_order();
+        let mut last_err = JsValue::from_str("No rustdag nodes configured.");
+
+        for index in order {
+            if !self.nodes.borrow().is_synced(index) {
+                if let Err(e) = self.sync_tips(index).await {
+                    let url = self.nodes.borrow().get(index).url.clone();
+                    warn!(
+                        "Failed to reach {} for {}, trying next node",
+                        url, func_name
+                    );
+                    last_err = e;
+                    continue;
+                }
+            }
+            let (url, inner, retarget) = {
+                let mut nodes = self.nodes.borrow_mut();
+                let retarget = nodes.point_at(index, address);
+                let node = nodes.get(index);
+                (node.url.clone(), node.handle.clone_inner(), retarget)
+            };
+            let start = js_sys::Date::now();
+
+            let mut remote = inner.lock().await;
+            if retarget {
+                // Tip selection should include our own transactions
+                remote.update_address(address);
+            }
+            let result = remote
+                .execute_contract(&keypair, self.contract_address, func_name, args)
+                .await;
+            return match result {
+                Ok(result) => {
+                    self.nodes
+                        .borrow_mut()
+                        .record_success(index, js_sys::Date::now() - start);
+                    Ok(result)
+                }
+                Err(e) => {
+                    warn!("{} failed on {}", func_name, url);
+                    self.nodes.borrow_mut().record_failure(index);
+                    Err(e.into())
+                }
+            };
+        }
+
+        Err(last_err)
+    }
+
+    /// Execute a contract call and replicate its transaction locally, so
+    /// view functions see it straight away
+    async fn execute_and_replicate(
+        &self,
+        keypair: &RwLock<Option<Rc<EdDSAKeyPair>>>,
+        func_name: &str,
+        args: &[ContractValue],
+    ) -> Result<(Option<ContractValue>, Transaction), JsValue> {
+        let (value, trans) = self.execute(keypair, func_name, args).await?;
+        let trans = trans.unwrap_or_else(|| {
+            panic!(
+                "{} contract execution failed to produce a transaction.",
+                func_name
+            )
+        });
+        self.replica.borrow_mut().replicate(trans.clone());
+        Ok((value, trans))
+    }
+}
+
 #[wasm_bindgen]
 pub struct PlayerData {
     x: i32,
     y: i32,
     heading: u32,
+    name: String,
+    color: u32,
 }
 
 impl PlayerData {
@@ -284,6 +1050,16 @@ impl PlayerData {
             x,
             y,
             heading: heading as u32,
+            name: String::new(),
+            color: 0,
+        }
+    }
+
+    pub fn with_profile(self, name: String, color: u32) -> Self {
+        PlayerData {
+            name,
+            color,
+            ..self
         }
     }
 }
@@ -301,24 +1077,224 @@ impl PlayerData {
     pub fn heading(&self) -> JsValue {
         self.heading.into()
     }
+
+    /// Name the player chose, or an empty string
+    pub fn name(&self) -> JsValue {
+        self.name.clone().into()
+    }
+
+    /// Color as 0xRRGGBB
+    pub fn color(&self) -> JsValue {
+        self.color.into()
+    }
 }
 
 #[wasm_bindgen]
-#[derive(PartialEq, Debug)]
+pub struct GameSummary {
+    id: u64,
+    round: u64,
+    phase: u32,
+    round_length: u64,
+    players: u64,
+    max_players: u64,
+    team_count: u64,
+}
+
+#[wasm_bindgen]
+impl GameSummary {
+    pub fn id(&self) -> JsValue {
+        self.id.to_string().into()
+    }
+
+    pub fn round(&self) -> JsValue {
+        (self.round as u32).into()
+    }
+
+    pub fn is_lobby(&self) -> bool {
+        self.phase == 0
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.phase == 1
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.phase == 2
+    }
+
+    /// Round length in ticks, or 0 if rounds never run out
+    pub fn round_length(&self) -> JsValue {
+        (self.round_length as u32).into()
+    }
+
+    /// Players alive in the game
+    pub fn players(&self) -> JsValue {
+        (self.players as u32).into()
+    }
+
+    pub fn max_players(&self) -> JsValue {
+        (self.max_players as u32).into()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.players >= self.max_players
+    }
+
+    /// Number of teams, or 0 if it is every player for themselves
+    pub fn team_count(&self) -> JsValue {
+        (self.team_count as u32).into()
+    }
+}
+
+#[wasm_bindgen]
+pub struct RoundInfo {
+    round: u64,
+    phase: u32,
+    length: u64,
+    tick: u64,
+    winner: u64,
+    winning_team: u64,
+}
+
+#[wasm_bindgen]
+impl RoundInfo {
+    pub fn round(&self) -> JsValue {
+        (self.round as u32).into()
+    }
+
+    pub fn is_lobby(&self) -> bool {
+        self.phase == 0
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.phase == 1
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.phase == 2
+    }
+
+    /// Round length in ticks, or 0 if the round never runs out
+    pub fn length(&self) -> JsValue {
+        (self.length as u32).into()
+    }
+
+    pub fn tick(&self) -> JsValue {
+        (self.tick as u32).into()
+    }
+
+    /// Id of the last round's winner, or undefined if there was none
+    pub fn winner(&self) -> JsValue {
+        if self.winner == 0 {
+            JsValue::UNDEFINED
+        } else {
+            self.winner.to_string().into()
+        }
+    }
+
+    /// Team of the last round's winner in a team game, or undefined
+    pub fn winning_team(&self) -> JsValue {
+        if self.winning_team == 0 {
+            JsValue::UNDEFINED
+        } else {
+            (self.winning_team as u32).into()
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub struct LeaderboardRow {
+    id: u64,
+    area: u64,
+    kills: u64,
+    survival: u64,
+    team: u64,
+}
+
+#[wasm_bindgen]
+impl LeaderboardRow {
+    pub fn id(&self) -> JsValue {
+        self.id.to_string().into()
+    }
+
+    /// Claimed grid cells
+    pub fn area(&self) -> JsValue {
+        (self.area as u32).into()
+    }
+
+    pub fn kills(&self) -> JsValue {
+        (self.kills as u32).into()
+    }
+
+    /// Game ticks survived in the current life
+    pub fn survival(&self) -> JsValue {
+        (self.survival as u32).into()
+    }
+
+    /// Team the player is on, or 0 if the game has no teams
+    pub fn team(&self) -> JsValue {
+        (self.team as u32).into()
+    }
+}
+
+#[wasm_bindgen]
+pub struct TeamRow {
+    team: u64,
+    area: u64,
+    kills: u64,
+    players: u64,
+}
+
+#[wasm_bindgen]
+impl TeamRow {
+    pub fn team(&self) -> JsValue {
+        (self.team as u32).into()
+    }
+
+    /// Grid cells claimed by the team's live players
+    pub fn area(&self) -> JsValue {
+        (self.area as u32).into()
+    }
+
+    pub fn kills(&self) -> JsValue {
+        (self.kills as u32).into()
+    }
+
+    /// Live players on the team
+    pub fn players(&self) -> JsValue {
+        (self.players as u32).into()
+    }
+}
+
+#[wasm_bindgen]
+#[derive(PartialEq, Debug, Clone)]
 pub struct Event {
     id: String,
     x: i32,
     y: i32,
     heading: u32,
     kind: EventKind,
+    /// Game of a round event. Player events are matched to a game through
+    /// the player instead.
+    game: u64,
+    /// Profile of the player on spawn and profile events, filled in from the
+    /// replica when the event is taken
+    name: String,
+    color: u32,
     timestamp: u64,
 }
 
 #[wasm_bindgen]
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum EventKind {
     Spawn,
     Input,
+    RoundStart,
+    RoundEnd,
+    Profile,
+    Boost,
+    Paused,
+    Resumed,
 }
 
 impl Event {
@@ -329,6 +1305,9 @@ impl Event {
             y,
             heading: 0,
             kind: EventKind::Spawn,
+            game: 0,
+            name: String::new(),
+            color: 0,
             timestamp,
         }
     }
@@ -340,9 +1319,89 @@ impl Event {
             y: 0,
             heading,
             kind: EventKind::Input,
+            game: 0,
+            name: String::new(),
+            color: 0,
+            timestamp,
+        }
+    }
+
+    /// A change to the round or pause of `game`, made by a call from `id`
+    pub fn round(kind: EventKind, id: String, game: u64, timestamp: u64) -> Self {
+        Event {
+            id,
+            x: 0,
+            y: 0,
+            heading: 0,
+            kind,
+            game,
+            name: String::new(),
+            color: 0,
             timestamp,
         }
     }
+
+    /// A player started a speed boost. Boosts still cooling down do nothing,
+    /// so check `get_player_boost_end` for the outcome.
+    pub fn boost(id: String, timestamp: u64) -> Self {
+        Event {
+            id,
+            x: 0,
+            y: 0,
+            heading: 0,
+            kind: EventKind::Boost,
+            game: 0,
+            name: String::new(),
+            color: 0,
+            timestamp,
+        }
+    }
+
+    /// A player changed their name or color
+    pub fn profile(id: String, timestamp: u64) -> Self {
+        Event {
+            id,
+            x: 0,
+            y: 0,
+            heading: 0,
+            kind: EventKind::Profile,
+            game: 0,
+            name: String::new(),
+            color: 0,
+            timestamp,
+        }
+    }
+
+    /// Address of the player, or of the sender of a round event
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn kind(&self) -> EventKind {
+        self.kind
+    }
+
+    /// Spawn point, offset so the top left corner of the grid is (0, 0)
+    pub fn spawn_point(&self) -> Option<(i32, i32)> {
+        if self.is_spawn() {
+            Some((self.x, self.y))
+        } else {
+            None
+        }
+    }
+
+    /// Heading as passed to `apply_input`
+    pub fn input_heading(&self) -> Option<u32> {
+        if self.is_input() {
+            Some(self.heading)
+        } else {
+            None
+        }
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
 }
 
 #[wasm_bindgen]
@@ -355,6 +1414,46 @@ impl Event {
         self.kind == EventKind::Input
     }
 
+    pub fn is_round_start(&self) -> bool {
+        self.kind == EventKind::RoundStart
+    }
+
+    pub fn is_round_end(&self) -> bool {
+        self.kind == EventKind::RoundEnd
+    }
+
+    pub fn is_profile(&self) -> bool {
+        self.kind == EventKind::Profile
+    }
+
+    pub fn is_boost(&self) -> bool {
+        self.kind == EventKind::Boost
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.kind == EventKind::Paused
+    }
+
+    pub fn is_resumed(&self) -> bool {
+        self.kind == EventKind::Resumed
+    }
+
+    /// Player's name on spawn and profile events
+    pub fn get_name(&self) -> JsValue {
+        match self.kind {
+            EventKind::Spawn | EventKind::Profile => self.name.clone().into(),
+            _ => JsValue::UNDEFINED,
+        }
+    }
+
+    /// Player's color as 0xRRGGBB on spawn and profile events
+    pub fn get_color(&self) -> JsValue {
+        match self.kind {
+            EventKind::Spawn | EventKind::Profile => self.color.into(),
+            _ => JsValue::UNDEFINED,
+        }
+    }
+
     pub fn get_input_heading(&self) -> JsValue {
         if self.is_input() {
             self.heading.into()
@@ -390,6 +1489,182 @@ impl Event {
 
 // UTIL
 
+/// Contract functions that only read state, and can run on the local replica
+const READ_ONLY_FUNCTIONS: &[&str] = &[
+    "get_start_time",
+    "get_current_game_tick",
+    "get_game_count",
+    "get_player_game",
+    "get_max_players",
+    "get_queue_position",
+    "get_player_name_len",
+    "get_player_name_word",
+    "get_player_color",
+    "get_round",
+    "get_phase",
+    "get_round_length",
+    "get_winner",
+    "get_player_x",
+    "get_player_y",
+    "get_player_heading",
+    "get_player_area",
+    "get_player_kills",
+    "get_player_survival",
+    "get_player_spawn_time",
+    "get_player_alive",
+    "get_player_boost_end",
+    "get_player_boost_ready",
+    "get_player_trail_len",
+    "get_player_trail_cell",
+    "get_team_count",
+    "get_player_team",
+    "get_team_area",
+    "get_team_kills",
+    "get_team_players",
+    "get_team_leaderboard",
+    "get_winning_team",
+    "get_cell_owner",
+    "get_player_count",
+    "get_live_player",
+    "get_leaderboard_size",
+    "get_leaderboard",
+    "get_owner",
+    "get_paused",
+    "get_paused_ms",
+    "get_player_banned",
+];
+
+fn is_read_only(func_name: &str) -> bool {
+    READ_ONLY_FUNCTIONS.contains(&func_name)
+}
+
+fn is_contract_call(trans: &Transaction, contract_address: u64) -> bool {
+    match trans.get_data() {
+        TransactionData::ExecContract { contract, .. } => *contract == contract_address,
+        _ => false,
+    }
+}
+
+/// Decode a transaction into the event it represents, if it is a call to the
+/// p2pio contract at `contract_address`
+pub fn event_from_transaction(trans: &Transaction, contract_address: u64) -> Option<Event> {
+    match trans.get_data() {
+        TransactionData::ExecContract {
+            func_name,
+            args,
+            contract,
+        } if *contract == contract_address => {
+            event_from_call(trans.get_address(), func_name, args, trans.get_timestamp())
+        }
+        _ => None,
+    }
+}
+
+/// Decode a call to the p2pio contract into the event it represents, if any.
+/// Round and pause calls have none of their own, since only the replica
+/// knows whether they did anything (see `rounds`).
+pub(crate) fn event_from_call(
+    address: u64,
+    func_name: &str,
+    args: &[ContractValue],
+    timestamp: u64,
+) -> Option<Event> {
+    match func_name {
+        "spawn_player" => match args {
+            [x @ ContractValue::U64(_), y @ ContractValue::U64(_)] => Some(Event::spawn(
+                address.to_string(),
+                contract_val_to_i32(*x),
+                contract_val_to_i32(*y),
+                timestamp,
+            )),
+            _ => malformed_call(func_name, args, 2),
+        },
+        "apply_input" => match args {
+            [heading @ ContractValue::U64(_)] => Some(Event::input(
+                address.to_string(),
+                unwrap_contract_u64(*heading) as u32,
+                timestamp,
+            )),
+            _ => malformed_call(func_name, args, 1),
+        },
+        "set_profile" => Some(Event::profile(address.to_string(), timestamp)),
+        "activate_boost" => Some(Event::boost(address.to_string(), timestamp)),
+        _ => None,
+    }
+}
+
+/// Reject with the `ContractError` code an entry point returned, unless it
+/// returned 0 for applied
+fn check_code(func_name: &str, code: Option<ContractValue>) -> Result<(), JsValue> {
+    match code.map(unwrap_contract_u64) {
+        Some(0) => Ok(()),
+        Some(code) => Err(JsValue::from_str(&format!(
+            "{} returned error code {}",
+            func_name, code
+        ))),
+        None => Err(JsValue::from_str(&format!(
+            "{} returned no code",
+            func_name
+        ))),
+    }
+}
+
+/// Calls come from anyone on the network, so one with the wrong arguments
+/// is logged and skipped rather than taking the client down
+fn malformed_call(func_name: &str, args: &[ContractValue], expected: usize) -> Option<Event> {
+    warn!(
+        "Ignoring {} call with arguments {:?}, expected {} integers",
+        func_name, args, expected
+    );
+    None
+}
+
+/// Longest name the contract accepts, in bytes
+const MAX_NAME_LEN: usize = 24;
+/// Names are packed little endian into this many contract values
+const NAME_WORDS: usize = 3;
+
+/// Arguments for the contract's `set_profile`. The contract does the full
+/// validation, this only catches names that can't be packed.
+fn profile_args(name: &str, color: Option<u32>) -> Result<Vec<ContractValue>, String> {
+    let bytes = name.as_bytes();
+    if bytes.is_empty() || bytes.len() > MAX_NAME_LEN {
+        return Err(format!("Names must be 1 to {} bytes long", MAX_NAME_LEN));
+    }
+    let mut args = vec![ContractValue::U64(bytes.len() as u64)];
+    let mut words = [0u64; NAME_WORDS];
+    for (i, chunk) in bytes.chunks(8).enumerate() {
+        let mut word = [0; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        words[i] = u64::from_le_bytes(word);
+    }
+    args.extend(words.iter().map(|w| ContractValue::U64(*w)));
+    // Anything past 0xFFFFFF means the default color
+    args.push(ContractValue::U64(color.map_or(u64::MAX, u64::from)));
+    Ok(args)
+}
+
+/// Read a player's name and color through the contract getters
+fn read_profile<F>(query: F, id: u64) -> Result<(String, u32), JsValue>
+where
+    F: Fn(&str, &[ContractValue]) -> Result<ContractValue, JsValue>,
+{
+    let id = ContractValue::U64(id);
+    let len = unwrap_contract_u64(query("get_player_name_len", &[id])?) as usize;
+    let mut bytes = Vec::with_capacity(NAME_WORDS * 8);
+    for word in 0..NAME_WORDS {
+        let value = query(
+            "get_player_name_word",
+            &[id, ContractValue::U64(word as u64)],
+        )?;
+        bytes.extend_from_slice(&unwrap_contract_u64(value).to_le_bytes());
+    }
+    bytes.truncate(len.min(MAX_NAME_LEN));
+    let name = String::from_utf8_lossy(&bytes).into_owned();
+    let color = unwrap_contract_u64(query("get_player_color", &[id])?) as u32;
+    Ok((name, color))
+}
+
 fn unwrap_contract_u64(cv: ContractValue) -> u64 {
     if let ContractValue::U64(x) = cv {
         x
@@ -404,7 +1679,97 @@ fn contract_val_to_i32(cv: ContractValue) -> i32 {
     (i128::from(unwrap_contract_u64(cv)) - i128::from(u32::MAX)) as i32
 }
 
+/// Game or player id passed from JavaScript as a decimal string
+fn parse_id(id: &str) -> Result<u64, JsValue> {
+    id.parse()
+        .map_err(|_| JsValue::from_str(&format!("Failed to parse id {}.", id)))
+}
+
+fn js_to_contract_val(value: &JsValue) -> Result<ContractValue, JsValue> {
+    if let Some(n) = value.as_f64() {
+        if n >= 0.0 && n.fract() == 0.0 {
+            return Ok(ContractValue::U64(n as u64));
+        }
+    } else if let Some(s) = value.as_string() {
+        if let Ok(n) = s.parse() {
+            return Ok(ContractValue::U64(n));
+        }
+    }
+    Err(JsValue::from_str(&format!(
+        "Expected a non-negative integer argument, got {:?}",
+        value
+    )))
+}
+
+/// Largest integer a JavaScript number holds exactly
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+/// A contract value as the signed integer the contract returned, if a
+/// JavaScript number can hold it exactly
+fn safe_integer(value: ContractValue) -> Option<i64> {
+    let n = unwrap_contract_u64(value) as i64;
+    if -MAX_SAFE_INTEGER <= n && n <= MAX_SAFE_INTEGER {
+        Some(n)
+    } else {
+        None
+    }
+}
+
 //Convert our signed numbers centered around (0, 0) to unsigned u64 centered around (u32::MAX, u32::MAX)
 fn i32_to_contract_val(x: i32) -> ContractValue {
     ContractValue::U64((i64::from(x) + i64::from(u32::MAX)) as u64)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_results_fit_in_numbers() {
+        assert_eq!(safe_integer(ContractValue::U64(42)), Some(42));
+        assert_eq!(safe_integer(ContractValue::U64(-7i64 as u64)), Some(-7));
+        assert_eq!(
+            safe_integer(ContractValue::U64(MAX_SAFE_INTEGER as u64)),
+            Some(MAX_SAFE_INTEGER)
+        );
+        // Addresses need all 64 bits, so stay strings
+        assert_eq!(safe_integer(ContractValue::U64(1 << 53)), None);
+        assert_eq!(safe_integer(ContractValue::U64(u64::MAX - (1 << 60))), None);
+    }
+
+    #[test]
+    fn test_profile_args_round_trip() {
+        let args = profile_args("Paper Tiger", Some(0x12_3456)).unwrap();
+        assert_eq!(args.len(), 2 + NAME_WORDS);
+        assert_eq!(unwrap_contract_u64(args[0]), 11);
+        assert_eq!(unwrap_contract_u64(args[NAME_WORDS + 1]), 0x12_3456);
+
+        // Answer the getters the way the contract would after `set_profile`
+        let query = |func_name: &str, query_args: &[ContractValue]| match func_name {
+            "get_player_name_len" => Ok(args[0]),
+            "get_player_name_word" => Ok(args[1 + unwrap_contract_u64(query_args[1]) as usize]),
+            "get_player_color" => Ok(args[NAME_WORDS + 1]),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            read_profile(query, 7).unwrap(),
+            ("Paper Tiger".to_string(), 0x12_3456)
+        );
+    }
+
+    #[test]
+    fn test_malformed_calls_are_skipped() {
+        let origin = u64::from(u32::MAX);
+        assert!(event_from_call(7, "spawn_player", &[ContractValue::U64(origin)], 1000).is_none());
+        assert!(event_from_call(7, "apply_input", &[], 1000).is_none());
+        assert!(event_from_call(7, "apply_input", &[ContractValue::U64(1)], 1000).is_some());
+    }
+
+    #[test]
+    fn test_profile_args_default_color() {
+        let args = profile_args("a", None).unwrap();
+        assert_eq!(unwrap_contract_u64(args[NAME_WORDS + 1]), u64::MAX);
+        assert!(profile_args("", None).is_err());
+        assert!(profile_args(&"a".repeat(MAX_NAME_LEN + 1), None).is_err());
+    }
+}
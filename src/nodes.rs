@@ -0,0 +1,220 @@
+/// Number of consecutive failures after which a node is considered down
+const MAX_FAILURES: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NodeHealth {
+    pub failures: u32,
+    pub latency_ms: Option<f64>,
+}
+
+impl NodeHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.failures < MAX_FAILURES
+    }
+}
+
+pub struct Node<T> {
+    pub url: String,
+    pub handle: T,
+    pub health: NodeHealth,
+    /// Address the node's tip selection was last pointed at
+    pub address: Option<u64>,
+}
+
+/// The set of rustdag nodes a `Context` can talk to.
+///
+/// One node is active at a time. Requests that can't reach it go to the
+/// other nodes, healthy ones first and fastest first, and the first node to
+/// succeed becomes the active one.
+pub struct NodePool<T> {
+    nodes: Vec<Node<T>>,
+    active: usize,
+    /// Node whose tips were synced last. Any other node's are out of date.
+    synced: Option<usize>,
+}
+
+impl<T> NodePool<T> {
+    pub fn new<F: Fn(String) -> T>(urls: Vec<String>, connect: F) -> Self {
+        assert!(!urls.is_empty(), "At least one node url is required.");
+        NodePool {
+            nodes: urls
+                .into_iter()
+                .map(|url| Node {
+                    handle: connect(url.clone()),
+                    url,
+                    health: NodeHealth::default(),
+                    address: None,
+                })
+                .collect(),
+            active: 0,
+            synced: None,
+        }
+    }
+
+    pub fn active(&self) -> &Node<T> {
+        &self.nodes[self.active]
+    }
+
+    pub fn get(&self, index: usize) -> &Node<T> {
+        &self.nodes[index]
+    }
+
+    /// Order in which nodes should be tried for a request, starting with the
+    /// active node
+    pub fn attempt_order(&self) -> Vec<usize> {
+        let mut rest = (0..self.nodes.len())
+            .filter(|i| *i != self.active)
+            .collect::<Vec<_>>();
+        rest.sort_by(|a, b| {
+            let a = &self.nodes[*a].health;
+            let b = &self.nodes[*b].health;
+            b.is_healthy()
+                .cmp(&a.is_healthy())
+                .then(a.failures.cmp(&b.failures))
+                .then(
+                    a.latency_ms
+                        .unwrap_or(std::f64::MAX)
+                        .partial_cmp(&b.latency_ms.unwrap_or(std::f64::MAX))
+                        .unwrap_or(std::cmp::Ordering::Equal),
+                )
+        });
+        let mut order = vec![self.active];
+        order.append(&mut rest);
+        order
+    }
+
+    pub fn record_success(&mut self, index: usize, latency_ms: f64) {
+        let health = &mut self.nodes[index].health;
+        health.failures = 0;
+        // Smooth latency so a single slow request doesn't reorder the pool
+        health.latency_ms = Some(match health.latency_ms {
+            Some(prev) => prev * 0.8 + latency_ms * 0.2,
+            None => latency_ms,
+        });
+        self.active = index;
+    }
+
+    /// Whether the node's tips are the last ones synced
+    pub fn is_synced(&self, index: usize) -> bool {
+        self.synced == Some(index)
+    }
+
+    pub fn record_sync(&mut self, index: usize, latency_ms: f64) {
+        self.record_success(index, latency_ms);
+        self.synced = Some(index);
+    }
+
+    /// Point the node's tip selection at `address`. Returns false if it
+    /// already was, so the node needn't be told again.
+    pub fn point_at(&mut self, index: usize, address: u64) -> bool {
+        let node = &mut self.nodes[index];
+        if node.address == Some(address) {
+            return false;
+        }
+        node.address = Some(address);
+        true
+    }
+
+    /// Replace the node's handle with a new connection, which has fetched
+    /// nothing yet and isn't pointed at any address
+    pub fn reconnect<F: Fn(String) -> T>(&mut self, index: usize, connect: F) {
+        let node = &mut self.nodes[index];
+        node.handle = connect(node.url.clone());
+        node.address = None;
+        if self.synced == Some(index) {
+            self.synced = None;
+        }
+    }
+
+    pub fn record_failure(&mut self, index: usize) {
+        self.nodes[index].health.failures += 1;
+        if index == self.active && !self.nodes[index].health.is_healthy() {
+            if let Some(next) = self.attempt_order().into_iter().nth(1) {
+                self.active = next;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(n: usize) -> NodePool<usize> {
+        NodePool::new(
+            (0..n).map(|i| format!("http://node{}:8090", i)).collect(),
+            |url| url.len(),
+        )
+    }
+
+    #[test]
+    fn test_starts_on_first_node() {
+        let pool = pool(3);
+        assert_eq!(pool.active().url, "http://node0:8090");
+        assert_eq!(pool.attempt_order(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_retry_prefers_fast_healthy_nodes() {
+        let mut pool = pool(3);
+        pool.record_success(2, 10.0);
+        pool.record_success(1, 50.0);
+        pool.record_success(0, 30.0);
+        assert_eq!(pool.attempt_order(), vec![0, 2, 1]);
+
+        for _ in 0..MAX_FAILURES {
+            pool.record_failure(2);
+        }
+        assert_eq!(pool.attempt_order(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_failover_switches_active_node() {
+        let mut pool = pool(2);
+        pool.record_failure(0);
+        assert_eq!(pool.active().url, "http://node0:8090");
+        for _ in 1..MAX_FAILURES {
+            pool.record_failure(0);
+        }
+        assert_eq!(pool.active().url, "http://node1:8090");
+        assert!(!pool.get(0).health.is_healthy());
+
+        // A success on the retried node makes it the active one
+        pool.record_success(0, 5.0);
+        assert_eq!(pool.active().url, "http://node0:8090");
+        assert!(pool.get(0).health.is_healthy());
+    }
+
+    #[test]
+    fn test_only_the_last_synced_node_is_current() {
+        let mut pool = pool(2);
+        assert!(!pool.is_synced(0));
+        pool.record_sync(1, 5.0);
+        assert!(pool.is_synced(1));
+        assert_eq!(pool.active().url, "http://node1:8090");
+        pool.record_sync(0, 5.0);
+        assert!(!pool.is_synced(1));
+    }
+
+    #[test]
+    fn test_nodes_are_pointed_at_each_address_once() {
+        let mut pool = pool(2);
+        assert!(pool.point_at(0, 7));
+        assert!(!pool.point_at(0, 7));
+        assert!(pool.point_at(1, 7));
+        assert!(pool.point_at(0, 8));
+        assert!(pool.point_at(0, 7));
+    }
+
+    #[test]
+    fn test_reconnecting_forgets_sync_and_address() {
+        let mut pool = pool(2);
+        pool.record_sync(1, 5.0);
+        pool.point_at(1, 7);
+        pool.reconnect(1, |url| url.len() + 1);
+        assert_eq!(pool.get(1).handle, "http://node1:8090".len() + 1);
+        assert!(!pool.is_synced(1));
+        assert!(pool.point_at(1, 7));
+        assert_eq!(pool.active().url, "http://node1:8090");
+    }
+}
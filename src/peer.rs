@@ -0,0 +1,219 @@
+use wasm_bindgen::prelude::*;
+
+use rustdag_lib::dag::contract::{source::ContractSource, ContractValue};
+use rustdag_lib::security::keys::eddsa::{get_address, get_public_key, new_key_pair, EdDSAKeyPair};
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver};
+
+use crate::gossip::{local_dag, GossipNode, LocalNode};
+use crate::rounds::{round_call_game, RoundStatus};
+use crate::rtc::RtcTransport;
+use crate::stream::EventSink;
+use crate::{
+    event_from_transaction, i32_to_contract_val, profile_args, read_profile, unwrap_contract_u64,
+    Event, EventKind, PlayerData,
+};
+
+/// A game session that runs its own DAG in the browser and gossips
+/// transactions directly to other players, with no rustdag node involved.
+#[wasm_bindgen]
+pub struct PeerContext {
+    node: RefCell<Box<dyn LocalNode>>,
+    keypair: EdDSAKeyPair,
+    events: EventSink,
+    event_receiver: Receiver<Event>,
+    contract_address: Cell<u64>,
+}
+
+#[wasm_bindgen]
+impl PeerContext {
+    #[wasm_bindgen(constructor)]
+    pub fn new(signalling_url: String, contract_address: String) -> Result<PeerContext, JsValue> {
+        let keypair = new_key_pair();
+        let dag = local_dag(get_address(&get_public_key(&keypair)));
+        let transport = RtcTransport::connect(&signalling_url)?;
+        let (send, recv) = channel();
+
+        Ok(PeerContext {
+            node: RefCell::from(Box::new(GossipNode::new(dag, transport)) as Box<dyn LocalNode>),
+            keypair,
+            events: EventSink::new(send),
+            event_receiver: recv,
+            contract_address: Cell::from(
+                contract_address
+                    .parse()
+                    .expect("Failed to parse contract address."),
+            ),
+        })
+    }
+
+    /// Deploy the contract from wasm bytes and play on it. Returns the new
+    /// contract address.
+    pub fn deploy(&self, contract_wasm: Vec<u8>) -> Result<String, JsValue> {
+        let contract_key = new_key_pair();
+        self.node
+            .borrow_mut()
+            .deploy(&contract_key, ContractSource::with_vec(contract_wasm))
+            .map_err(|e| JsValue::from_str(&e))?;
+
+        let contract_address = get_address(&get_public_key(&contract_key));
+        self.contract_address.set(contract_address);
+        Ok(contract_address.to_string())
+    }
+
+    pub fn get_address(&self) -> String {
+        get_address(&get_public_key(&self.keypair)).to_string()
+    }
+
+    /// Commit transactions gossiped by peers and queue their events
+    pub fn sync(&self) {
+        let contract_address = self.contract_address.get();
+        // Round events compare where a game stood before the batch, or after
+        // the last round call in it, with the state each round call left
+        let mut rounds = self.round_statuses();
+        self.node.borrow_mut().poll_each(&mut |node, trans| {
+            let mut events = event_from_transaction(&trans, contract_address)
+                .into_iter()
+                .collect::<Vec<_>>();
+            if let Some(game) = round_call_game(&trans, contract_address) {
+                let node = RefCell::from(node);
+                let after = RoundStatus::read(
+                    |f, args| {
+                        query_node(
+                            &mut **node.borrow_mut(),
+                            &self.keypair,
+                            contract_address,
+                            f,
+                            args,
+                        )
+                    },
+                    game,
+                );
+                if let (Some(before), Ok(after)) = (rounds.get(&game).copied(), after) {
+                    events.extend(before.events_to(
+                        &after,
+                        trans.get_address(),
+                        game,
+                        trans.get_timestamp(),
+                    ));
+                    rounds.insert(game, after);
+                }
+            }
+            self.events.emit_all(trans.get_hash(), events);
+        });
+    }
+
+    pub fn spawn_player(&self, x: i32, y: i32) -> Result<(), JsValue> {
+        self.execute(
+            "spawn_player",
+            &[i32_to_contract_val(x), i32_to_contract_val(y)],
+        )
+    }
+
+    pub fn apply_input(&self, heading: u32) -> Result<(), JsValue> {
+        self.execute("apply_input", &[ContractValue::U64(heading.into())])
+    }
+
+    pub fn activate_boost(&self) -> Result<(), JsValue> {
+        self.execute("activate_boost", &[])
+    }
+
+    pub fn get_player(&self, id: String) -> Result<PlayerData, JsValue> {
+        let id_num = id.parse().expect("Failed to parse id.");
+        let x = self.query("get_player_x", id_num)?;
+        let y = self.query("get_player_y", id_num)?;
+        let heading = self.query("get_player_heading", id_num)?;
+        let (name, color) = read_profile(|f, args| self.query_with(f, args), id_num)?;
+
+        Ok(PlayerData::from_contract(x, y, heading).with_profile(name, color))
+    }
+
+    /// Publish the name and color (as 0xRRGGBB) other players see
+    pub fn set_profile(&self, name: String, color: Option<u32>) -> Result<(), JsValue> {
+        let args = profile_args(&name, color).map_err(|e| JsValue::from_str(&e))?;
+        self.execute("set_profile", &args)
+    }
+
+    pub fn take_events(&self) -> JsValue {
+        self.event_receiver
+            .try_iter()
+            .map(|mut e| {
+                if e.kind == EventKind::Spawn || e.kind == EventKind::Profile {
+                    let id = e.id.parse().expect("Failed to parse id.");
+                    if let Ok((name, color)) = read_profile(|f, args| self.query_with(f, args), id)
+                    {
+                        e.name = name;
+                        e.color = color;
+                    }
+                }
+                JsValue::from(e)
+            })
+            .collect::<js_sys::Array>()
+            .into()
+    }
+}
+
+impl PeerContext {
+    fn execute(&self, func_name: &str, args: &[ContractValue]) -> Result<(), JsValue> {
+        let contract_address = self.contract_address.get();
+        let (_, trans) = self
+            .node
+            .borrow_mut()
+            .execute(&self.keypair, contract_address, func_name, args)
+            .map_err(|e| JsValue::from_str(&e))?;
+
+        self.events.emit(
+            trans.get_hash(),
+            event_from_transaction(&trans, contract_address),
+        );
+        Ok(())
+    }
+
+    /// Where the round of every game in the contract stands
+    fn round_statuses(&self) -> HashMap<u64, RoundStatus> {
+        let count = self
+            .query_with("get_game_count", &[])
+            .map_or(0, unwrap_contract_u64);
+        (0..count)
+            .filter_map(|game| {
+                RoundStatus::read(|f, args| self.query_with(f, args), game)
+                    .ok()
+                    .map(|status| (game, status))
+            })
+            .collect()
+    }
+
+    fn query(&self, func_name: &str, id: u64) -> Result<ContractValue, JsValue> {
+        self.query_with(func_name, &[ContractValue::U64(id)])
+    }
+
+    fn query_with(
+        &self,
+        func_name: &str,
+        args: &[ContractValue],
+    ) -> Result<ContractValue, JsValue> {
+        query_node(
+            &mut **self.node.borrow_mut(),
+            &self.keypair,
+            self.contract_address.get(),
+            func_name,
+            args,
+        )
+    }
+}
+
+/// Run a contract function against `node` without committing it
+fn query_node(
+    node: &mut dyn LocalNode,
+    key: &EdDSAKeyPair,
+    contract_address: u64,
+    func_name: &str,
+    args: &[ContractValue],
+) -> Result<ContractValue, JsValue> {
+    let value = node
+        .query(key, contract_address, func_name, args)
+        .map_err(|e| JsValue::from_str(&e))?;
+    Ok(value.expect("Should return a value"))
+}
@@ -0,0 +1,226 @@
+//! Round and pause events, read off the replica.
+//!
+//! The contract ignores a round or pause call made at the wrong time, so the
+//! call alone doesn't say whether a round started or the game paused.
+//! Instead the game's state is read before and after the transaction is
+//! applied, and events are only raised for what actually changed.
+
+use wasm_bindgen::prelude::*;
+
+use rustdag_lib::dag::contract::ContractValue;
+use rustdag_lib::dag::transaction::{data::TransactionData, Transaction};
+
+use crate::{unwrap_contract_u64, Event, EventKind};
+
+/// Calls that can start or end a game's round, or pause it. Each takes the
+/// game as its only argument.
+const ROUND_CALLS: &[&str] = &[
+    "start_round",
+    "end_round",
+    "open_lobby",
+    "reset_round",
+    "pause_game",
+    "resume_game",
+];
+
+/// `Phase::Running` in the contract
+const RUNNING: u64 = 1;
+
+pub fn is_round_call(func_name: &str) -> bool {
+    ROUND_CALLS.contains(&func_name)
+}
+
+/// Game whose round a transaction may change, if it is a round call
+pub fn round_call_game(trans: &Transaction, contract_address: u64) -> Option<u64> {
+    match trans.get_data() {
+        TransactionData::ExecContract {
+            func_name,
+            args,
+            contract,
+        } if *contract == contract_address && is_round_call(func_name) => match args.as_slice() {
+            [game @ ContractValue::U64(_)] => Some(unwrap_contract_u64(*game)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Where a game's round stands
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RoundStatus {
+    pub round: u64,
+    pub phase: u64,
+    pub paused: bool,
+}
+
+impl RoundStatus {
+    pub fn read<F>(query: F, game: u64) -> Result<Self, JsValue>
+    where
+        F: Fn(&str, &[ContractValue]) -> Result<ContractValue, JsValue>,
+    {
+        let get = |func_name| -> Result<u64, JsValue> {
+            Ok(unwrap_contract_u64(query(
+                func_name,
+                &[ContractValue::U64(game)],
+            )?))
+        };
+        Ok(RoundStatus {
+            round: get("get_round")?,
+            phase: get("get_phase")?,
+            paused: get("get_paused")? != 0,
+        })
+    }
+
+    /// Events for the game going from this status to `after`, credited to
+    /// the call by `address` at `timestamp` that moved it on
+    pub fn events_to(
+        &self,
+        after: &RoundStatus,
+        address: u64,
+        game: u64,
+        timestamp: u64,
+    ) -> Vec<Event> {
+        let new_round = after.round != self.round;
+        let mut kinds = Vec::new();
+        if self.phase == RUNNING && (after.phase != RUNNING || new_round) {
+            kinds.push(EventKind::RoundEnd);
+        }
+        if after.phase == RUNNING && (self.phase != RUNNING || new_round) {
+            kinds.push(EventKind::RoundStart);
+        }
+        match (self.paused, after.paused) {
+            (false, true) => kinds.push(EventKind::Paused),
+            (true, false) => kinds.push(EventKind::Resumed),
+            _ => (),
+        }
+        kinds
+            .into_iter()
+            .map(|kind| Event::round(kind, address.to_string(), game, timestamp))
+            .collect()
+    }
+}
+
+/// Apply a transaction with `apply`, and return the round events for how it
+/// changed its game
+pub fn round_events<F, A>(
+    trans: &Transaction,
+    contract_address: u64,
+    query: F,
+    apply: A,
+) -> Vec<Event>
+where
+    F: Fn(&str, &[ContractValue]) -> Result<ContractValue, JsValue>,
+    A: FnOnce(),
+{
+    let game = match round_call_game(trans, contract_address) {
+        Some(game) => game,
+        None => {
+            apply();
+            return Vec::new();
+        }
+    };
+    let before = RoundStatus::read(&query, game);
+    apply();
+    match (before, RoundStatus::read(&query, game)) {
+        (Ok(before), Ok(after)) => {
+            before.events_to(&after, trans.get_address(), game, trans.get_timestamp())
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOBBY: u64 = 0;
+    const FINISHED: u64 = 2;
+
+    fn kinds(before: RoundStatus, after: RoundStatus) -> Vec<EventKind> {
+        before
+            .events_to(&after, 7, 3, 1000)
+            .into_iter()
+            .map(|e| e.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_round_events_follow_the_phase() {
+        let lobby = RoundStatus {
+            round: 1,
+            phase: LOBBY,
+            paused: false,
+        };
+        let running = RoundStatus {
+            phase: RUNNING,
+            ..lobby
+        };
+        let finished = RoundStatus {
+            phase: FINISHED,
+            ..lobby
+        };
+
+        assert_eq!(kinds(lobby, running), vec![EventKind::RoundStart]);
+        assert_eq!(kinds(running, finished), vec![EventKind::RoundEnd]);
+        assert_eq!(kinds(finished, RoundStatus { round: 2, ..lobby }), vec![]);
+        // A reset ends one round and starts the next in a single call
+        assert_eq!(
+            kinds(
+                running,
+                RoundStatus {
+                    round: 2,
+                    ..running
+                }
+            ),
+            vec![EventKind::RoundEnd, EventKind::RoundStart]
+        );
+        // Ignored by the contract, so nothing changed
+        assert_eq!(kinds(running, running), vec![]);
+        assert_eq!(kinds(lobby, lobby), vec![]);
+    }
+
+    #[test]
+    fn test_pause_events_follow_the_replica() {
+        let running = RoundStatus {
+            round: 1,
+            phase: RUNNING,
+            paused: false,
+        };
+        let paused = RoundStatus {
+            paused: true,
+            ..running
+        };
+
+        assert_eq!(kinds(running, paused), vec![EventKind::Paused]);
+        assert_eq!(kinds(paused, running), vec![EventKind::Resumed]);
+        // Pausing a paused game is ignored by the contract
+        assert_eq!(kinds(paused, paused), vec![]);
+        let lobby = RoundStatus {
+            phase: LOBBY,
+            ..paused
+        };
+        assert_eq!(kinds(lobby, paused), vec![EventKind::RoundStart]);
+    }
+
+    #[test]
+    fn test_round_events_are_credited_to_the_call() {
+        let lobby = RoundStatus {
+            round: 1,
+            phase: LOBBY,
+            paused: false,
+        };
+        let running = RoundStatus {
+            phase: RUNNING,
+            ..lobby
+        };
+        assert_eq!(
+            lobby.events_to(&running, 7, 3, 1000),
+            vec![Event::round(
+                EventKind::RoundStart,
+                "7".to_string(),
+                3,
+                1000
+            )]
+        );
+    }
+}
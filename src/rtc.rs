@@ -0,0 +1,342 @@
+//! WebRTC data channel transport for transaction gossip.
+//!
+//! Peers find each other through a signalling server reachable over a
+//! WebSocket. The server relays JSON `Signal` messages: it greets every new
+//! peer with its id and the ids already present, and forwards offers, answers
+//! and ICE candidates to the peer named in `to`. The joining peer sends an
+//! offer to everyone already present.
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    MessageEvent, RtcDataChannel, RtcDataChannelEvent, RtcDataChannelType, RtcIceCandidateInit,
+    RtcPeerConnection, RtcPeerConnectionIceEvent, RtcSdpType, RtcSessionDescriptionInit, WebSocket,
+};
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+
+use log::{info, warn};
+
+use crate::gossip::{PeerId, Transport};
+
+const CHANNEL_LABEL: &str = "p2pio-gossip";
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Signal {
+    Welcome {
+        id: PeerId,
+        peers: Vec<PeerId>,
+    },
+    Offer {
+        from: PeerId,
+        to: PeerId,
+        sdp: String,
+    },
+    Answer {
+        from: PeerId,
+        to: PeerId,
+        sdp: String,
+    },
+    Candidate {
+        from: PeerId,
+        to: PeerId,
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_m_line_index: Option<u16>,
+    },
+}
+
+#[derive(Default)]
+struct RtcState {
+    connections: HashMap<PeerId, RtcPeerConnection>,
+    channels: HashMap<PeerId, RtcDataChannel>,
+    inbox: VecDeque<(PeerId, Vec<u8>)>,
+    new_peers: Vec<PeerId>,
+}
+
+/// Gossip transport over WebRTC data channels, one per remote peer
+pub struct RtcTransport {
+    socket: WebSocket,
+    state: Rc<RefCell<RtcState>>,
+    _on_signal: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl RtcTransport {
+    pub fn connect(signalling_url: &str) -> Result<Self, JsValue> {
+        let socket = WebSocket::new(signalling_url)?;
+        let state = Rc::from(RefCell::from(RtcState::default()));
+
+        let on_signal = {
+            let socket = socket.clone();
+            let state = state.clone();
+            Closure::wrap(Box::new(move |e: MessageEvent| {
+                let frame = match e.data().as_string() {
+                    Some(frame) => frame,
+                    None => return,
+                };
+                match serde_json::from_str(&frame) {
+                    Ok(signal) => handle_signal(&socket, &state, signal),
+                    Err(err) => warn!("Dropping malformed signal: {}", err),
+                }
+            }) as Box<dyn FnMut(MessageEvent)>)
+        };
+        socket.set_onmessage(Some(on_signal.as_ref().unchecked_ref()));
+
+        Ok(RtcTransport {
+            socket,
+            state,
+            _on_signal: on_signal,
+        })
+    }
+}
+
+impl Drop for RtcTransport {
+    fn drop(&mut self) {
+        self.socket.set_onmessage(None);
+        let _ = self.socket.close();
+        for connection in self.state.borrow().connections.values() {
+            connection.close();
+        }
+    }
+}
+
+impl Transport for RtcTransport {
+    fn broadcast(&mut self, message: &[u8]) {
+        let peers = self
+            .state
+            .borrow()
+            .channels
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        for peer in peers {
+            self.send(peer, message);
+        }
+    }
+
+    fn send(&mut self, peer: PeerId, message: &[u8]) {
+        if let Some(channel) = self.state.borrow().channels.get(&peer) {
+            let mut message = message.to_vec();
+            if let Err(e) = channel.send_with_u8_array(&mut message) {
+                warn!("Failed to send to peer {}: {:?}", peer, e);
+            }
+        }
+    }
+
+    fn receive(&mut self) -> Vec<(PeerId, Vec<u8>)> {
+        self.state.borrow_mut().inbox.drain(..).collect()
+    }
+
+    fn take_new_peers(&mut self) -> Vec<PeerId> {
+        self.state.borrow_mut().new_peers.drain(..).collect()
+    }
+}
+
+fn send_signal(socket: &WebSocket, signal: &Signal) {
+    let frame = serde_json::to_string(signal).expect("Failed to serialize signal");
+    if let Err(e) = socket.send_with_str(&frame) {
+        warn!("Failed to send signal: {:?}", e);
+    }
+}
+
+fn handle_signal(socket: &WebSocket, state: &Rc<RefCell<RtcState>>, signal: Signal) {
+    match signal {
+        Signal::Welcome { id, peers } => {
+            info!("Joined signalling server as peer {}", id);
+            for peer in peers {
+                offer(socket.clone(), state.clone(), id, peer);
+            }
+        }
+        Signal::Offer { from, to, sdp } => answer(socket.clone(), state.clone(), to, from, sdp),
+        Signal::Answer { from, sdp, .. } => {
+            let connection = state.borrow().connections.get(&from).cloned();
+            if let Some(connection) = connection {
+                let mut description = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+                description.sdp(&sdp);
+                spawn_local(async move {
+                    let promise = connection.set_remote_description(&description);
+                    if let Err(e) = JsFuture::from(promise).await {
+                        warn!("Failed to accept answer from peer {}: {:?}", from, e);
+                    }
+                });
+            }
+        }
+        Signal::Candidate {
+            from,
+            candidate,
+            sdp_mid,
+            sdp_m_line_index,
+            ..
+        } => {
+            let connection = state.borrow().connections.get(&from).cloned();
+            if let Some(connection) = connection {
+                let mut init = RtcIceCandidateInit::new(&candidate);
+                init.sdp_mid(sdp_mid.as_deref());
+                init.sdp_m_line_index(sdp_m_line_index);
+                let promise =
+                    connection.add_ice_candidate_with_opt_rtc_ice_candidate_init(Some(&init));
+                spawn_local(async move {
+                    if let Err(e) = JsFuture::from(promise).await {
+                        warn!("Failed to add candidate from peer {}: {:?}", from, e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Create a connection to `peer`, forwarding our ICE candidates through the
+/// signalling socket
+fn new_connection(
+    socket: &WebSocket,
+    state: &Rc<RefCell<RtcState>>,
+    local: PeerId,
+    peer: PeerId,
+) -> Result<RtcPeerConnection, JsValue> {
+    let connection = RtcPeerConnection::new()?;
+
+    let ice_socket = socket.clone();
+    let on_ice = Closure::wrap(Box::new(move |e: RtcPeerConnectionIceEvent| {
+        if let Some(candidate) = e.candidate() {
+            send_signal(
+                &ice_socket,
+                &Signal::Candidate {
+                    from: local,
+                    to: peer,
+                    candidate: candidate.candidate(),
+                    sdp_mid: candidate.sdp_mid(),
+                    sdp_m_line_index: candidate.sdp_m_line_index(),
+                },
+            );
+        }
+    }) as Box<dyn FnMut(RtcPeerConnectionIceEvent)>);
+    connection.set_onicecandidate(Some(on_ice.as_ref().unchecked_ref()));
+    on_ice.forget();
+
+    state
+        .borrow_mut()
+        .connections
+        .insert(peer, connection.clone());
+    Ok(connection)
+}
+
+/// Route messages from a data channel into the inbox once it opens
+fn register_channel(state: &Rc<RefCell<RtcState>>, peer: PeerId, channel: RtcDataChannel) {
+    channel.set_binary_type(RtcDataChannelType::Arraybuffer);
+
+    let message_state = state.clone();
+    let on_message = Closure::wrap(Box::new(move |e: MessageEvent| {
+        let bytes = js_sys::Uint8Array::new(&e.data()).to_vec();
+        message_state.borrow_mut().inbox.push_back((peer, bytes));
+    }) as Box<dyn FnMut(MessageEvent)>);
+    channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    on_message.forget();
+
+    let open_state = state.clone();
+    let open_channel = channel.clone();
+    let on_open = Closure::wrap(Box::new(move |_: JsValue| {
+        info!("Data channel to peer {} open", peer);
+        let mut state = open_state.borrow_mut();
+        state.channels.insert(peer, open_channel.clone());
+        state.new_peers.push(peer);
+    }) as Box<dyn FnMut(JsValue)>);
+    channel.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+    on_open.forget();
+
+    let close_state = state.clone();
+    let on_close = Closure::wrap(Box::new(move |_: JsValue| {
+        info!("Data channel to peer {} closed", peer);
+        let mut state = close_state.borrow_mut();
+        state.channels.remove(&peer);
+        state.connections.remove(&peer);
+    }) as Box<dyn FnMut(JsValue)>);
+    channel.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+    on_close.forget();
+}
+
+fn offer(socket: WebSocket, state: Rc<RefCell<RtcState>>, local: PeerId, peer: PeerId) {
+    let connection = match new_connection(&socket, &state, local, peer) {
+        Ok(connection) => connection,
+        Err(e) => return warn!("Failed to connect to peer {}: {:?}", peer, e),
+    };
+    let channel = connection.create_data_channel(CHANNEL_LABEL);
+    register_channel(&state, peer, channel);
+
+    spawn_local(async move {
+        let result: Result<(), JsValue> = async {
+            let offer = JsFuture::from(connection.create_offer()).await?;
+            let sdp = js_sys::Reflect::get(&offer, &JsValue::from_str("sdp"))?
+                .as_string()
+                .unwrap_or_default();
+            let mut description = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
+            description.sdp(&sdp);
+            JsFuture::from(connection.set_local_description(&description)).await?;
+            send_signal(
+                &socket,
+                &Signal::Offer {
+                    from: local,
+                    to: peer,
+                    sdp,
+                },
+            );
+            Ok(())
+        }
+        .await;
+        if let Err(e) = result {
+            warn!("Failed to offer to peer {}: {:?}", peer, e);
+        }
+    });
+}
+
+fn answer(
+    socket: WebSocket,
+    state: Rc<RefCell<RtcState>>,
+    local: PeerId,
+    peer: PeerId,
+    offer_sdp: String,
+) {
+    let connection = match new_connection(&socket, &state, local, peer) {
+        Ok(connection) => connection,
+        Err(e) => return warn!("Failed to accept peer {}: {:?}", peer, e),
+    };
+
+    let channel_state = state.clone();
+    let on_channel = Closure::wrap(Box::new(move |e: RtcDataChannelEvent| {
+        register_channel(&channel_state, peer, e.channel());
+    }) as Box<dyn FnMut(RtcDataChannelEvent)>);
+    connection.set_ondatachannel(Some(on_channel.as_ref().unchecked_ref()));
+    on_channel.forget();
+
+    spawn_local(async move {
+        let result: Result<(), JsValue> = async {
+            let mut remote = RtcSessionDescriptionInit::new(RtcSdpType::Offer);
+            remote.sdp(&offer_sdp);
+            JsFuture::from(connection.set_remote_description(&remote)).await?;
+            let answer = JsFuture::from(connection.create_answer()).await?;
+            let sdp = js_sys::Reflect::get(&answer, &JsValue::from_str("sdp"))?
+                .as_string()
+                .unwrap_or_default();
+            let mut description = RtcSessionDescriptionInit::new(RtcSdpType::Answer);
+            description.sdp(&sdp);
+            JsFuture::from(connection.set_local_description(&description)).await?;
+            send_signal(
+                &socket,
+                &Signal::Answer {
+                    from: local,
+                    to: peer,
+                    sdp,
+                },
+            );
+            Ok(())
+        }
+        .await;
+        if let Err(e) = result {
+            warn!("Failed to answer peer {}: {:?}", peer, e);
+        }
+    });
+}
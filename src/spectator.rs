@@ -0,0 +1,303 @@
+use js_sys::Promise;
+use wasm_bindgen::prelude::*;
+
+use rustdag_lib::dag::contract::ContractValue;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use crate::{read_profile, unwrap_contract_u64, Context, Event, EventKind, PlayerData};
+
+#[derive(Debug, Default, PartialEq)]
+struct WorldPlayer {
+    last_seen: u64,
+    area: u64,
+}
+
+/// The players a spectator has seen, built from the event stream
+#[derive(Debug, Default)]
+pub struct World {
+    players: BTreeMap<String, WorldPlayer>,
+}
+
+impl World {
+    pub fn apply(&mut self, event: &Event) {
+        match event.kind {
+            // Everyone has to spawn again for the next round
+            EventKind::RoundEnd => return self.players.clear(),
+            EventKind::RoundStart | EventKind::Paused | EventKind::Resumed | EventKind::Profile => {
+                return
+            }
+            EventKind::Spawn | EventKind::Input | EventKind::Boost => (),
+        }
+        let player = self.players.entry(event.id.clone()).or_default();
+        if event.kind == EventKind::Spawn {
+            player.area = 0;
+        }
+        player.last_seen = player.last_seen.max(event.timestamp);
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &String> {
+        self.players.keys()
+    }
+
+    pub fn area(&self, id: &str) -> Option<u64> {
+        self.players.get(id).map(|p| p.area)
+    }
+
+    pub fn set_area(&mut self, id: &str, area: u64) {
+        if let Some(player) = self.players.get_mut(id) {
+            player.area = area;
+        }
+    }
+
+    /// Player with the most territory. Ties go to the lowest id so every
+    /// spectator picks the same leader.
+    pub fn leader(&self) -> Option<&str> {
+        self.players
+            .iter()
+            .max_by(|(a_id, a), (b_id, b)| a.area.cmp(&b.area).then(b_id.cmp(a_id)))
+            .map(|(id, _)| id.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CameraMode {
+    Free,
+    Follow(String),
+    FollowLeader,
+}
+
+impl CameraMode {
+    /// Player the camera should be centred on, if any
+    pub fn target<'a>(&'a self, world: &'a World) -> Option<&'a str> {
+        match self {
+            CameraMode::Free => None,
+            CameraMode::Follow(id) => world.area(id).map(|_| id.as_str()),
+            CameraMode::FollowLeader => world.leader(),
+        }
+    }
+}
+
+/// A keyless view of the game that follows the DAG without taking part
+#[wasm_bindgen]
+pub struct Spectator {
+    context: Context,
+    world: RefCell<World>,
+    camera: RefCell<CameraMode>,
+}
+
+#[wasm_bindgen]
+impl Spectator {
+    #[wasm_bindgen(constructor)]
+    pub fn new(urls: Box<[JsValue]>, contract_address: String) -> Self {
+        Spectator {
+            context: Context::new(urls, contract_address),
+            world: RefCell::from(World::default()),
+            camera: RefCell::from(CameraMode::FollowLeader),
+        }
+    }
+
+    pub fn tips_sync(&self) -> Promise {
+        self.context.tips_sync()
+    }
+
+    pub fn subscribe(&self) -> Promise {
+        self.context.subscribe()
+    }
+
+    /// Events since the last call. They are also folded into the world
+    /// snapshot.
+    pub fn take_events(&self) -> JsValue {
+        let events = self.context.drain_events();
+        let mut world = self.world.borrow_mut();
+        for e in events.iter() {
+            world.apply(e);
+        }
+        events
+            .into_iter()
+            .map(JsValue::from)
+            .collect::<js_sys::Array>()
+            .into()
+    }
+
+    /// Current state of every player seen so far
+    pub fn snapshot(&self) -> Result<JsValue, JsValue> {
+        let world = self.world.borrow();
+        let rows = js_sys::Array::new();
+        for id in world.ids() {
+            let data = self.player_data(id)?;
+            rows.push(
+                &PlayerSnapshot {
+                    id: id.clone(),
+                    area: world.area(id).unwrap_or(0),
+                    data,
+                }
+                .into(),
+            );
+        }
+        Ok(rows.into())
+    }
+
+    /// Territory is still computed by the renderer, which reports it here
+    /// so the camera can pick a leader
+    pub fn report_area(&self, id: String, area: u32) {
+        self.world.borrow_mut().set_area(&id, area.into());
+    }
+
+    /// Watch another game in the contract, starting from an empty world
+    pub fn watch(&self, game_id: String) -> Result<(), JsValue> {
+        let game = game_id
+            .parse()
+            .map_err(|_| JsValue::from_str("Failed to parse game id."))?;
+        self.context.game.set(game);
+        self.world.replace(World::default());
+        Ok(())
+    }
+
+    pub fn list_games(&self) -> Result<JsValue, JsValue> {
+        self.context.list_games()
+    }
+
+    pub fn follow(&self, id: String) {
+        self.camera.replace(CameraMode::Follow(id));
+    }
+
+    pub fn follow_leader(&self) {
+        self.camera.replace(CameraMode::FollowLeader);
+    }
+
+    pub fn free_camera(&self) {
+        self.camera.replace(CameraMode::Free);
+    }
+
+    /// Id of the player the camera follows, or undefined for a free camera
+    pub fn camera_target(&self) -> JsValue {
+        let world = self.world.borrow();
+        match self.camera.borrow().target(&world) {
+            Some(id) => id.into(),
+            None => JsValue::UNDEFINED,
+        }
+    }
+
+    /// Position of the followed player, or undefined for a free camera
+    pub fn camera_position(&self) -> Result<JsValue, JsValue> {
+        let target = match self.camera_target().as_string() {
+            Some(id) => id,
+            None => return Ok(JsValue::UNDEFINED),
+        };
+        let data = self.player_data(&target)?;
+        Ok(data.into())
+    }
+}
+
+impl Spectator {
+    fn player_data(&self, id: &str) -> Result<PlayerData, JsValue> {
+        let id = ContractValue::U64(id.parse().expect("Failed to parse id."));
+        let x = self.context.call_local("get_player_x", &[id])?;
+        let y = self.context.call_local("get_player_y", &[id])?;
+        let heading = self.context.call_local("get_player_heading", &[id])?;
+        let (name, color) = read_profile(
+            |f, args| self.context.call_local(f, args),
+            unwrap_contract_u64(id),
+        )?;
+        Ok(PlayerData::from_contract(x, y, heading).with_profile(name, color))
+    }
+}
+
+#[wasm_bindgen]
+pub struct PlayerSnapshot {
+    id: String,
+    area: u64,
+    data: PlayerData,
+}
+
+#[wasm_bindgen]
+impl PlayerSnapshot {
+    pub fn id(&self) -> JsValue {
+        self.id.clone().into()
+    }
+
+    pub fn area(&self) -> JsValue {
+        self.area.to_string().into()
+    }
+
+    pub fn x(&self) -> JsValue {
+        self.data.x()
+    }
+
+    pub fn y(&self) -> JsValue {
+        self.data.y()
+    }
+
+    pub fn heading(&self) -> JsValue {
+        self.data.heading()
+    }
+
+    pub fn name(&self) -> JsValue {
+        self.data.name()
+    }
+
+    pub fn color(&self) -> JsValue {
+        self.data.color()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world() -> World {
+        let mut world = World::default();
+        world.apply(&Event::spawn("1".to_string(), 0, 0, 10));
+        world.apply(&Event::spawn("2".to_string(), 5, 5, 20));
+        world.apply(&Event::input("1".to_string(), 2, 30));
+        world
+    }
+
+    #[test]
+    fn test_world_tracks_spawned_players() {
+        let world = world();
+        assert_eq!(world.ids().collect::<Vec<_>>(), vec!["1", "2"]);
+        assert_eq!(world.players["1"].last_seen, 30);
+    }
+
+    #[test]
+    fn test_leader_by_area() {
+        let mut world = world();
+        assert_eq!(world.leader(), Some("1"));
+
+        world.set_area("2", 9);
+        assert_eq!(world.leader(), Some("2"));
+
+        // Respawning resets area
+        world.apply(&Event::spawn("2".to_string(), 0, 0, 40));
+        world.set_area("1", 3);
+        assert_eq!(world.leader(), Some("1"));
+    }
+
+    #[test]
+    fn test_round_end_clears_world() {
+        let mut world = world();
+        world.apply(&Event::round(EventKind::RoundStart, "9".to_string(), 0, 40));
+        assert_eq!(world.ids().count(), 2);
+
+        world.apply(&Event::round(EventKind::RoundEnd, "9".to_string(), 0, 50));
+        assert_eq!(world.ids().count(), 0);
+        assert_eq!(world.leader(), None);
+    }
+
+    #[test]
+    fn test_camera_target() {
+        let mut world = world();
+        world.set_area("2", 4);
+
+        assert_eq!(CameraMode::FollowLeader.target(&world), Some("2"));
+        assert_eq!(
+            CameraMode::Follow("1".to_string()).target(&world),
+            Some("1")
+        );
+        assert_eq!(CameraMode::Follow("3".to_string()).target(&world), None);
+        assert_eq!(CameraMode::Free.target(&world), None);
+    }
+}
@@ -0,0 +1,360 @@
+use js_sys::Promise;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{MessageEvent, WebSocket};
+
+use rustdag_lib::dag::contract::ContractValue;
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashSet, VecDeque};
+use std::rc::Rc;
+use std::sync::mpsc::Sender;
+
+use log::{info, warn};
+
+use crate::rounds::is_round_call;
+use crate::{event_from_call, Event};
+
+/// A contract call pushed by the node over a subscription socket
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ContractCall {
+    pub hash: u64,
+    pub address: u64,
+    pub contract: u64,
+    pub timestamp: u64,
+    pub func_name: String,
+    pub args: Vec<u64>,
+}
+
+/// Most transaction hashes an `EventSink` remembers. The socket and
+/// `tips_sync` see a transaction within moments of each other, so only the
+/// recent ones need checking.
+const SEEN_LIMIT: usize = 10_000;
+
+/// How long a dropped subscription waits before reconnecting, polling with
+/// `tips_sync` meanwhile
+const RECONNECT_MS: f64 = 5000.0;
+
+/// The most recent transaction hashes, oldest first out
+#[derive(Default)]
+struct Seen {
+    hashes: HashSet<u64>,
+    order: VecDeque<u64>,
+}
+
+impl Seen {
+    /// Remember `hash`, returning false if it is already remembered
+    fn insert(&mut self, hash: u64) -> bool {
+        if !self.hashes.insert(hash) {
+            return false;
+        }
+        self.order.push_back(hash);
+        if self.order.len() > SEEN_LIMIT {
+            if let Some(oldest) = self.order.pop_front() {
+                self.hashes.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+/// Deduplicating front of the event channel.
+///
+/// The same transaction can be seen by both `tips_sync` and the subscription
+/// socket, so events are only forwarded the first time their hash shows up.
+#[derive(Clone)]
+pub struct EventSink {
+    sender: Sender<Event>,
+    seen: Rc<RefCell<Seen>>,
+}
+
+impl EventSink {
+    pub fn new(sender: Sender<Event>) -> Self {
+        EventSink {
+            sender,
+            seen: Rc::from(RefCell::from(Seen::default())),
+        }
+    }
+
+    /// Forward `event` unless the transaction `hash` was already handled.
+    /// Returns whether the transaction was new.
+    pub fn emit(&self, hash: u64, event: Option<Event>) -> bool {
+        self.emit_all(hash, event.into_iter().collect())
+    }
+
+    /// Forward every event of the transaction `hash`, unless it was already
+    /// handled
+    pub fn emit_all(&self, hash: u64, events: Vec<Event>) -> bool {
+        if !self.seen.borrow_mut().insert(hash) {
+            return false;
+        }
+        for e in events {
+            self.sender
+                .send(e)
+                .expect("Failed to send in the event mpsc");
+        }
+        true
+    }
+}
+
+/// Decode one frame from the subscription socket and feed it to `sink`.
+/// Round and pause calls are left for `tips_sync`, which replicates them and
+/// can tell whether they did anything.
+pub fn handle_frame(
+    frame: &str,
+    contract_address: u64,
+    sink: &EventSink,
+) -> Result<(), serde_json::Error> {
+    let call: ContractCall = serde_json::from_str(frame)?;
+    if call.contract == contract_address && !is_round_call(&call.func_name) {
+        let args = call
+            .args
+            .iter()
+            .map(|a| ContractValue::U64(*a))
+            .collect::<Vec<_>>();
+        let event = event_from_call(call.address, &call.func_name, &args, call.timestamp);
+        sink.emit(call.hash, event);
+    }
+    Ok(())
+}
+
+/// Build the subscription endpoint for `contract_address` on the node at `node_url`
+pub fn subscribe_url(node_url: &str, contract_address: u64) -> String {
+    let base = if node_url.starts_with("https://") {
+        node_url.replacen("https://", "wss://", 1)
+    } else if node_url.starts_with("http://") {
+        node_url.replacen("http://", "ws://", 1)
+    } else {
+        node_url.to_string()
+    };
+    format!(
+        "{}/subscribe/{}",
+        base.trim_end_matches('/'),
+        contract_address
+    )
+}
+
+/// Whether a subscription closed at `closed_at` should be opened again
+fn reconnect_due(closed_at: Option<f64>, now: f64) -> bool {
+    closed_at.map_or(false, |closed_at| now - closed_at >= RECONNECT_MS)
+}
+
+/// An open WebSocket subscription. Dropping it closes the socket.
+pub struct Subscription {
+    socket: WebSocket,
+    closed_at: Rc<Cell<Option<f64>>>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_open: Closure<dyn FnMut(JsValue)>,
+    _on_close: Closure<dyn FnMut(JsValue)>,
+}
+
+impl Subscription {
+    /// Open a subscription socket. The returned promise resolves to `true`
+    /// once the socket is open, or `false` if the endpoint is unavailable, in
+    /// which case the caller should keep polling with `tips_sync`.
+    pub fn open(
+        url: &str,
+        contract_address: u64,
+        sink: EventSink,
+        streaming: Rc<Cell<bool>>,
+    ) -> Result<(Self, Promise), JsValue> {
+        let socket = WebSocket::new(url)?;
+
+        let on_message = Closure::wrap(Box::new(move |e: MessageEvent| {
+            if let Some(frame) = e.data().as_string() {
+                if let Err(err) = handle_frame(&frame, contract_address, &sink) {
+                    warn!("Dropping malformed subscription frame: {}", err);
+                }
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        let closed_at = Rc::from(Cell::from(None));
+        let (on_open, on_close, promise) = {
+            let open_flag = streaming.clone();
+            let close_flag = streaming;
+            let close_time = closed_at.clone();
+            let resolve_cell: Rc<RefCell<Option<js_sys::Function>>> = Rc::from(RefCell::from(None));
+            let promise = {
+                let resolve_cell = resolve_cell.clone();
+                Promise::new(&mut move |resolve, _reject| {
+                    resolve_cell.borrow_mut().replace(resolve);
+                })
+            };
+
+            let open_resolve = resolve_cell.clone();
+            let on_open = Closure::wrap(Box::new(move |_: JsValue| {
+                info!("Subscription socket open");
+                open_flag.set(true);
+                if let Some(resolve) = open_resolve.borrow_mut().take() {
+                    let _ = resolve.call1(&JsValue::NULL, &JsValue::TRUE);
+                }
+            }) as Box<dyn FnMut(JsValue)>);
+
+            let close_resolve = resolve_cell;
+            let on_close = Closure::wrap(Box::new(move |_: JsValue| {
+                warn!("Subscription socket closed, falling back to polling");
+                close_flag.set(false);
+                close_time.set(Some(js_sys::Date::now()));
+                if let Some(resolve) = close_resolve.borrow_mut().take() {
+                    let _ = resolve.call1(&JsValue::NULL, &JsValue::FALSE);
+                }
+            }) as Box<dyn FnMut(JsValue)>);
+
+            (on_open, on_close, promise)
+        };
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        // An unreachable endpoint fires `error` followed by `close`
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        Ok((
+            Subscription {
+                socket,
+                closed_at,
+                _on_message: on_message,
+                _on_open: on_open,
+                _on_close: on_close,
+            },
+            promise,
+        ))
+    }
+
+    /// Whether the socket has been closed long enough to try opening it again
+    pub fn reconnect_due(&self, now: f64) -> bool {
+        reconnect_due(self.closed_at.get(), now)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.socket.set_onmessage(None);
+        self.socket.set_onopen(None);
+        self.socket.set_onclose(None);
+        let _ = self.socket.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::mpsc::channel;
+
+    /// Stand-in for the node's subscription endpoint
+    struct MockServer {
+        frames: VecDeque<String>,
+    }
+
+    impl MockServer {
+        fn new() -> Self {
+            MockServer {
+                frames: VecDeque::new(),
+            }
+        }
+
+        fn push(&mut self, call: ContractCall) {
+            self.frames.push_back(serde_json::to_string(&call).unwrap());
+        }
+
+        fn push_raw(&mut self, frame: &str) {
+            self.frames.push_back(frame.to_string());
+        }
+
+        fn drain_into(&mut self, contract_address: u64, sink: &EventSink) -> usize {
+            let mut errors = 0;
+            while let Some(frame) = self.frames.pop_front() {
+                if handle_frame(&frame, contract_address, sink).is_err() {
+                    errors += 1;
+                }
+            }
+            errors
+        }
+    }
+
+    fn call(hash: u64, contract: u64, func_name: &str, args: Vec<u64>) -> ContractCall {
+        ContractCall {
+            hash,
+            address: 7,
+            contract,
+            timestamp: 1000 + hash,
+            func_name: func_name.to_string(),
+            args,
+        }
+    }
+
+    #[test]
+    fn test_subscribe_url() {
+        assert_eq!(
+            subscribe_url("http://localhost:8090", 42),
+            "ws://localhost:8090/subscribe/42"
+        );
+        assert_eq!(
+            subscribe_url("https://node.example/", 42),
+            "wss://node.example/subscribe/42"
+        );
+    }
+
+    #[test]
+    fn test_frames_become_events() {
+        let (send, recv) = channel();
+        let sink = EventSink::new(send);
+        let mut server = MockServer::new();
+
+        let origin = u64::from(u32::MAX);
+        server.push(call(1, 5, "spawn_player", vec![origin + 3, origin - 2]));
+        server.push(call(2, 5, "apply_input", vec![1]));
+        server.push(call(3, 6, "apply_input", vec![2])); // other contract
+        server.push(call(4, 5, "get_player_x", vec![7])); // not an event
+        server.push(call(5, 5, "end_round", vec![2]));
+        assert_eq!(server.drain_into(5, &sink), 0);
+
+        let events = recv.try_iter().collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                Event::spawn("7".to_string(), 3, -2, 1001),
+                Event::input("7".to_string(), 1, 1002),
+            ]
+        );
+        // Still to be handled by tips_sync
+        assert!(sink.emit(5, None));
+    }
+
+    #[test]
+    fn test_duplicate_and_malformed_frames() {
+        let (send, recv) = channel();
+        let sink = EventSink::new(send);
+        let mut server = MockServer::new();
+
+        server.push(call(1, 5, "apply_input", vec![0]));
+        server.push(call(1, 5, "apply_input", vec![0]));
+        server.push_raw("not json");
+        assert_eq!(server.drain_into(5, &sink), 1);
+        assert_eq!(recv.try_iter().count(), 1);
+
+        // Already delivered by the socket, so tips_sync must not repeat it
+        assert!(!sink.emit(1, Some(Event::input("7".to_string(), 0, 1001))));
+        assert_eq!(recv.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_seen_forgets_the_oldest() {
+        let mut seen = Seen::default();
+        for hash in 0..SEEN_LIMIT as u64 {
+            assert!(seen.insert(hash));
+        }
+        assert!(!seen.insert(0));
+        assert!(seen.insert(SEEN_LIMIT as u64));
+        assert_eq!(seen.hashes.len(), SEEN_LIMIT);
+        assert!(seen.insert(0));
+        assert!(!seen.insert(SEEN_LIMIT as u64));
+    }
+
+    #[test]
+    fn test_reconnect_waits_after_closing() {
+        assert!(!reconnect_due(None, 1e9));
+        assert!(!reconnect_due(Some(1000.0), 1000.0 + RECONNECT_MS - 1.0));
+        assert!(reconnect_due(Some(1000.0), 1000.0 + RECONNECT_MS));
+    }
+}
@@ -0,0 +1,91 @@
+use rand::{rngs::StdRng, Rng};
+
+use p2pio_contract::{
+    error::ContractError, heading::PlayerHeading, CELL_WIDTH, GRID_COUNT, GRID_ORIGIN,
+};
+
+use rustdag_lib::{dag::contract::ContractValue, security::keys::eddsa::EdDSAKeyPair};
+
+use super::Backend;
+
+const VERTICAL: [PlayerHeading; 2] = [PlayerHeading::Up, PlayerHeading::Down];
+const HORIZONTAL: [PlayerHeading; 2] = [PlayerHeading::Left, PlayerHeading::Right];
+
+/// A scripted player that wanders about turning at random, with their own key
+pub struct Bot {
+    key: EdDSAKeyPair,
+    /// Cells from the middle of the grid they spawn within
+    spread: i64,
+    alive: bool,
+    heading: Option<PlayerHeading>,
+}
+
+impl Bot {
+    pub fn new(key: EdDSAKeyPair, spread: i64) -> Self {
+        Bot {
+            key,
+            spread,
+            alive: false,
+            heading: None,
+        }
+    }
+
+    pub fn key(&self) -> &EdDSAKeyPair {
+        &self.key
+    }
+
+    /// Next call the bot makes: a spawn if they're dead, otherwise a turn
+    /// across their current heading, so they never run straight back into
+    /// their own trail
+    pub fn next_call(&self, rng: &mut StdRng) -> (&'static str, Vec<ContractValue>) {
+        if !self.alive {
+            let line = |rng: &mut StdRng| {
+                let cell = GRID_COUNT / 2 + rng.gen_range(-self.spread, self.spread);
+                ContractValue::U64((GRID_ORIGIN + cell * CELL_WIDTH) as u64)
+            };
+            return ("spawn_player", vec![line(rng), line(rng)]);
+        }
+        let choices = match self.heading {
+            Some(heading) if VERTICAL.contains(&heading) => HORIZONTAL,
+            _ => VERTICAL,
+        };
+        let heading = choices[rng.gen_range(0, 2)];
+        (
+            "apply_input",
+            vec![ContractValue::U64(heading.as_i64() as u64)],
+        )
+    }
+
+    /// Update what we know of the bot from the code a call returned
+    pub fn observe(&mut self, func_name: &str, args: &[ContractValue], code: i64) {
+        match (func_name, code) {
+            ("spawn_player", 0) => {
+                self.alive = true;
+                self.heading = None;
+            }
+            ("apply_input", code) if code == ContractError::NotAlive.code() => self.alive = false,
+            ("apply_input", 0) => {
+                if let Some(ContractValue::U64(heading)) = args.first() {
+                    self.heading = Some(PlayerHeading::from(*heading as i64));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Make the bot's next call on `backend`
+    pub fn step(&mut self, backend: &mut dyn Backend, rng: &mut StdRng) -> Result<(), String> {
+        let (func_name, args) = self.next_call(rng);
+        let code = code(backend.call(&self.key, func_name, &args)?);
+        self.observe(func_name, &args, code);
+        Ok(())
+    }
+}
+
+/// Code an entry point returned, or -1 if it returned nothing
+pub fn code(value: Option<ContractValue>) -> i64 {
+    match value {
+        Some(ContractValue::U64(code)) => code as i64,
+        _ => -1,
+    }
+}
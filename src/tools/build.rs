@@ -0,0 +1,47 @@
+//! Building the contract for the wasm runtime. The integration tests can't
+//! turn on the `tools` feature, so they include this file directly.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Kept apart from the target directory of whatever is running the build,
+/// which cargo holds a lock on while it runs
+const CONTRACT_TARGET_DIR: &str = "target/contract";
+const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
+/// Cargo running us, or the one on the path
+pub fn cargo() -> String {
+    env::var("CARGO").unwrap_or_else(|_| "cargo".to_string())
+}
+
+/// Where `build_contract` leaves the module for the repository at `root`
+pub fn built_contract(root: &Path) -> PathBuf {
+    root.join(CONTRACT_TARGET_DIR)
+        .join(WASM_TARGET)
+        .join("release")
+        .join("p2pio_contract.wasm")
+}
+
+/// Build the contract under the repository at `root`, returning the path of
+/// the module. Cargo caches the build, so this only recompiles when the
+/// contract has changed.
+pub fn build_contract(root: &Path) -> Result<PathBuf, String> {
+    let status = Command::new(cargo())
+        .arg("build")
+        .arg("--release")
+        .args(&["--target", WASM_TARGET])
+        .arg("--manifest-path")
+        .arg(root.join("contract/Cargo.toml"))
+        .arg("--target-dir")
+        .arg(root.join(CONTRACT_TARGET_DIR))
+        .status()
+        .map_err(|e| format!("Failed to run cargo build for contract: {}", e))?;
+    if !status.success() {
+        return Err(format!(
+            "Failed to build contract. Is the {} target installed?",
+            WASM_TARGET
+        ));
+    }
+    Ok(built_contract(root))
+}
@@ -0,0 +1,20 @@
+//! Pieces shared by the native tools: the terminal client, the load tester
+//! and the command line client. They drive the contract from outside a
+//! browser, so they sit behind the `tools` feature.
+
+mod bot;
+mod build;
+mod node;
+
+use std::path::{Path, PathBuf};
+
+pub use bot::{code, Bot};
+pub use build::{build_contract, built_contract, cargo};
+pub use node::{Backend, InProcess, Remote};
+
+/// Where `p2pio build` leaves the contract's wasm module in this
+/// repository, the default for tools that deploy it on a DAG in their own
+/// process
+pub fn contract_wasm() -> PathBuf {
+    built_contract(Path::new(env!("CARGO_MANIFEST_DIR")))
+}
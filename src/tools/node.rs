@@ -0,0 +1,193 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use futures::executor::block_on;
+
+use rustdag_lib::{
+    dag::{
+        contract::{source::ContractSource, ContractValue},
+        transaction::Transaction,
+    },
+    security::keys::eddsa::{get_address, get_public_key, new_key_pair, EdDSAKeyPair},
+};
+use rustdag_wasm::blockdag::BlockDAG;
+
+use crate::gossip::{local_dag, GossipNode, LocalNode, NullTransport};
+
+/// Where calls go, and a replica of the DAG to read the board from
+pub trait Backend {
+    fn contract(&self) -> u64;
+
+    /// Execute a contract call as `key`, returning the contract's result
+    fn call(
+        &mut self,
+        key: &EdDSAKeyPair,
+        func_name: &str,
+        args: &[ContractValue],
+    ) -> Result<Option<ContractValue>, String>;
+
+    /// Transactions that reached the replica since the last sync, in commit
+    /// order
+    fn sync(&mut self) -> Result<Vec<Transaction>, String>;
+
+    /// Run a getter against the replica
+    fn query(&mut self, func_name: &str, args: &[ContractValue]) -> Result<i64, String>;
+}
+
+/// Transactions appended to `node`'s history since it was `seen` long
+fn take_new(node: &dyn LocalNode, seen: &mut usize) -> Vec<Transaction> {
+    let history = node.history();
+    let new = history[*seen..].to_vec();
+    *seen = history.len();
+    new
+}
+
+fn query(
+    node: &mut dyn LocalNode,
+    key: &EdDSAKeyPair,
+    contract: u64,
+    func_name: &str,
+    args: &[ContractValue],
+) -> Result<i64, String> {
+    match node.query(key, contract, func_name, args)? {
+        Some(ContractValue::U64(value)) => Ok(value as i64),
+        _ => Err(format!("{} didn't return a value", func_name)),
+    }
+}
+
+/// A DAG in this process, with the contract deployed from a local file. It
+/// is its own replica.
+pub struct InProcess {
+    node: Box<dyn LocalNode>,
+    contract: u64,
+    query_key: EdDSAKeyPair,
+    seen: usize,
+}
+
+impl InProcess {
+    pub fn deploy(wasm_path: &Path) -> Result<Self, String> {
+        let bytes = fs::read(wasm_path)
+            .map_err(|e| format!("Failed to read contract {}: {}", wasm_path.display(), e))?;
+        let contract_key = new_key_pair();
+        let contract = get_address(&get_public_key(&contract_key));
+        let mut node: Box<dyn LocalNode> =
+            Box::new(GossipNode::new(local_dag(contract), NullTransport));
+        node.deploy(&contract_key, ContractSource::with_vec(bytes))?;
+        Ok(InProcess {
+            node,
+            contract,
+            query_key: new_key_pair(),
+            seen: 0,
+        })
+    }
+}
+
+impl Backend for InProcess {
+    fn contract(&self) -> u64 {
+        self.contract
+    }
+
+    fn call(
+        &mut self,
+        key: &EdDSAKeyPair,
+        func_name: &str,
+        args: &[ContractValue],
+    ) -> Result<Option<ContractValue>, String> {
+        let (value, _) = self.node.execute(key, self.contract, func_name, args)?;
+        Ok(value)
+    }
+
+    fn sync(&mut self) -> Result<Vec<Transaction>, String> {
+        Ok(take_new(self.node.as_ref(), &mut self.seen))
+    }
+
+    fn query(&mut self, func_name: &str, args: &[ContractValue]) -> Result<i64, String> {
+        query(
+            self.node.as_mut(),
+            &self.query_key,
+            self.contract,
+            func_name,
+            args,
+        )
+    }
+}
+
+/// A rustdag node over HTTP, through the same client the browser uses, with
+/// a local replica kept up to date the way `Context` keeps its own. Getters
+/// run on the node would commit a transaction for every read, so they run
+/// on the replica, which calls reach on the next sync.
+pub struct Remote {
+    dag: BlockDAG,
+    contract: u64,
+    replica: Box<dyn LocalNode>,
+    query_key: EdDSAKeyPair,
+    seen: usize,
+}
+
+impl Remote {
+    pub fn new(url: &str, contract: u64) -> Self {
+        let query_key = new_key_pair();
+        let replica = GossipNode::new(
+            local_dag(get_address(&get_public_key(&query_key))),
+            NullTransport,
+        );
+        Remote {
+            dag: BlockDAG::new(url.to_string()),
+            contract,
+            replica: Box::new(replica),
+            query_key,
+            seen: 0,
+        }
+    }
+}
+
+impl Backend for Remote {
+    fn contract(&self) -> u64 {
+        self.contract
+    }
+
+    fn call(
+        &mut self,
+        key: &EdDSAKeyPair,
+        func_name: &str,
+        args: &[ContractValue],
+    ) -> Result<Option<ContractValue>, String> {
+        let inner = self.dag.clone_inner();
+        let contract = self.contract;
+        block_on(async move {
+            let mut remote = inner.lock().await;
+            // Tip selection should include the caller's own transactions
+            remote.update_address(get_address(&get_public_key(key)));
+            remote
+                .execute_contract(key, contract, func_name, args)
+                .await
+                .map(|(value, _)| value)
+                .map_err(|e| format!("{:?}", e))
+        })
+    }
+
+    fn sync(&mut self) -> Result<Vec<Transaction>, String> {
+        let fetched = Rc::new(RefCell::new(Vec::new()));
+        let sink = fetched.clone();
+        block_on(BlockDAG::tips_sync(self.dag.clone(), move |trans| {
+            sink.borrow_mut().push(trans.clone())
+        }))
+        .map_err(|e| format!("{:?}", e))?;
+        for trans in fetched.replace(Vec::new()) {
+            self.replica.replicate(trans);
+        }
+        Ok(take_new(self.replica.as_ref(), &mut self.seen))
+    }
+
+    fn query(&mut self, func_name: &str, args: &[ContractValue]) -> Result<i64, String> {
+        query(
+            self.replica.as_mut(),
+            &self.query_key,
+            self.contract,
+            func_name,
+            args,
+        )
+    }
+}
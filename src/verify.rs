@@ -0,0 +1,592 @@
+use wasm_bindgen::prelude::*;
+
+use rustdag_lib::dag::contract::ContractValue;
+use rustdag_lib::dag::transaction::{data::TransactionData, Transaction};
+use rustdag_lib::security::keys::eddsa::{get_address, get_public_key, new_key_pair};
+
+use p2pio_contract::heading::PlayerHeading;
+use p2pio_contract::movement::{ms_delta_to_tick, position_at, Boost, BOOST_COOLDOWN};
+use p2pio_contract::{Cell, CELL_WIDTH, GRID_COUNT, GRID_ORIGIN};
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+
+use crate::gossip::{local_dag, GossipNode, LocalNode, NullTransport};
+use crate::unwrap_contract_u64;
+
+/// Most turns anyone can key in over a second of game ticks
+pub const MAX_TURNS_PER_SECOND: usize = 10;
+const TICKS_PER_SECOND: i64 = 60;
+
+/// Something in a player's transactions that a fair client never sends
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// Timestamped before a transaction it approves
+    BackDated {
+        hash: u64,
+        timestamp: u64,
+        parent: u64,
+        parent_timestamp: u64,
+    },
+    /// Turned along the axis the player was already moving on, which
+    /// `changeHeading` in game-client.js never sends
+    SameAxisTurn { hash: u64, from: i64, to: i64 },
+    /// More turns within a second than anyone can key in
+    TurnRate { hash: u64, tick: i64, turns: usize },
+    /// Spawned again while still alive, jumping across the board
+    Teleport {
+        hash: u64,
+        from: Option<(i64, i64)>,
+        to: (i64, i64),
+    },
+    /// Spawned somewhere the client's spawn picker never chooses: off the
+    /// grid, on its edge, or off the corner of a cell
+    BadSpawn { hash: u64, x: i64, y: i64 },
+    /// Sent a heading the contract doesn't know
+    BadHeading { hash: u64, heading: i64 },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::BackDated {
+                hash,
+                timestamp,
+                parent,
+                parent_timestamp,
+            } => write!(
+                f,
+                "{} is dated {} but approves {} dated {}",
+                hash, timestamp, parent, parent_timestamp
+            ),
+            Violation::SameAxisTurn { hash, from, to } => {
+                write!(f, "{} turned from heading {} to {}", hash, from, to)
+            }
+            Violation::TurnRate { hash, tick, turns } => write!(
+                f,
+                "{} made {} turns in the second up to tick {}",
+                hash, turns, tick
+            ),
+            Violation::Teleport {
+                hash,
+                from: Some((fx, fy)),
+                to: (x, y),
+            } => write!(
+                f,
+                "{} spawned at ({}, {}) while alive at ({}, {})",
+                hash, x, y, fx, fy
+            ),
+            Violation::Teleport {
+                hash,
+                from: None,
+                to: (x, y),
+            } => write!(f, "{} spawned at ({}, {}) while alive", hash, x, y),
+            Violation::BadSpawn { hash, x, y } => {
+                write!(f, "{} spawned off a free cell at ({}, {})", hash, x, y)
+            }
+            Violation::BadHeading { hash, heading } => {
+                write!(f, "{} sent unknown heading {}", hash, heading)
+            }
+        }
+    }
+}
+
+/// Everything one address sent, and what was wrong with it
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Report {
+    pub address: u64,
+    pub transactions: usize,
+    pub violations: Vec<Violation>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+#[wasm_bindgen]
+pub struct VerifyReport(Report);
+
+#[wasm_bindgen]
+impl VerifyReport {
+    pub fn address(&self) -> JsValue {
+        self.0.address.to_string().into()
+    }
+
+    pub fn transactions(&self) -> JsValue {
+        (self.0.transactions as u32).into()
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.0.is_clean()
+    }
+
+    /// A description of each violation, oldest first
+    pub fn violations(&self) -> JsValue {
+        self.0
+            .violations
+            .iter()
+            .map(|v| JsValue::from(v.to_string()))
+            .collect::<js_sys::Array>()
+            .into()
+    }
+}
+
+impl From<Report> for VerifyReport {
+    fn from(report: Report) -> Self {
+        VerifyReport(report)
+    }
+}
+
+/// What the contract said about a call's sender just before the call ran
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Sender {
+    pub alive: bool,
+    /// Start time of the round in the sender's game, which ticks count from
+    pub start_time: u64,
+    /// Milliseconds that round has spent paused, which don't count as ticks
+    pub paused_ms: u64,
+}
+
+/// A call to the p2pio contract, as found in a transaction
+pub struct Call<'a> {
+    pub hash: u64,
+    pub address: u64,
+    pub timestamp: u64,
+    pub func_name: &'a str,
+    pub args: &'a [ContractValue],
+}
+
+/// One player's movement, rebuilt from their own calls with the contract's
+/// rules
+struct Replay {
+    /// Last anchor while the player is alive. A player admitted from the
+    /// waiting queue is alive before we know where.
+    anchor: Option<(i64, i64, i64)>,
+    heading: i64,
+    boost: Boost,
+    boost_ready: i64,
+    /// Ticks of the turns made in the last second
+    turns: VecDeque<i64>,
+}
+
+impl Replay {
+    fn new() -> Self {
+        Replay {
+            anchor: None,
+            heading: PlayerHeading::NoHeading.as_i64(),
+            boost: Boost::default(),
+            boost_ready: 0,
+            turns: VecDeque::new(),
+        }
+    }
+
+    fn position(&self, tick: i64) -> Option<(i64, i64)> {
+        self.anchor.map(|anchor| {
+            let heading = PlayerHeading::from(self.heading);
+            position_at(anchor, heading, self.boost, tick.max(anchor.2))
+        })
+    }
+
+    fn spawn(&mut self, hash: u64, x: i64, y: i64, tick: i64, alive: bool) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        if alive {
+            violations.push(Violation::Teleport {
+                hash,
+                from: self.position(tick),
+                to: (x, y),
+            });
+        }
+        let on_corner = (x - GRID_ORIGIN) % CELL_WIDTH == 0 && (y - GRID_ORIGIN) % CELL_WIDTH == 0;
+        let inside = Cell::at(x, y).map_or(false, |cell| {
+            0 < cell.col && cell.col < GRID_COUNT - 1 && 0 < cell.row && cell.row < GRID_COUNT - 1
+        });
+        if !on_corner || !inside {
+            violations.push(Violation::BadSpawn { hash, x, y });
+        }
+
+        *self = Replay::new();
+        self.anchor = Some((x, y, tick));
+        self.boost_ready = tick;
+        violations
+    }
+
+    fn turn(&mut self, hash: u64, heading: i64, tick: i64) -> Vec<Violation> {
+        if !(0..=PlayerHeading::NoHeading.as_i64()).contains(&heading) {
+            return vec![Violation::BadHeading { hash, heading }];
+        }
+        if heading == self.heading {
+            return Vec::new();
+        }
+
+        let mut violations = Vec::new();
+        let no_heading = PlayerHeading::NoHeading.as_i64();
+        if self.heading != no_heading && heading != no_heading && heading % 2 == self.heading % 2 {
+            violations.push(Violation::SameAxisTurn {
+                hash,
+                from: self.heading,
+                to: heading,
+            });
+        }
+        while self
+            .turns
+            .front()
+            .map_or(false, |t| *t <= tick - TICKS_PER_SECOND)
+        {
+            self.turns.pop_front();
+        }
+        self.turns.push_back(tick);
+        if self.turns.len() > MAX_TURNS_PER_SECOND {
+            violations.push(Violation::TurnRate {
+                hash,
+                tick,
+                turns: self.turns.len(),
+            });
+        }
+
+        if let Some((x, y)) = self.position(tick) {
+            self.anchor = Some((x, y, tick));
+        }
+        self.heading = heading;
+        violations
+    }
+
+    fn boost(&mut self, tick: i64) {
+        // The contract ignores boosts that are still cooling down
+        if tick < self.boost_ready {
+            return;
+        }
+        if let Some((x, y)) = self.position(tick) {
+            self.anchor = Some((x, y, tick));
+        }
+        self.boost = Boost::starting_at(tick);
+        self.boost_ready = tick + BOOST_COOLDOWN;
+    }
+}
+
+/// Checks every address's transactions against what a fair client sends.
+///
+/// Transactions are fed in commit order through `observe`, and the contract
+/// calls among them through `replay` along with the state of their sender,
+/// which only the contract knows.
+#[derive(Default)]
+pub struct Verifier {
+    timestamps: HashMap<u64, u64>,
+    replays: HashMap<u64, Replay>,
+    reports: BTreeMap<u64, Report>,
+}
+
+impl Verifier {
+    /// Record a transaction, checking it isn't dated before its parents.
+    /// Parents the verifier hasn't seen are skipped.
+    pub fn observe(&mut self, hash: u64, address: u64, timestamp: u64, parents: [u64; 2]) {
+        let mut violations = Vec::new();
+        for parent in parents.iter() {
+            if let Some(parent_timestamp) = self.timestamps.get(parent) {
+                if timestamp < *parent_timestamp {
+                    violations.push(Violation::BackDated {
+                        hash,
+                        timestamp,
+                        parent: *parent,
+                        parent_timestamp: *parent_timestamp,
+                    });
+                }
+            }
+        }
+        self.timestamps.insert(hash, timestamp);
+
+        let report = self.report_mut(address);
+        report.transactions += 1;
+        report.violations.extend(violations);
+    }
+
+    /// Replay a contract call by a player
+    pub fn replay(&mut self, call: &Call, sender: Sender) {
+        let tick = ms_delta_to_tick(
+            call.timestamp as i64 - sender.start_time as i64 - sender.paused_ms as i64,
+        );
+        let value = |v: &ContractValue| unwrap_contract_u64(*v) as i64;
+        let replay = self.replays.entry(call.address).or_insert_with(Replay::new);
+        if !sender.alive {
+            // Killed since their last call, or never spawned
+            replay.anchor = None;
+        }
+
+        let violations = match (call.func_name, call.args) {
+            ("spawn_player", [x, y]) => {
+                replay.spawn(call.hash, value(x), value(y), tick, sender.alive)
+            }
+            // Calls from dead players change nothing
+            ("apply_input", [heading]) if sender.alive => {
+                replay.turn(call.hash, value(heading), tick)
+            }
+            ("activate_boost", []) if sender.alive => {
+                replay.boost(tick);
+                Vec::new()
+            }
+            _ => Vec::new(),
+        };
+        self.report_mut(call.address).violations.extend(violations);
+    }
+
+    pub fn report(&self, address: u64) -> Option<&Report> {
+        self.reports.get(&address)
+    }
+
+    /// Reports for every address seen, in address order
+    pub fn reports(&self) -> Vec<Report> {
+        self.reports.values().cloned().collect()
+    }
+
+    fn report_mut(&mut self, address: u64) -> &mut Report {
+        self.reports.entry(address).or_insert_with(|| Report {
+            address,
+            ..Report::default()
+        })
+    }
+}
+
+/// Replay a DAG's transactions, in commit order, through a fresh copy of
+/// the contract at `contract_address`, and report on every address that
+/// sent one
+pub fn verify_history(history: &[Transaction], contract_address: u64) -> Vec<Report> {
+    let key = new_key_pair();
+    let mut contract =
+        GossipNode::new(local_dag(get_address(&get_public_key(&key))), NullTransport);
+    let mut verifier = Verifier::default();
+
+    for trans in history {
+        verifier.observe(
+            trans.get_hash(),
+            trans.get_address(),
+            trans.get_timestamp(),
+            [trans.get_trunk_hash(), trans.get_branch_hash()],
+        );
+        match trans.get_data() {
+            TransactionData::ExecContract {
+                func_name,
+                args,
+                contract: address,
+            } if *address == contract_address => {
+                let mut get = |func_name, args: &[ContractValue]| {
+                    contract
+                        .query(&key, contract_address, func_name, args)
+                        .ok()
+                        .flatten()
+                        .map_or(0, unwrap_contract_u64)
+                };
+                let sender = ContractValue::U64(trans.get_address());
+                let game = ContractValue::U64(get("get_player_game", &[sender]));
+                let state = Sender {
+                    alive: get("get_player_alive", &[sender]) == 1,
+                    start_time: get("get_start_time", &[game]),
+                    paused_ms: get("get_paused_ms", &[game]),
+                };
+                verifier.replay(
+                    &Call {
+                        hash: trans.get_hash(),
+                        address: trans.get_address(),
+                        timestamp: trans.get_timestamp(),
+                        func_name,
+                        args,
+                    },
+                    state,
+                );
+            }
+            _ => (),
+        }
+        contract.replicate(trans.clone());
+    }
+
+    verifier.reports()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYER: u64 = 42;
+
+    /// Contract coordinate of the grid line `cells` cells from the origin
+    fn line(cells: i64) -> ContractValue {
+        ContractValue::U64((GRID_ORIGIN + cells * CELL_WIDTH) as u64)
+    }
+
+    fn call<'a>(
+        hash: u64,
+        timestamp: u64,
+        func_name: &'a str,
+        args: &'a [ContractValue],
+    ) -> Call<'a> {
+        Call {
+            hash,
+            address: PLAYER,
+            timestamp,
+            func_name,
+            args,
+        }
+    }
+
+    fn alive(alive: bool) -> Sender {
+        Sender {
+            alive,
+            start_time: 0,
+            paused_ms: 0,
+        }
+    }
+
+    #[test]
+    fn test_fair_player_is_clean() {
+        let mut verifier = Verifier::default();
+        let spawn = [line(10), line(20)];
+        verifier.replay(&call(1, 0, "spawn_player", &spawn), alive(false));
+        for (i, heading) in [0, 3, 0, 1, 2].iter().enumerate() {
+            let args = [ContractValue::U64(*heading)];
+            let timestamp = 500 * (i as u64 + 1);
+            verifier.replay(
+                &call(2 + i as u64, timestamp, "apply_input", &args),
+                alive(true),
+            );
+        }
+        verifier.replay(&call(9, 3000, "activate_boost", &[]), alive(true));
+        assert!(verifier.report(PLAYER).unwrap().is_clean());
+    }
+
+    #[test]
+    fn test_back_dated_transaction() {
+        let mut verifier = Verifier::default();
+        verifier.observe(1, 7, 1000, [0, 0]);
+        verifier.observe(2, 8, 1200, [1, 1]);
+        verifier.observe(3, PLAYER, 900, [2, 1]);
+
+        assert!(verifier.report(8).unwrap().is_clean());
+        let report = verifier.report(PLAYER).unwrap();
+        assert_eq!(report.transactions, 1);
+        assert_eq!(
+            report.violations,
+            vec![
+                Violation::BackDated {
+                    hash: 3,
+                    timestamp: 900,
+                    parent: 2,
+                    parent_timestamp: 1200
+                },
+                Violation::BackDated {
+                    hash: 3,
+                    timestamp: 900,
+                    parent: 1,
+                    parent_timestamp: 1000
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_impossible_turns() {
+        let mut verifier = Verifier::default();
+        let spawn = [line(10), line(20)];
+        verifier.replay(&call(1, 0, "spawn_player", &spawn), alive(false));
+
+        let up = [ContractValue::U64(0)];
+        let left = [ContractValue::U64(2)];
+        let right = [ContractValue::U64(3)];
+        verifier.replay(&call(2, 100, "apply_input", &up), alive(true));
+        verifier.replay(&call(3, 200, "apply_input", &left), alive(true));
+        assert_eq!(
+            verifier.report(PLAYER).unwrap().violations,
+            vec![Violation::SameAxisTurn {
+                hash: 3,
+                from: 0,
+                to: 2
+            }]
+        );
+
+        // Zig-zagging every frame
+        for i in 0..20 {
+            let args = if i % 2 == 0 { &right } else { &left };
+            verifier.replay(
+                &call(10 + i, 300 + 17 * i, "apply_input", args),
+                alive(true),
+            );
+        }
+        assert!(verifier
+            .report(PLAYER)
+            .unwrap()
+            .violations
+            .iter()
+            .any(|v| match v {
+                Violation::TurnRate { turns, .. } => *turns > MAX_TURNS_PER_SECOND,
+                _ => false,
+            }));
+    }
+
+    #[test]
+    fn test_teleport_spawns() {
+        let mut verifier = Verifier::default();
+        let spawn = [line(10), line(20)];
+        verifier.replay(&call(1, 0, "spawn_player", &spawn), alive(false));
+        let right = [ContractValue::U64(3)];
+        verifier.replay(&call(2, 0, "apply_input", &right), alive(true));
+
+        // One second later, at 60 units a second, respawning far away
+        let jump = [line(200), line(200)];
+        verifier.replay(&call(3, 1000, "spawn_player", &jump), alive(true));
+        // Half a cell off the grid lines
+        let off = [
+            ContractValue::U64((GRID_ORIGIN + CELL_WIDTH / 2) as u64),
+            line(5),
+        ];
+        verifier.replay(&call(4, 2000, "spawn_player", &off), alive(false));
+
+        let (x, y) = (GRID_ORIGIN + 10 * CELL_WIDTH, GRID_ORIGIN + 20 * CELL_WIDTH);
+        assert_eq!(
+            verifier.report(PLAYER).unwrap().violations,
+            vec![
+                Violation::Teleport {
+                    hash: 3,
+                    from: Some((x + 60, y)),
+                    to: (
+                        GRID_ORIGIN + 200 * CELL_WIDTH,
+                        GRID_ORIGIN + 200 * CELL_WIDTH
+                    ),
+                },
+                Violation::BadSpawn {
+                    hash: 4,
+                    x: GRID_ORIGIN + CELL_WIDTH / 2,
+                    y: GRID_ORIGIN + 5 * CELL_WIDTH,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pauses_hold_players() {
+        let mut verifier = Verifier::default();
+        let spawn = [line(10), line(20)];
+        verifier.replay(&call(1, 0, "spawn_player", &spawn), alive(false));
+        let right = [ContractValue::U64(3)];
+        verifier.replay(&call(2, 0, "apply_input", &right), alive(true));
+
+        // One second of play either side of a five second pause
+        let paused = Sender {
+            paused_ms: 5000,
+            ..alive(true)
+        };
+        let jump = [line(200), line(200)];
+        verifier.replay(&call(3, 7000, "spawn_player", &jump), paused);
+
+        let (x, y) = (GRID_ORIGIN + 10 * CELL_WIDTH, GRID_ORIGIN + 20 * CELL_WIDTH);
+        assert_eq!(
+            verifier.report(PLAYER).unwrap().violations,
+            vec![Violation::Teleport {
+                hash: 3,
+                from: Some((x + 120, y)),
+                to: (
+                    GRID_ORIGIN + 200 * CELL_WIDTH,
+                    GRID_ORIGIN + 200 * CELL_WIDTH
+                ),
+            }]
+        );
+    }
+}
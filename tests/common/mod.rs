@@ -0,0 +1,37 @@
+//! Setup shared by the integration tests
+
+#[path = "../../src/tools/build.rs"]
+mod build;
+
+use std::path::PathBuf;
+use std::sync::Once;
+use std::{env, fs};
+
+use rustdag_lib::dag::contract::source::ContractSource;
+
+static BUILD: Once = Once::new();
+
+/// Compiled contract. `P2PIO_CONTRACT_WASM` can point at a prebuilt one,
+/// otherwise it is built with cargo the first time a test binary asks for it.
+/// The artifact is cached in the target directory, so later runs only
+/// rebuild when the contract has changed.
+pub fn contract_wasm() -> PathBuf {
+    if let Some(path) = env::var_os("P2PIO_CONTRACT_WASM") {
+        return path.into();
+    }
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    BUILD.call_once(|| {
+        log::debug!("Building contract.");
+        if let Err(e) = build::build_contract(&root) {
+            panic!("{}", e);
+        }
+    });
+    build::built_contract(&root)
+}
+
+pub fn contract_source() -> ContractSource {
+    let path = contract_wasm();
+    let bytes =
+        fs::read(&path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path.display(), e));
+    ContractSource::with_vec(bytes)
+}
@@ -0,0 +1,225 @@
+mod common;
+
+use p2pio::gossip::{
+    local_dag, GossipMessage, GossipNode, LocalNode, MemoryNetwork, NullTransport, Transport,
+};
+
+use rustdag_lib::{
+    dag::contract::ContractValue::U64,
+    security::keys::eddsa::{get_address, get_public_key, new_key_pair},
+};
+
+use common::contract_source;
+
+#[test]
+fn test_gossip_between_peers() {
+    let _ = simple_logger::init_with_level(log::Level::Info);
+
+    let network = MemoryNetwork::new();
+    let contract_key = new_key_pair();
+    let contract_id = get_address(&get_public_key(&contract_key));
+    let key1 = new_key_pair();
+    let key2 = new_key_pair();
+    let addr1 = get_address(&get_public_key(&key1));
+
+    let mut peer1 = GossipNode::new(local_dag(addr1), network.join());
+    peer1.deploy(&contract_key, contract_source()).unwrap();
+    peer1
+        .execute(&key1, contract_id, "spawn_player", &[U64(10), U64(20)])
+        .unwrap();
+
+    // A late joiner receives the full history once its connection opens
+    let mut peer2 = GossipNode::new(
+        local_dag(get_address(&get_public_key(&key2))),
+        network.join(),
+    );
+    assert!(peer2.poll().is_empty());
+    assert_eq!(peer1.poll().len(), 0);
+    assert_eq!(peer2.poll().len(), 2);
+    assert_eq!(peer2.len(), peer1.len());
+
+    let x = peer2
+        .query(&key2, contract_id, "get_player_x", &[U64(addr1)])
+        .unwrap();
+    assert_eq!(x, Some(U64(10)));
+
+    // New transactions are gossiped as they are made
+    peer2
+        .execute(&key2, contract_id, "spawn_player", &[U64(30), U64(40)])
+        .unwrap();
+    let committed = peer1.poll();
+    assert_eq!(committed.len(), 1);
+    assert_eq!(
+        committed[0].get_address(),
+        get_address(&get_public_key(&key2))
+    );
+
+    // Queries don't create transactions
+    assert_eq!(peer1.len(), 3);
+    assert_eq!(peer2.len(), 3);
+}
+
+#[test]
+fn test_gossip_deduplicates_flooded_transactions() {
+    let _ = simple_logger::init_with_level(log::Level::Info);
+
+    let network = MemoryNetwork::new();
+    let contract_key = new_key_pair();
+    let keys = (0..3).map(|_| new_key_pair()).collect::<Vec<_>>();
+    let mut peers = keys
+        .iter()
+        .map(|k| GossipNode::new(local_dag(get_address(&get_public_key(k))), network.join()))
+        .collect::<Vec<_>>();
+
+    peers[0].deploy(&contract_key, contract_source()).unwrap();
+    for _ in 0..2 {
+        for peer in peers.iter_mut() {
+            peer.poll();
+        }
+    }
+    assert!(peers.iter().all(|p| p.len() == 1));
+
+    // Messages arriving twice, once directly and once re-gossiped, are committed once
+    for peer in peers.iter_mut() {
+        peer.poll();
+    }
+    assert!(peers.iter().all(|p| p.len() == 1));
+}
+
+#[test]
+fn test_replica_executes_transactions_locally() {
+    let _ = simple_logger::init_with_level(log::Level::Info);
+
+    let network = MemoryNetwork::new();
+    let contract_key = new_key_pair();
+    let contract_id = get_address(&get_public_key(&contract_key));
+    let key = new_key_pair();
+    let addr = get_address(&get_public_key(&key));
+    let query_key = new_key_pair();
+
+    let mut origin = GossipNode::new(local_dag(addr), network.join());
+    origin.deploy(&contract_key, contract_source()).unwrap();
+    let (_, spawn) = origin
+        .execute(&key, contract_id, "spawn_player", &[U64(10), U64(20)])
+        .unwrap();
+
+    // The replica only sees transactions, and derives updates by executing them
+    let mut replica = GossipNode::new(
+        local_dag(get_address(&get_public_key(&query_key))),
+        NullTransport,
+    );
+    let mut other = GossipNode::new(local_dag(addr), network.join());
+    origin.poll();
+    let history = other.poll();
+    assert_eq!(history.len(), 2);
+    for trans in history {
+        assert!(replica.replicate(trans));
+    }
+    assert!(!replica.replicate(spawn));
+
+    let y = replica
+        .query(&query_key, contract_id, "get_player_y", &[U64(addr)])
+        .unwrap();
+    assert_eq!(y, Some(U64(20)));
+    assert_eq!(replica.len(), 2);
+}
+
+#[test]
+fn test_gossip_executes_transactions_out_of_order() {
+    let _ = simple_logger::init_with_level(log::Level::Info);
+
+    let contract_key = new_key_pair();
+    let contract_id = get_address(&get_public_key(&contract_key));
+    let key = new_key_pair();
+    let addr = get_address(&get_public_key(&key));
+
+    let mut origin = GossipNode::new(local_dag(addr), NullTransport);
+    origin.deploy(&contract_key, contract_source()).unwrap();
+    origin
+        .execute(&key, contract_id, "spawn_player", &[U64(10), U64(20)])
+        .unwrap();
+
+    // Only the transactions travel, newest first, and the peer executes
+    // each one itself once its parents are in
+    let network = MemoryNetwork::new();
+    let mut sender = network.join();
+    let mut peer = GossipNode::new(local_dag(addr), network.join());
+    let mut history = origin.history();
+    history.reverse();
+    let message = serde_json::to_vec(&GossipMessage::Sync(history)).unwrap();
+    sender.send(1, &message);
+
+    assert_eq!(peer.poll().len(), 2);
+    let y = peer
+        .query(&key, contract_id, "get_player_y", &[U64(addr)])
+        .unwrap();
+    assert_eq!(y, Some(U64(20)));
+}
+
+#[test]
+fn test_replica_retries_transactions_before_their_parents() {
+    let _ = simple_logger::init_with_level(log::Level::Info);
+
+    let contract_key = new_key_pair();
+    let contract_id = get_address(&get_public_key(&contract_key));
+    let key = new_key_pair();
+    let addr = get_address(&get_public_key(&key));
+
+    let mut origin = GossipNode::new(local_dag(addr), NullTransport);
+    origin.deploy(&contract_key, contract_source()).unwrap();
+    origin
+        .execute(&key, contract_id, "spawn_player", &[U64(10), U64(20)])
+        .unwrap();
+    let history = origin.history();
+
+    // The spawn can't run before the contract is deployed, so it waits
+    let mut replica = GossipNode::new(local_dag(addr), NullTransport);
+    assert!(!replica.replicate(history[1].clone()));
+    assert!(replica.is_empty());
+    assert!(replica.missing_parents().contains(&history[0].get_hash()));
+    assert!(replica.replicate(history[0].clone()));
+    assert_eq!(replica.len(), 2);
+    assert!(replica.missing_parents().is_empty());
+
+    let y = replica
+        .query(&key, contract_id, "get_player_y", &[U64(addr)])
+        .unwrap();
+    assert_eq!(y, Some(U64(20)));
+}
+
+#[test]
+fn test_poll_each_reads_state_as_each_commit_left_it() {
+    let _ = simple_logger::init_with_level(log::Level::Info);
+
+    let network = MemoryNetwork::new();
+    let contract_key = new_key_pair();
+    let contract_id = get_address(&get_public_key(&contract_key));
+    let key1 = new_key_pair();
+    let key2 = new_key_pair();
+    let addr2 = get_address(&get_public_key(&key2));
+
+    let mut origin = GossipNode::new(
+        local_dag(get_address(&get_public_key(&key1))),
+        network.join(),
+    );
+    origin.deploy(&contract_key, contract_source()).unwrap();
+    origin
+        .execute(&key1, contract_id, "spawn_player", &[U64(10), U64(20)])
+        .unwrap();
+    origin
+        .execute(&key2, contract_id, "spawn_player", &[U64(30), U64(40)])
+        .unwrap();
+
+    // The whole history arrives in one batch, but each commit is seen before
+    // the next one is made
+    let mut peer = GossipNode::new(local_dag(addr2), network.join());
+    origin.poll();
+    let mut seen = Vec::new();
+    peer.poll_each(&mut |node, _| {
+        let x = node
+            .query(&key2, contract_id, "get_player_x", &[U64(addr2)])
+            .unwrap();
+        seen.push((node.history().len(), x == Some(U64(30))));
+    });
+    assert_eq!(seen, vec![(1, false), (2, false), (3, true)]);
+}
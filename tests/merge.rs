@@ -0,0 +1,203 @@
+mod common;
+
+use std::thread;
+use std::time::Duration;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use p2pio::gossip::{local_dag, GossipNode, LocalNode, NullTransport};
+
+use rustdag_lib::{
+    dag::{
+        contract::{ContractValue, ContractValue::U64},
+        transaction::Transaction,
+    },
+    security::keys::eddsa::{get_address, get_public_key, new_key_pair},
+};
+
+use common::contract_source;
+
+/// Random interleaving of two branches, keeping each branch in order
+fn interleave(rng: &mut StdRng, a: &[Transaction], b: &[Transaction]) -> Vec<Transaction> {
+    let (mut a, mut b) = (a.iter(), b.iter());
+    let (mut left_a, mut left_b) = (a.len(), b.len());
+    let mut merged = Vec::new();
+    while left_a + left_b > 0 {
+        if rng.gen_range(0, left_a + left_b) < left_a {
+            merged.extend(a.next().cloned());
+            left_a -= 1;
+        } else {
+            merged.extend(b.next().cloned());
+            left_b -= 1;
+        }
+    }
+    merged
+}
+
+/// Turns on the contract, as `PlayerHeading` numbers them
+const DOWN: u64 = 1;
+const LEFT: u64 = 2;
+const RIGHT: u64 = 3;
+
+/// Time between turns: 90 ticks, so the player leaves their territory and
+/// lays some trail, and a branch merged late is far behind the other
+const TURN_GAP: Duration = Duration::from_millis(1500);
+
+#[test]
+fn test_merge_order_does_not_change_state() {
+    let _ = simple_logger::init_with_level(log::Level::Info);
+
+    let contract_key = new_key_pair();
+    let contract_id = get_address(&get_public_key(&contract_key));
+    let key = new_key_pair();
+    let id = get_address(&get_public_key(&key));
+
+    let middle = u64::from(u32::MAX) + 150 * 40;
+
+    // The same player turning on two peers that can't see each other
+    let mut peer1 = GossipNode::new(local_dag(id), NullTransport);
+    peer1.deploy(&contract_key, contract_source()).unwrap();
+    peer1
+        .execute(
+            &key,
+            contract_id,
+            "spawn_player",
+            &[U64(middle), U64(middle)],
+        )
+        .unwrap();
+    let base = peer1.history();
+    let mut peer2 = GossipNode::new(local_dag(id), NullTransport);
+    for trans in base.iter() {
+        assert!(peer2.replicate(trans.clone()));
+    }
+
+    // Turns alternate between the peers, so each branch has turns from
+    // before and after the other's
+    let mut branch1 = Vec::new();
+    let mut branch2 = Vec::new();
+    for (i, heading) in [RIGHT, DOWN, LEFT, DOWN].iter().enumerate() {
+        thread::sleep(TURN_GAP);
+        let (peer, branch) = if i % 2 == 0 {
+            (&mut peer1, &mut branch1)
+        } else {
+            (&mut peer2, &mut branch2)
+        };
+        let (value, trans) = peer
+            .execute(&key, contract_id, "apply_input", &[U64(*heading)])
+            .unwrap();
+        assert_eq!(value, Some(U64(0)));
+        branch.push(trans);
+    }
+
+    // Positions move on with the clock each replica is read at, so compare
+    // what the turns left behind instead: the heading, the trail and the
+    // territory around the player
+    let get = |peer: &mut GossipNode<_, _>, func_name: &str, args: &[ContractValue]| -> u64 {
+        match peer.query(&key, contract_id, func_name, args).unwrap() {
+            Some(U64(value)) => value,
+            other => panic!("Unexpected return from {}: {:?}", func_name, other),
+        }
+    };
+    let state = |peer: &mut GossipNode<_, _>| -> (u64, Vec<u64>, Vec<u64>) {
+        let heading = get(peer, "get_player_heading", &[U64(id)]);
+        let trail = (0..get(peer, "get_player_trail_len", &[U64(id)]))
+            .map(|i| get(peer, "get_player_trail_cell", &[U64(id), U64(i)]))
+            .collect();
+        let cells = (145..155)
+            .flat_map(|row| (145..155).map(move |col| (col, row)))
+            .map(|(col, row)| get(peer, "get_cell_owner", &[U64(0), U64(col), U64(row)]))
+            .collect();
+        (heading, trail, cells)
+    };
+
+    for seed in 0..8 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let states = (0..4)
+            .map(|_| {
+                let mut peer = GossipNode::new(local_dag(id), NullTransport);
+                for trans in base
+                    .iter()
+                    .chain(interleave(&mut rng, &branch1, &branch2).iter())
+                {
+                    assert!(peer.replicate(trans.clone()));
+                }
+                state(&mut peer)
+            })
+            .collect::<Vec<_>>();
+        assert!(!states[0].1.is_empty(), "No trail was laid");
+        for state in states.iter() {
+            assert_eq!(state, &states[0], "Seed {}", seed);
+        }
+    }
+}
+
+#[test]
+fn test_pause_and_turn_on_concurrent_branches() {
+    let _ = simple_logger::init_with_level(log::Level::Info);
+
+    let contract_key = new_key_pair();
+    let contract_id = get_address(&get_public_key(&contract_key));
+    let key = new_key_pair();
+    let id = get_address(&get_public_key(&key));
+
+    let middle = u64::from(u32::MAX) + 150 * 40;
+
+    let mut peer1 = GossipNode::new(local_dag(id), NullTransport);
+    peer1.deploy(&contract_key, contract_source()).unwrap();
+    peer1
+        .execute(
+            &key,
+            contract_id,
+            "spawn_player",
+            &[U64(middle), U64(middle)],
+        )
+        .unwrap();
+    let base = peer1.history();
+    let mut peer2 = GossipNode::new(local_dag(id), NullTransport);
+    for trans in base.iter() {
+        assert!(peer2.replicate(trans.clone()));
+    }
+
+    // The player turns on one peer, and the owner pauses the game on the
+    // other after it, before either has seen the other's call
+    thread::sleep(TURN_GAP);
+    let (value, turn) = peer1
+        .execute(&key, contract_id, "apply_input", &[U64(RIGHT)])
+        .unwrap();
+    assert_eq!(value, Some(U64(0)));
+    thread::sleep(TURN_GAP);
+    let (value, pause) = peer2
+        .execute(&contract_key, contract_id, "pause_game", &[U64(0)])
+        .unwrap();
+    assert_eq!(value, Some(U64(0)));
+
+    // The clock is stopped, so positions can be compared too
+    let get = |peer: &mut GossipNode<_, _>, func_name: &str, args: &[ContractValue]| -> u64 {
+        match peer.query(&key, contract_id, func_name, args).unwrap() {
+            Some(U64(value)) => value,
+            other => panic!("Unexpected return from {}: {:?}", func_name, other),
+        }
+    };
+    let state = |order: &[&Transaction]| -> Vec<u64> {
+        let mut peer = GossipNode::new(local_dag(id), NullTransport);
+        for trans in base.iter().chain(order.iter().cloned()) {
+            assert!(peer.replicate(trans.clone()));
+        }
+        let mut state = [
+            "get_player_heading",
+            "get_player_x",
+            "get_player_y",
+            "get_player_trail_len",
+        ]
+        .iter()
+        .map(|func_name| get(&mut peer, func_name, &[U64(id)]))
+        .collect::<Vec<_>>();
+        state.push(get(&mut peer, "get_paused", &[U64(0)]));
+        state
+    };
+
+    let merged = state(&[&turn, &pause]);
+    assert_eq!(merged[0], RIGHT);
+    assert_eq!(merged[4], 1);
+    assert_eq!(state(&[&pause, &turn]), merged);
+}
@@ -1,5 +1,6 @@
+mod common;
+
 use std::collections::{HashMap, VecDeque};
-use std::fs;
 
 use rand::{Rng, SeedableRng};
 
@@ -7,13 +8,15 @@ use rustdag_lib::{
     dag::{
         blockdag::BlockDAG,
         consensus::key::KeyTipManager,
-        contract::{runtime::wasmi::WasmiRuntime, source::ContractSource, ContractValue::U64},
+        contract::{runtime::wasmi::WasmiRuntime, ContractValue::U64},
         generic_blockdag::GenericBlockDAG,
         transaction::{updates::TransactionUpdates, Transaction},
     },
     security::keys::eddsa::{get_address, get_public_key, new_key_pair, EdDSAKeyPair},
 };
 
+use common::contract_source;
+
 fn spawn<'a, DAG: 'a + BlockDAG<'a>>(
     dag: &'a mut DAG,
     key: &EdDSAKeyPair,
@@ -99,20 +102,7 @@ fn test_random() {
 
 
     // Get Contract Source
-    let path = "contract/pkg/p2pio_contract_bg.wasm";
-    let contract_src_bytes = fs::read(path).unwrap_or_else(|_| {
-        log::debug!("Generating wasm for contract.");
-        std::process::Command::new("wasm-pack")
-            .arg("build")
-            .current_dir("contract")
-            .output()
-            .expect("Failed to run wasm-pack build for contract.");
-        fs::read(path).expect(&format!(
-            "Failed to generate {} using wasm-pack build.",
-            path
-        ))
-    });
-    let contract_src = ContractSource::with_vec(contract_src_bytes);
+    let contract_src = contract_source();
 
 
     for _ in 0..num_repeats {
@@ -232,20 +222,7 @@ fn test_random() {
 fn test_same_depth_ancestors() {
     let _ = simple_logger::init_with_level(log::Level::Debug);
 
-    let path = "contract/pkg/p2pio_contract_bg.wasm";
-    let contract_src_bytes = fs::read(path).unwrap_or_else(|_| {
-        log::debug!("Generating wasm for contract.");
-        std::process::Command::new("wasm-pack")
-            .arg("build")
-            .current_dir("contract")
-            .output()
-            .expect("Failed to run wasm-pack build for contract.");
-        fs::read(path).expect(&format!(
-            "Failed to generate {} using wasm-pack build.",
-            path
-        ))
-    });
-    let contract_src = ContractSource::with_vec(contract_src_bytes);
+    let contract_src = contract_source();
 
     let contract_key = new_key_pair();
     let contract_id = get_address(&get_public_key(&contract_key));
@@ -301,20 +278,7 @@ fn test_same_depth_ancestors() {
 fn test_double_ancestor() {
     let _ = simple_logger::init_with_level(log::Level::Debug);
 
-    let path = "contract/pkg/p2pio_contract_bg.wasm";
-    let contract_src_bytes = fs::read(path).unwrap_or_else(|_| {
-        log::debug!("Generating wasm for contract.");
-        std::process::Command::new("wasm-pack")
-            .arg("build")
-            .current_dir("contract")
-            .output()
-            .expect("Failed to run wasm-pack build for contract.");
-        fs::read(path).expect(&format!(
-            "Failed to generate {} using wasm-pack build.",
-            path
-        ))
-    });
-    let contract_src = ContractSource::with_vec(contract_src_bytes);
+    let contract_src = contract_source();
 
     let contract_key = new_key_pair();
     let contract_id = get_address(&get_public_key(&contract_key));
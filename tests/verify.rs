@@ -0,0 +1,69 @@
+mod common;
+
+use p2pio::gossip::{local_dag, GossipNode, LocalNode, NullTransport};
+use p2pio::verify::{verify_history, Violation};
+
+use rustdag_lib::{
+    dag::contract::ContractValue::U64,
+    security::keys::eddsa::{get_address, get_public_key, new_key_pair},
+};
+
+use common::contract_source;
+
+/// Contract coordinate of a grid line, as the client sends it
+fn line(cells: u64) -> u64 {
+    u64::from(u32::MAX) + cells * 40
+}
+
+#[test]
+fn test_verify_flags_teleporting_player() {
+    let _ = simple_logger::init_with_level(log::Level::Info);
+
+    let contract_key = new_key_pair();
+    let contract_id = get_address(&get_public_key(&contract_key));
+    let cheat_key = new_key_pair();
+    let fair_key = new_key_pair();
+    let cheat = get_address(&get_public_key(&cheat_key));
+    let fair = get_address(&get_public_key(&fair_key));
+
+    let mut peer = GossipNode::new(local_dag(cheat), NullTransport);
+    peer.deploy(&contract_key, contract_source()).unwrap();
+    peer.execute(
+        &fair_key,
+        contract_id,
+        "spawn_player",
+        &[U64(line(50)), U64(line(50))],
+    )
+    .unwrap();
+    peer.execute(&fair_key, contract_id, "apply_input", &[U64(0)])
+        .unwrap();
+    peer.execute(
+        &cheat_key,
+        contract_id,
+        "spawn_player",
+        &[U64(line(10)), U64(line(10))],
+    )
+    .unwrap();
+    // Jumping across the board without dying first
+    peer.execute(
+        &cheat_key,
+        contract_id,
+        "spawn_player",
+        &[U64(line(200)), U64(line(200))],
+    )
+    .unwrap();
+
+    let reports = verify_history(&peer.history(), contract_id);
+    let report = |address| reports.iter().find(|r| r.address == address).unwrap();
+
+    assert!(report(fair).is_clean());
+    assert_eq!(report(fair).transactions, 2);
+    let cheat_report = report(cheat);
+    assert_eq!(cheat_report.violations.len(), 1);
+    match &cheat_report.violations[0] {
+        Violation::Teleport { to, .. } => {
+            assert_eq!(*to, (line(200) as i64, line(200) as i64))
+        }
+        other => panic!("Expected a teleport, got {:?}", other),
+    }
+}
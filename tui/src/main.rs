@@ -0,0 +1,226 @@
+//! Terminal client for a p2pio contract, for debugging without a browser.
+//! Plays on a DAG in this process against scripted opponents, or on a
+//! rustdag node over HTTP.
+
+mod view;
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use clap::{value_t, App, Arg};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self as term, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::Print,
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use p2pio::event_from_transaction;
+use p2pio::tools::{code, contract_wasm, Backend, Bot, InProcess, Remote};
+use p2pio_contract::{heading::PlayerHeading, CELL_WIDTH, GRID_COUNT, GRID_ORIGIN};
+
+use rustdag_lib::{
+    dag::contract::ContractValue,
+    security::keys::eddsa::{get_address, get_public_key, new_key_pair, EdDSAKeyPair},
+};
+
+use view::View;
+
+const FRAME: Duration = Duration::from_millis(100);
+/// Cells from the middle of the grid bots spawn within, so they show up
+/// near a player who just spawned there too
+const SPAWN_SPREAD: i64 = 20;
+
+/// Puts the terminal back the way it was, even if we panic
+struct Screen;
+
+impl Screen {
+    fn enter() -> crossterm::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        Ok(Screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+enum Action {
+    Turn(PlayerHeading),
+    Spawn,
+    Quit,
+}
+
+fn action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Up => Some(Action::Turn(PlayerHeading::Up)),
+        KeyCode::Down => Some(Action::Turn(PlayerHeading::Down)),
+        KeyCode::Left => Some(Action::Turn(PlayerHeading::Left)),
+        KeyCode::Right => Some(Action::Turn(PlayerHeading::Right)),
+        KeyCode::Char(' ') => Some(Action::Turn(PlayerHeading::NoHeading)),
+        KeyCode::Enter => Some(Action::Spawn),
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(Action::Quit),
+        KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+        _ => None,
+    }
+}
+
+fn main() {
+    let matches = App::new("p2pio-tui")
+        .about("Plays a p2pio contract in the terminal")
+        .arg(
+            Arg::with_name("node")
+                .long("node")
+                .short("n")
+                .takes_value(true)
+                .requires("contract")
+                .help("URL of a rustdag node. Without one, a DAG is run in this process."),
+        )
+        .arg(
+            Arg::with_name("contract")
+                .long("contract")
+                .short("c")
+                .takes_value(true)
+                .help("Address of the contract deployed on the node"),
+        )
+        .arg(
+            Arg::with_name("wasm")
+                .long("wasm")
+                .takes_value(true)
+                .help("Contract to deploy on the in-process DAG. Defaults to the module `p2pio build` leaves."),
+        )
+        .arg(
+            Arg::with_name("bots")
+                .long("bots")
+                .short("b")
+                .takes_value(true)
+                .help("Scripted opponents to run. Defaults to 3 in process and 0 on a node."),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .help("Seed for the opponents' spawn points and turns"),
+        )
+        .get_matches();
+
+    let _ = simple_logger::init_with_level(log::Level::Error);
+    let seed = if matches.is_present("seed") {
+        value_t!(matches, "seed", u64).unwrap_or_else(|e| e.exit())
+    } else {
+        rand::random()
+    };
+
+    let (mut backend, default_bots): (Box<dyn Backend>, usize) = match matches.value_of("node") {
+        Some(url) => {
+            let contract = value_t!(matches, "contract", u64).unwrap_or_else(|e| e.exit());
+            (Box::new(Remote::new(url, contract)), 0)
+        }
+        None => match InProcess::deploy(
+            &matches
+                .value_of("wasm")
+                .map_or_else(contract_wasm, PathBuf::from),
+        ) {
+            Ok(backend) => (Box::new(backend), 3),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+    };
+    let bots = if matches.is_present("bots") {
+        value_t!(matches, "bots", usize).unwrap_or_else(|e| e.exit())
+    } else {
+        default_bots
+    };
+
+    let result = Screen::enter()
+        .map_err(|e| e.to_string())
+        .and_then(|_screen| run(backend.as_mut(), bots, seed));
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(backend: &mut dyn Backend, bots: usize, seed: u64) -> Result<(), String> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let start = Instant::now();
+    let mut bots = (0..bots)
+        .map(|_| (Bot::new(new_key_pair(), SPAWN_SPREAD), start))
+        .collect::<Vec<_>>();
+    let key = new_key_pair();
+    let mut view = View::new(get_address(&get_public_key(&key)));
+    spawn(backend, &key, &mut view)?;
+
+    loop {
+        if term::poll(FRAME).map_err(|e| e.to_string())? {
+            if let term::Event::Key(key_event) = term::read().map_err(|e| e.to_string())? {
+                match action(key_event) {
+                    Some(Action::Turn(heading)) => {
+                        let args = [ContractValue::U64(heading.as_i64() as u64)];
+                        let code = code(backend.call(&key, "apply_input", &args)?);
+                        if code != 0 {
+                            view.note(format!("apply_input returned {}", code));
+                        }
+                    }
+                    Some(Action::Spawn) => spawn(backend, &key, &mut view)?,
+                    Some(Action::Quit) => return Ok(()),
+                    None => (),
+                }
+            }
+        }
+
+        let now = Instant::now();
+        for (bot, next_call) in bots.iter_mut() {
+            if now >= *next_call {
+                *next_call = now + Duration::from_millis(rng.gen_range(300, 1500));
+                bot.step(backend, &mut rng)?;
+            }
+        }
+
+        // The same stream of events `Context` hands the browser
+        let contract = backend.contract();
+        for trans in backend.sync()? {
+            if let Some(event) = event_from_transaction(&trans, contract) {
+                view.apply(&event);
+            }
+        }
+
+        let (cols, rows) = terminal::size().map_err(|e| e.to_string())?;
+        let (cols, rows) = (cols as usize, rows as usize);
+        view.refresh(backend, cols, rows)?;
+        draw(&view.render(cols, rows)).map_err(|e| e.to_string())?;
+    }
+}
+
+/// Spawn in the middle of the grid, where the opponents are
+fn spawn(backend: &mut dyn Backend, key: &EdDSAKeyPair, view: &mut View) -> Result<(), String> {
+    let middle = ContractValue::U64((GRID_ORIGIN + GRID_COUNT / 2 * CELL_WIDTH) as u64);
+    let code = code(backend.call(key, "spawn_player", &[middle, middle])?);
+    if code != 0 {
+        view.note(format!("spawn_player returned {}", code));
+    }
+    Ok(())
+}
+
+fn draw(lines: &[String]) -> crossterm::Result<()> {
+    let mut stdout = io::stdout();
+    for (row, line) in lines.iter().enumerate() {
+        queue!(
+            stdout,
+            MoveTo(0, row as u16),
+            Print(line),
+            Clear(ClearType::UntilNewLine)
+        )?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
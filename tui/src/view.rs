@@ -0,0 +1,321 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use p2pio::tools::Backend;
+use p2pio::{Event, EventKind};
+use p2pio_contract::{cells_between, heading::PlayerHeading, Cell, GRID_COUNT, GRID_ORIGIN};
+
+use rustdag_lib::dag::contract::ContractValue;
+
+/// Event lines kept for the bottom of the screen
+const LOG_LINES: usize = 4;
+
+struct PlayerView {
+    /// Order the player was first seen in, which picks their letter
+    order: usize,
+    x: i64,
+    y: i64,
+    heading: PlayerHeading,
+    alive: bool,
+    area: i64,
+    /// Cells passed through since the player was last on their own
+    /// territory. The contract doesn't expose trails, so this is pieced
+    /// together from the positions seen on each refresh.
+    trail: Vec<Cell>,
+}
+
+/// What one player in game 0 can see of the board, built from the contract's
+/// event stream and its getters
+pub struct View {
+    me: u64,
+    players: BTreeMap<u64, PlayerView>,
+    owners: HashMap<Cell, u64>,
+    /// Cells `owners` was read for, as left, top, right and bottom
+    loaded: Option<(i64, i64, i64, i64)>,
+    log: VecDeque<String>,
+}
+
+/// Lines of a screen `height` high left for the board, under the status
+/// line and above the event log
+fn board_rows(height: usize) -> usize {
+    height.saturating_sub(LOG_LINES + 1)
+}
+
+fn arg(value: i64) -> ContractValue {
+    ContractValue::U64(value as u64)
+}
+
+impl View {
+    pub fn new(me: u64) -> Self {
+        View {
+            me,
+            players: BTreeMap::new(),
+            owners: HashMap::new(),
+            loaded: None,
+            log: VecDeque::new(),
+        }
+    }
+
+    pub fn apply(&mut self, event: &Event) {
+        let id = match event.id().parse::<u64>() {
+            Ok(id) => id,
+            Err(_) => return,
+        };
+        match event.kind() {
+            EventKind::Spawn => {
+                let (x, y) = event.spawn_point().expect("Spawn events have a point");
+                let (x, y) = (GRID_ORIGIN + i64::from(x), GRID_ORIGIN + i64::from(y));
+                let order = self.players.len();
+                let player = self.players.entry(id).or_insert(PlayerView {
+                    order,
+                    x,
+                    y,
+                    heading: PlayerHeading::NoHeading,
+                    alive: true,
+                    area: 0,
+                    trail: Vec::new(),
+                });
+                player.x = x;
+                player.y = y;
+                player.alive = true;
+                player.trail.clear();
+            }
+            EventKind::Input => {
+                if let Some(player) = self.players.get_mut(&id) {
+                    let heading = event.input_heading().expect("Input events have a heading");
+                    player.heading = PlayerHeading::from(i64::from(heading));
+                }
+            }
+            _ => (),
+        }
+        let line = format!(
+            "{} {:?} {}",
+            event.timestamp(),
+            event.kind(),
+            self.symbol(id)
+        );
+        self.note(line);
+    }
+
+    /// Add a line to the event log at the bottom of the screen
+    pub fn note(&mut self, line: String) {
+        self.log.push_back(line);
+        if self.log.len() > LOG_LINES {
+            self.log.pop_front();
+        }
+    }
+
+    /// Read positions for every player the events have told us about, and
+    /// territory around us if anyone's area changed or we've moved out of
+    /// the cells last read. Territory is read with a margin of half a screen
+    /// of `width` x `height`, since every cell is a separate query.
+    pub fn refresh(
+        &mut self,
+        backend: &mut dyn Backend,
+        width: usize,
+        height: usize,
+    ) -> Result<(), String> {
+        let mut captured = false;
+        let ids = self.players.keys().copied().collect::<Vec<_>>();
+        for id in ids {
+            let id_arg = [arg(id as i64)];
+            let alive = backend.query("get_player_alive", &id_arg)? != 0;
+            let x = backend.query("get_player_x", &id_arg)?;
+            let y = backend.query("get_player_y", &id_arg)?;
+            let area = backend.query("get_player_area", &id_arg)?;
+            let player = &self.players[&id];
+            captured |= area != player.area;
+            self.update_player(id, x, y, alive, area);
+        }
+        let (cols, rows) = (width, board_rows(height));
+        let (left, top) = self.corner(cols, rows);
+        let (right, bottom) = (left + cols as i64, top + rows as i64);
+        let in_loaded = match self.loaded {
+            Some((l, t, r, b)) => l <= left && t <= top && right <= r && bottom <= b,
+            None => false,
+        };
+        if captured || !in_loaded {
+            let (margin_x, margin_y) = (cols as i64 / 2, rows as i64 / 2);
+            let (left, top) = (left - margin_x, top - margin_y);
+            let (right, bottom) = (right + margin_x, bottom + margin_y);
+            self.owners.clear();
+            self.loaded = Some((left, top, right, bottom));
+            for row in top..bottom {
+                for col in left..right {
+                    let cell = Cell { col, row };
+                    if !cell.in_bounds() {
+                        continue;
+                    }
+                    let owner = backend.query("get_cell_owner", &[arg(0), arg(col), arg(row)])?;
+                    if owner != 0 {
+                        self.owners.insert(cell, owner as u64);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn update_player(&mut self, id: u64, x: i64, y: i64, alive: bool, area: i64) {
+        let owners = &self.owners;
+        let player = match self.players.get_mut(&id) {
+            Some(player) => player,
+            None => return,
+        };
+        if alive && player.alive {
+            // The player may have turned since we last looked, so go along
+            // one axis and then the other
+            let corner = (x, player.y);
+            player
+                .trail
+                .extend(cells_between((player.x, player.y), corner));
+            player.trail.extend(cells_between(corner, (x, y)));
+        }
+        if !alive || Cell::at(x, y).and_then(|cell| owners.get(&cell)) == Some(&id) {
+            player.trail.clear();
+        }
+        player.x = x;
+        player.y = y;
+        player.alive = alive;
+        player.area = area;
+    }
+
+    /// Top left cell of a `cols` x `rows` window centred on us, or on the
+    /// middle of the grid while we're dead
+    fn corner(&self, cols: usize, rows: usize) -> (i64, i64) {
+        let centre = match self.players.get(&self.me) {
+            Some(player) if player.alive => Cell::at(player.x, player.y),
+            _ => None,
+        }
+        .unwrap_or(Cell {
+            col: GRID_COUNT / 2,
+            row: GRID_COUNT / 2,
+        });
+        (centre.col - cols as i64 / 2, centre.row - rows as i64 / 2)
+    }
+
+    /// Letter for a player, upper case for their head and lower case for
+    /// their territory. We're always `@`.
+    fn symbol(&self, id: u64) -> char {
+        if id == self.me {
+            return '@';
+        }
+        match self.players.get(&id) {
+            Some(player) => (b'A' + (player.order % 26) as u8) as char,
+            None => '?',
+        }
+    }
+
+    /// The screen as lines of `width` characters: a status line, the board
+    /// and the latest events
+    pub fn render(&self, width: usize, height: usize) -> Vec<String> {
+        let rows = board_rows(height);
+        let (left, top) = self.corner(width, rows);
+        let mut board = vec![vec![' '; width]; rows];
+        for (r, line) in board.iter_mut().enumerate() {
+            for (c, ch) in line.iter_mut().enumerate() {
+                let cell = Cell {
+                    col: left + c as i64,
+                    row: top + r as i64,
+                };
+                if !cell.in_bounds() {
+                    continue;
+                }
+                *ch = match self.owners.get(&cell) {
+                    Some(&owner) if owner == self.me => '#',
+                    Some(&owner) => self.symbol(owner).to_ascii_lowercase(),
+                    None => '.',
+                };
+            }
+        }
+
+        let mut plot = |cell: Cell, ch: char| {
+            let (c, r) = (cell.col - left, cell.row - top);
+            if 0 <= c && (c as usize) < width && 0 <= r && (r as usize) < rows {
+                board[r as usize][c as usize] = ch;
+            }
+        };
+        let alive = self.players.iter().filter(|(_, player)| player.alive);
+        for (&id, player) in alive.clone() {
+            let ch = if id == self.me { 'o' } else { '+' };
+            for &cell in &player.trail {
+                plot(cell, ch);
+            }
+        }
+        for (&id, player) in alive {
+            if let Some(cell) = Cell::at(player.x, player.y) {
+                plot(cell, self.symbol(id));
+            }
+        }
+
+        let mut lines = vec![self.status()];
+        lines.extend(board.into_iter().map(|line| line.into_iter().collect()));
+        lines.extend(self.log.iter().cloned());
+        lines.resize(height, String::new());
+        for line in lines.iter_mut() {
+            if let Some((end, _)) = line.char_indices().nth(width) {
+                line.truncate(end);
+            }
+        }
+        lines
+    }
+
+    fn status(&self) -> String {
+        let alive = self.players.values().filter(|player| player.alive).count();
+        match self.players.get(&self.me) {
+            Some(player) if player.alive => format!(
+                "@ {} at ({}, {}) heading {:?}, area {}. {} alive. Arrows turn, space stops, q quits.",
+                self.me,
+                player.x - GRID_ORIGIN,
+                player.y - GRID_ORIGIN,
+                player.heading,
+                player.area,
+                alive
+            ),
+            _ => format!(
+                "@ {} is dead. {} alive. Press enter to spawn, q to quit.",
+                self.me, alive
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use p2pio_contract::CELL_WIDTH;
+
+    fn spawn(id: u64, col: i32, row: i32) -> Event {
+        let width = CELL_WIDTH as i32;
+        Event::spawn(id.to_string(), col * width, row * width, 0)
+    }
+
+    #[test]
+    fn test_render_follows_player() {
+        let mut view = View::new(1);
+        view.apply(&spawn(1, 100, 100));
+        view.apply(&spawn(2, 103, 100));
+        view.owners.insert(Cell { col: 100, row: 101 }, 1);
+        view.owners.insert(Cell { col: 103, row: 101 }, 2);
+
+        let lines = view.render(9, 5 + LOG_LINES + 1);
+        assert_eq!(lines.len(), 5 + LOG_LINES + 1);
+        assert_eq!(lines[3], "....@..B.");
+        assert_eq!(lines[4], "....#..b.");
+        assert!(lines[LOG_LINES + 2..]
+            .iter()
+            .any(|line| line.ends_with("Spawn B")));
+
+        // Walking two cells up off our territory leaves a trail, and the view
+        // stays centred on us
+        let x = GRID_ORIGIN + 100 * CELL_WIDTH;
+        view.update_player(1, x, GRID_ORIGIN + 98 * CELL_WIDTH, true, 1);
+        let lines = view.render(9, 5 + LOG_LINES + 1);
+        assert_eq!(lines[3], "....@....");
+        assert_eq!(lines[4], "....o....");
+        assert_eq!(lines[5], ".......B.");
+
+        view.update_player(1, x, GRID_ORIGIN + 98 * CELL_WIDTH, false, 0);
+        assert!(view.render(9, 5 + LOG_LINES + 1)[0].contains("is dead"));
+    }
+}